@@ -16,11 +16,16 @@ CREATE TABLE IF NOT EXISTS bookmarks (
     id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
     title VARCHAR(500) NOT NULL,
     url TEXT NOT NULL,
+    description TEXT,
     tags TEXT[],
     folder VARCHAR(255),
     ai_summary TEXT,
     created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    search_vector TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', coalesce(title, '')), 'A') ||
+        setweight(to_tsvector('english', coalesce(description, '') || ' ' || coalesce(ai_summary, '')), 'B')
+    ) STORED
 );
 
 -- Workspaces table
@@ -66,14 +71,44 @@ CREATE TABLE IF NOT EXISTS chat_messages (
     timestamp TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
 );
 
+-- Persistent multi-turn AI conversations
+CREATE TABLE IF NOT EXISTS ai_conversations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    title VARCHAR(255),
+    messages JSONB NOT NULL DEFAULT '[]',
+    model VARCHAR(100),
+    provider VARCHAR(100),
+    context_strategy VARCHAR(32) NOT NULL DEFAULT 'drop_oldest',
+    rolling_summary JSONB,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Token usage and estimated cost of every completed AI request
+CREATE TABLE IF NOT EXISTS ai_usage (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    provider VARCHAR(100) NOT NULL,
+    model VARCHAR(100) NOT NULL,
+    feature VARCHAR(32) NOT NULL,
+    input_tokens INTEGER NOT NULL DEFAULT 0,
+    output_tokens INTEGER NOT NULL DEFAULT 0,
+    cost_usd DOUBLE PRECISION,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
 -- Content analysis table
 CREATE TABLE IF NOT EXISTS content_analysis (
     id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
     url TEXT NOT NULL,
+    title VARCHAR(1000),
     content_hash VARCHAR(64),
-    analysis JSONB NOT NULL,
+    model VARCHAR(100),
+    analysis_data JSONB NOT NULL,
     created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
-    UNIQUE(url, content_hash)
+    search_vector TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', coalesce(title, '')), 'A') ||
+        setweight(to_tsvector('english', coalesce(analysis_data->>'summary', '')), 'B')
+    ) STORED
 );
 
 -- Search history table
@@ -125,6 +160,10 @@ CREATE TABLE IF NOT EXISTS page_content_cache (
     metadata JSONB,
     extracted_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
     expires_at TIMESTAMP WITH TIME ZONE DEFAULT (CURRENT_TIMESTAMP + INTERVAL '24 hours'),
+    search_vector TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', coalesce(title, '')), 'A') ||
+        setweight(to_tsvector('english', coalesce(content, '')), 'C')
+    ) STORED,
     UNIQUE(url)
 );
 
@@ -134,12 +173,20 @@ CREATE INDEX IF NOT EXISTS idx_bookmarks_created_at ON bookmarks(created_at DESC
 CREATE INDEX IF NOT EXISTS idx_bookmarks_tags ON bookmarks USING GIN(tags);
 CREATE INDEX IF NOT EXISTS idx_browser_tabs_workspace_id ON browser_tabs(workspace_id);
 CREATE INDEX IF NOT EXISTS idx_chat_messages_chat_id ON chat_messages(chat_id);
+CREATE INDEX IF NOT EXISTS idx_ai_conversations_updated_at ON ai_conversations(updated_at DESC);
+CREATE INDEX IF NOT EXISTS idx_ai_usage_created_at ON ai_usage(created_at DESC);
 CREATE INDEX IF NOT EXISTS idx_content_analysis_url ON content_analysis(url);
 CREATE INDEX IF NOT EXISTS idx_search_history_created_at ON search_history(created_at DESC);
 CREATE INDEX IF NOT EXISTS idx_integration_logs_integration_name ON integration_logs(integration_name);
 CREATE INDEX IF NOT EXISTS idx_page_content_cache_url ON page_content_cache(url);
 CREATE INDEX IF NOT EXISTS idx_page_content_cache_expires_at ON page_content_cache(expires_at);
 
+-- Full-text search over bookmarks, visited pages, analyses and notes
+CREATE INDEX IF NOT EXISTS idx_bookmarks_search ON bookmarks USING GIN(search_vector);
+CREATE INDEX IF NOT EXISTS idx_content_analysis_search ON content_analysis USING GIN(search_vector);
+CREATE INDEX IF NOT EXISTS idx_page_content_cache_search ON page_content_cache USING GIN(search_vector);
+CREATE INDEX IF NOT EXISTS idx_user_data_notes_search ON user_data USING GIN(to_tsvector('english', value)) WHERE category = 'note';
+
 -- Create functions for automatic timestamp updates
 CREATE OR REPLACE FUNCTION update_updated_at_column()
 RETURNS TRIGGER AS $$
@@ -154,6 +201,7 @@ CREATE TRIGGER update_user_data_updated_at BEFORE UPDATE ON user_data FOR EACH R
 CREATE TRIGGER update_bookmarks_updated_at BEFORE UPDATE ON bookmarks FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
 CREATE TRIGGER update_workspaces_updated_at BEFORE UPDATE ON workspaces FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
 CREATE TRIGGER update_ai_chats_updated_at BEFORE UPDATE ON ai_chats FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+CREATE TRIGGER update_ai_conversations_updated_at BEFORE UPDATE ON ai_conversations FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
 CREATE TRIGGER update_integration_logs_updated_at BEFORE UPDATE ON integration_logs FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
 CREATE TRIGGER update_user_preferences_updated_at BEFORE UPDATE ON user_preferences FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
 
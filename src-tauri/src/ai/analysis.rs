@@ -0,0 +1,257 @@
+use super::cache::content_hash;
+use super::models::{AIMessage, ResponseSchema};
+use super::prompts::PromptTemplate;
+use super::service::AIServiceManager;
+use crate::error::ThetaError;
+use crate::models::{AIFeature, AnalysisDiff, AnalysisSource, ContentAnalysis, StoredAnalysis};
+
+/// One retry: the second attempt is told what was wrong with the first.
+const MAX_ATTEMPTS: usize = 2;
+
+pub fn analysis_schema() -> ResponseSchema {
+    ResponseSchema {
+        name: "content_analysis".to_string(),
+        description: "Record the analysis of a web page".to_string(),
+        schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "summary": { "type": "string", "description": "Two or three sentence summary" },
+                "keywords": { "type": "array", "items": { "type": "string" }, "description": "Up to ten key terms" },
+                "sentiment": { "type": "number", "description": "Overall sentiment from -1 (negative) to 1 (positive)" },
+                "topics": { "type": "array", "items": { "type": "string" }, "description": "Up to five broad topics" },
+                "complexity_score": { "type": "number", "description": "Reading difficulty from 0 (simple) to 1 (expert)" }
+            },
+            "required": ["summary", "keywords", "sentiment", "topics", "complexity_score"],
+            "additionalProperties": false
+        }),
+    }
+}
+
+/// Runs the `content_analysis` prompt template against `content`.
+pub async fn analyze(
+    services: &AIServiceManager,
+    template: &PromptTemplate,
+    content: &str,
+) -> Result<ContentAnalysis, ThetaError> {
+    let mut request = template.request(&[("content", content)], AIFeature::Analysis)?;
+    request.response_schema = Some(analysis_schema());
+
+    let mut last_error = String::new();
+    let mut provider: Option<String> = None;
+    for _ in 0..MAX_ATTEMPTS {
+        // The corrective retry goes back to whichever provider answered first
+        let response = services.complete_with_failover(provider.as_deref(), &request).await?;
+        provider = Some(response.provider.clone());
+
+        match parse_analysis(&response.content) {
+            Ok(mut analysis) => {
+                // Word count gives a better reading time than the model's guess
+                analysis.reading_time = estimate_reading_time(content);
+                analysis.source = AnalysisSource::Model;
+                analysis.content_hash = Some(content_hash(content));
+                analysis.model = Some(response.model);
+                analysis.provider = Some(response.provider);
+                return Ok(analysis);
+            }
+            Err(e) => {
+                log::warn!("{} returned an invalid content analysis: {}", response.provider, e);
+                request.messages.push(AIMessage::new("assistant".to_string(), response.content));
+                request.messages.push(AIMessage::new(
+                    "user".to_string(),
+                    format!("That response did not match the schema ({}). Reply again with only the corrected JSON object.", e),
+                ));
+                last_error = e;
+            }
+        }
+    }
+
+    // Models are nondeterministic, so asking again may well succeed
+    Err(ThetaError::Ai {
+        message: format!("Invalid content analysis after {} attempts: {}", MAX_ATTEMPTS, last_error),
+        retryable: true,
+    })
+}
+
+pub fn parse_analysis(raw: &str) -> Result<ContentAnalysis, String> {
+    // Some models wrap JSON in a markdown fence even when asked not to
+    let json = raw
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+
+    let analysis: ContentAnalysis = serde_json::from_str(json).map_err(|e| e.to_string())?;
+
+    if analysis.summary.trim().is_empty() {
+        return Err("summary is empty".to_string());
+    }
+    if !(-1.0..=1.0).contains(&analysis.sentiment) {
+        return Err(format!("sentiment {} is outside -1..1", analysis.sentiment));
+    }
+    if !(0.0..=1.0).contains(&analysis.complexity_score) {
+        return Err(format!("complexity_score {} is outside 0..1", analysis.complexity_score));
+    }
+
+    Ok(analysis)
+}
+
+/// Word-list heuristics for when no provider is configured. The result is
+/// marked `AnalysisSource::Heuristic` so the UI can say so.
+pub fn heuristic_analysis(content: &str) -> ContentAnalysis {
+    ContentAnalysis {
+        summary: content.chars().take(200).collect(),
+        keywords: extract_keywords(content),
+        sentiment: extract_sentiment(content),
+        topics: vec![],
+        reading_time: estimate_reading_time(content),
+        complexity_score: calculate_complexity_score(content),
+        source: AnalysisSource::Heuristic,
+        content_hash: Some(content_hash(content)),
+        model: None,
+        provider: None,
+    }
+}
+
+/// Compares two saved analyses of the same page. Keywords and topics are
+/// compared case-insensitively.
+pub fn diff_analyses(previous: StoredAnalysis, current: StoredAnalysis) -> AnalysisDiff {
+    let (before, after) = (&previous.analysis, &current.analysis);
+
+    AnalysisDiff {
+        content_changed: previous.content_hash != current.content_hash,
+        summary_changed: before.summary.trim() != after.summary.trim(),
+        keywords_added: missing_from(&after.keywords, &before.keywords),
+        keywords_removed: missing_from(&before.keywords, &after.keywords),
+        topics_added: missing_from(&after.topics, &before.topics),
+        topics_removed: missing_from(&before.topics, &after.topics),
+        sentiment_change: after.sentiment - before.sentiment,
+        complexity_change: after.complexity_score - before.complexity_score,
+        reading_time_change: i64::from(after.reading_time) - i64::from(before.reading_time),
+        previous,
+        current,
+    }
+}
+
+/// Entries of `items` that do not appear in `other`.
+fn missing_from(items: &[String], other: &[String]) -> Vec<String> {
+    items
+        .iter()
+        .filter(|item| !other.iter().any(|o| o.eq_ignore_ascii_case(item)))
+        .cloned()
+        .collect()
+}
+
+fn extract_keywords(content: &str) -> Vec<String> {
+    content.split_whitespace()
+        .filter(|word| word.len() > 4)
+        .take(10)
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+fn extract_sentiment(content: &str) -> f32 {
+    let positive_words = ["good", "great", "excellent", "amazing", "wonderful"];
+    let negative_words = ["bad", "terrible", "awful", "horrible", "disappointing"];
+    
+    let words: Vec<&str> = content.split_whitespace().collect();
+    let positive_count = words.iter().filter(|word| positive_words.contains(&word.to_lowercase().as_str())).count();
+    let negative_count = words.iter().filter(|word| negative_words.contains(&word.to_lowercase().as_str())).count();
+    
+    if positive_count + negative_count == 0 {
+        0.0
+    } else {
+        (positive_count as f32 - negative_count as f32) / (positive_count + negative_count) as f32
+    }
+}
+
+fn estimate_reading_time(content: &str) -> u32 {
+    let words = content.split_whitespace().count();
+    ((words as f32 / 200.0).ceil() as u32).max(1)
+}
+
+fn calculate_complexity_score(content: &str) -> f32 {
+    let word_count = content.split_whitespace().count();
+    if word_count == 0 {
+        return 0.0;
+    }
+
+    let avg_word_length = content.split_whitespace()
+        .map(|word| word.len())
+        .sum::<usize>() as f32 / word_count as f32;
+    
+    (avg_word_length / 10.0).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: &str = r#"{"summary":"A page","keywords":["rust"],"sentiment":0.2,"topics":["programming"],"complexity_score":0.4}"#;
+
+    #[test]
+    fn parses_plain_and_fenced_json() {
+        assert_eq!(parse_analysis(VALID).unwrap().summary, "A page");
+        assert_eq!(parse_analysis(&format!("```json\n{}\n```", VALID)).unwrap().keywords, vec!["rust"]);
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        for raw in ["", "not json", "{\"summary\":", "[]", "null"] {
+            assert!(parse_analysis(raw).is_err(), "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn rejects_missing_fields() {
+        assert!(parse_analysis(r#"{"summary":"A page","keywords":[]}"#).is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_scores() {
+        let blank = VALID.replace("A page", " ");
+        let sentiment = VALID.replace("0.2", "3.0");
+        let complexity = VALID.replace("0.4", "-1");
+
+        assert!(parse_analysis(&blank).unwrap_err().contains("summary"));
+        assert!(parse_analysis(&sentiment).unwrap_err().contains("sentiment"));
+        assert!(parse_analysis(&complexity).unwrap_err().contains("complexity_score"));
+    }
+
+    #[test]
+    fn heuristics_handle_empty_content() {
+        for content in ["", "   \n\t"] {
+            let analysis = heuristic_analysis(content);
+            assert_eq!(analysis.complexity_score, 0.0);
+            assert_eq!(analysis.sentiment, 0.0);
+            assert!(analysis.keywords.is_empty());
+        }
+    }
+
+    #[test]
+    fn diff_reports_added_and_removed_terms() {
+        let stored = |hash: &str, analysis: ContentAnalysis| StoredAnalysis {
+            id: uuid::Uuid::new_v4(),
+            url: "https://example.com/article".to_string(),
+            title: None,
+            content_hash: Some(hash.to_string()),
+            model: None,
+            analysis,
+            created_at: chrono::Utc::now(),
+        };
+        let before = parse_analysis(VALID).unwrap();
+        let mut after = before.clone();
+        after.keywords = vec!["Rust".to_string(), "tokio".to_string()];
+        after.topics = vec![];
+        after.sentiment = -0.3;
+
+        let diff = diff_analyses(stored("a", before), stored("b", after));
+
+        assert!(diff.content_changed);
+        assert!(!diff.summary_changed);
+        assert_eq!(diff.keywords_added, vec!["tokio"]);
+        assert!(diff.keywords_removed.is_empty());
+        assert_eq!(diff.topics_removed, vec!["programming"]);
+        assert!((diff.sentiment_change + 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,413 @@
+use super::models::{AIMessage, AIProvider, CompletionRequest, TokenUsage};
+use super::providers::{check_status, CompletionStream, LlmProvider, ProviderError, ProviderErrorKind};
+use super::streaming::{self, SseEvent, StreamUpdate};
+use crate::models::{AIResponse, ToolCall};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::Value;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+pub struct AnthropicProvider {
+    config: AIProvider,
+    client: Client,
+}
+
+impl AnthropicProvider {
+    pub fn new(config: AIProvider, client: Client) -> Self {
+        Self { config, client }
+    }
+
+    fn api_key(&self) -> Result<&str, ProviderError> {
+        self.config.api_key
+            .as_deref()
+            .filter(|key| !key.trim().is_empty())
+            .ok_or_else(|| ProviderError::new(self.name(), ProviderErrorKind::NotConfigured, "API key not set"))
+    }
+
+    fn build_payload(&self, request: &CompletionRequest) -> Value {
+        // Anthropic takes system prompts as a top-level field rather than a message role
+        let system: Vec<&str> = request.messages
+            .iter()
+            .filter(|message| message.role == "system")
+            .map(|message| message.content.as_str())
+            .collect();
+
+        let mut messages: Vec<Value> = Vec::new();
+        for message in request.messages.iter().filter(|message| message.role != "system") {
+            let json = message_json(message);
+            // Results of parallel tool calls must arrive together in one user turn
+            match messages.last_mut() {
+                Some(last) if message.tool_call_id.is_some() && last["content"][0]["type"] == "tool_result" => {
+                    if let Some(blocks) = last["content"].as_array_mut() {
+                        blocks.extend(json["content"].as_array().cloned().unwrap_or_default());
+                    }
+                }
+                _ => messages.push(json),
+            }
+        }
+
+        let mut payload = serde_json::json!({
+            "model": request.model_or(self.default_model()),
+            "max_tokens": request.max_tokens.unwrap_or(1000),
+            "messages": messages
+        });
+
+        if let Some(temperature) = request.temperature {
+            payload["temperature"] = serde_json::json!(temperature);
+        }
+        if !system.is_empty() {
+            payload["system"] = Value::String(system.join("\n\n"));
+        }
+        // Structured output is done by forcing a single tool call whose input is the object
+        if let Some(schema) = &request.response_schema {
+            payload["tools"] = serde_json::json!([{
+                "name": schema.name,
+                "description": schema.description,
+                "input_schema": schema.schema
+            }]);
+            payload["tool_choice"] = serde_json::json!({ "type": "tool", "name": schema.name });
+        } else if !request.tools.is_empty() {
+            payload["tools"] = request.tools
+                .iter()
+                .map(|tool| serde_json::json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "input_schema": tool.parameters
+                }))
+                .collect();
+        }
+
+        payload
+    }
+}
+
+/// Tool calls are `tool_use` blocks of an assistant turn, and their results
+/// `tool_result` blocks of a user turn.
+fn message_json(message: &AIMessage) -> Value {
+    if let Some(tool_call_id) = &message.tool_call_id {
+        return serde_json::json!({
+            "role": "user",
+            "content": [{ "type": "tool_result", "tool_use_id": tool_call_id, "content": message.content }]
+        });
+    }
+    if message.tool_calls.is_empty() {
+        return serde_json::json!({
+            "role": message.role,
+            "content": message.content
+        });
+    }
+
+    let mut blocks = Vec::new();
+    if !message.content.trim().is_empty() {
+        blocks.push(serde_json::json!({ "type": "text", "text": message.content }));
+    }
+    blocks.extend(message.tool_calls.iter().map(|call| serde_json::json!({
+        "type": "tool_use",
+        "id": call.id,
+        "name": call.name,
+        "input": call.arguments
+    })));
+    serde_json::json!({ "role": "assistant", "content": blocks })
+}
+
+#[async_trait]
+impl LlmProvider for AnthropicProvider {
+    fn config(&self) -> &AIProvider {
+        &self.config
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<AIResponse, ProviderError> {
+        let start_time = std::time::Instant::now();
+
+        let response = self.client
+            .post(format!("{}/messages", self.config.endpoint))
+            .header("x-api-key", self.api_key()?)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .header("Content-Type", "application/json")
+            .json(&self.build_payload(request))
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+        let response = check_status(self.name(), response).await?;
+
+        let response_json: Value = response.json().await.map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+
+        let structured = request.response_schema.is_some();
+        let (content, tool_calls, usage) = parse_completion(self.name(), &response_json, structured)?;
+
+        Ok(AIResponse {
+            content,
+            model: request.model_or(self.default_model()).to_string(),
+            provider: self.name().to_string(),
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            tokens_used: usage.total(),
+            cost_usd: None,
+            processing_time: start_time.elapsed().as_millis() as u64,
+            confidence: None,
+            tool_calls,
+            run_id: None,
+            raw: Some(response_json),
+        })
+    }
+
+    async fn stream(&self, request: &CompletionRequest) -> Result<CompletionStream, ProviderError> {
+        let start_time = std::time::Instant::now();
+
+        let mut payload = self.build_payload(request);
+        payload["stream"] = Value::Bool(true);
+
+        let response = self.client
+            .post(format!("{}/messages", self.config.endpoint))
+            .header("x-api-key", self.api_key()?)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .header("Content-Type", "application/json")
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+        let response = check_status(self.name(), response).await?;
+
+        let model = request.model_or(self.default_model()).to_string();
+        Ok(streaming::completion_stream(streaming::sse_events(response), model, self.name().to_string(), start_time, parse_stream_event))
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+        let response = self.client
+            .get(format!("{}/models", self.config.endpoint))
+            .header("x-api-key", self.api_key()?)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+        let response = check_status(self.name(), response).await?;
+
+        let json: Value = response.json().await.map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+
+        let models = json["data"]
+            .as_array()
+            .ok_or_else(|| ProviderError::invalid_response(self.name(), "Response contained no model list"))?;
+
+        Ok(models
+            .iter()
+            .filter_map(|model| model["id"].as_str().map(|id| id.to_string()))
+            .collect())
+    }
+}
+
+/// Pulls the reply out of a Messages API body: the forced tool call's input
+/// when structured output was requested, otherwise the text blocks and any
+/// tool calls. The text may be empty only when tools were called.
+fn parse_completion(provider: &str, json: &Value, structured: bool) -> Result<(String, Vec<ToolCall>, TokenUsage), ProviderError> {
+    if json["type"] == "error" || json["error"].is_object() {
+        let message = json["error"]["message"].as_str().unwrap_or("unknown error");
+        return Err(ProviderError::invalid_response(provider, format!("Provider returned an error: {}", message)));
+    }
+
+    let blocks = json["content"]
+        .as_array()
+        .ok_or_else(|| ProviderError::invalid_response(provider, "Response contained no content blocks"))?;
+
+    let mut tool_calls = Vec::new();
+    for block in blocks.iter().filter(|block| block["type"] == "tool_use") {
+        if !block["input"].is_object() {
+            return Err(ProviderError::invalid_response(provider, "Tool call had no input object"));
+        }
+        tool_calls.push(ToolCall {
+            id: block["id"].as_str().unwrap_or_default().to_string(),
+            name: block["name"].as_str().unwrap_or_default().to_string(),
+            arguments: block["input"].clone(),
+        });
+    }
+
+    let content = match tool_calls.first() {
+        Some(call) if structured => call.arguments.to_string(),
+        _ => blocks
+            .iter()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect::<String>(),
+    };
+    if structured {
+        tool_calls.clear();
+    }
+
+    if content.trim().is_empty() && tool_calls.is_empty() {
+        return Err(ProviderError::invalid_response(provider, "Response contained no text"));
+    }
+
+    Ok((content, tool_calls, usage(&json["usage"])))
+}
+
+fn usage(usage: &Value) -> TokenUsage {
+    TokenUsage {
+        input_tokens: usage["input_tokens"].as_u64().unwrap_or(0) as u32,
+        output_tokens: usage["output_tokens"].as_u64().unwrap_or(0) as u32,
+    }
+}
+
+fn parse_stream_event(event: &SseEvent) -> Result<StreamUpdate, String> {
+    match event.event.as_deref() {
+        // Input tokens are only reported up front
+        Some("message_start") => {
+            let json: Value = serde_json::from_str(&event.data).map_err(|e| e.to_string())?;
+            Ok(StreamUpdate {
+                usage: Some(usage(&json["message"]["usage"])),
+                ..Default::default()
+            })
+        }
+        Some("content_block_delta") => {
+            let json: Value = serde_json::from_str(&event.data).map_err(|e| e.to_string())?;
+            Ok(StreamUpdate {
+                delta: json["delta"]["text"].as_str().map(|s| s.to_string()),
+                ..Default::default()
+            })
+        }
+        Some("message_delta") => {
+            let json: Value = serde_json::from_str(&event.data).map_err(|e| e.to_string())?;
+            Ok(StreamUpdate {
+                usage: Some(usage(&json["usage"])),
+                ..Default::default()
+            })
+        }
+        Some("message_stop") => Ok(StreamUpdate { done: true, ..Default::default() }),
+        Some("error") => {
+            let json: Value = serde_json::from_str(&event.data).unwrap_or_default();
+            Err(json["error"]["message"].as_str().unwrap_or("Anthropic stream error").to_string())
+        }
+        // content_block_start/stop and ping carry nothing we surface
+        _ => Ok(StreamUpdate::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_text_blocks() {
+        let body = json!({
+            "type": "message",
+            "content": [{ "type": "text", "text": "Hello" }, { "type": "text", "text": " there" }],
+            "usage": { "input_tokens": 5, "output_tokens": 3 }
+        });
+
+        let (content, _, usage) = parse_completion("anthropic", &body, false).unwrap();
+        assert_eq!(content, "Hello there");
+        assert_eq!(usage, TokenUsage { input_tokens: 5, output_tokens: 3 });
+    }
+
+    #[test]
+    fn prefers_tool_input_for_structured_output() {
+        let body = json!({
+            "content": [
+                { "type": "text", "text": "Here you go" },
+                { "type": "tool_use", "name": "content_analysis", "input": { "summary": "s" } }
+            ]
+        });
+
+        let (content, tool_calls, _) = parse_completion("anthropic", &body, true).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&content).unwrap(), json!({ "summary": "s" }));
+        assert!(tool_calls.is_empty());
+    }
+
+    #[test]
+    fn returns_tool_calls_alongside_text() {
+        let body = json!({
+            "content": [
+                { "type": "text", "text": "Let me look that up." },
+                { "type": "tool_use", "id": "toolu_1", "name": "search_web", "input": { "query": "rust" } }
+            ]
+        });
+
+        let (content, tool_calls, _) = parse_completion("anthropic", &body, false).unwrap();
+        assert_eq!(content, "Let me look that up.");
+        assert_eq!(tool_calls, vec![ToolCall {
+            id: "toolu_1".to_string(),
+            name: "search_web".to_string(),
+            arguments: json!({ "query": "rust" }),
+        }]);
+    }
+
+    #[test]
+    fn groups_parallel_tool_results_into_one_turn() {
+        let provider = AnthropicProvider::new(
+            AIProvider::new("anthropic".to_string(), "https://api.anthropic.com/v1".to_string(), None),
+            Client::new(),
+        );
+        let call = |id: &str| ToolCall { id: id.to_string(), name: "open_tab".to_string(), arguments: json!({}) };
+        let mut request = CompletionRequest::from_prompt("Open two tabs");
+        request.messages.extend([
+            AIMessage::tool_request(String::new(), vec![call("a"), call("b")]),
+            AIMessage::tool_result("a".to_string(), "ok".to_string()),
+            AIMessage::tool_result("b".to_string(), "ok".to_string()),
+        ]);
+
+        let messages = provider.build_payload(&request)["messages"].clone();
+        assert_eq!(messages.as_array().unwrap().len(), 3);
+        assert_eq!(messages[1]["content"].as_array().unwrap().len(), 2);
+        assert_eq!(messages[2]["content"][1]["tool_use_id"], "b");
+    }
+
+    #[test]
+    fn rejects_an_error_body() {
+        let body = json!({ "type": "error", "error": { "type": "overloaded_error", "message": "Overloaded" } });
+
+        let error = parse_completion("anthropic", &body, false).unwrap_err();
+        assert_eq!(error.kind, ProviderErrorKind::InvalidResponse);
+        assert!(error.message.contains("Overloaded"));
+    }
+
+    #[test]
+    fn rejects_missing_content() {
+        for body in [json!({}), json!({ "content": null }), json!({ "content": "text" })] {
+            let error = parse_completion("anthropic", &body, false).unwrap_err();
+            assert!(error.message.contains("no content blocks"), "{}", body);
+        }
+    }
+
+    #[test]
+    fn rejects_content_without_text() {
+        for content in [json!([]), json!([{ "type": "text" }]), json!([{ "type": "text", "text": "" }]), json!([{ "type": "image" }])] {
+            let body = json!({ "content": content });
+            let error = parse_completion("anthropic", &body, false).unwrap_err();
+            assert!(error.message.contains("no text"), "{}", body);
+        }
+    }
+
+    #[test]
+    fn rejects_a_tool_call_without_input() {
+        let body = json!({ "content": [{ "type": "tool_use", "name": "content_analysis" }] });
+
+        assert!(parse_completion("anthropic", &body, true).is_err());
+    }
+
+    #[test]
+    fn missing_api_key_is_not_configured() {
+        let provider = AnthropicProvider::new(
+            AIProvider::new("anthropic".to_string(), "https://api.anthropic.com/v1".to_string(), None),
+            Client::new(),
+        );
+
+        assert_eq!(provider.api_key().unwrap_err().kind, ProviderErrorKind::NotConfigured);
+    }
+
+    #[test]
+    fn stream_error_events_fail_the_stream() {
+        let event = SseEvent {
+            event: Some("error".to_string()),
+            data: r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#.to_string(),
+        };
+
+        assert_eq!(parse_stream_event(&event).unwrap_err(), "Overloaded");
+    }
+
+    #[test]
+    fn malformed_stream_deltas_fail_the_stream() {
+        let event = SseEvent { event: Some("content_block_delta".to_string()), data: "{".to_string() };
+
+        assert!(parse_stream_event(&event).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+use super::models::ToolDefinition;
+use super::tools::{parse_arguments, Tool, ToolRegistry};
+use crate::browser::SearchEngine;
+use crate::commands::browser::add_bookmark;
+use crate::error::ThetaError;
+use crate::integrations::productivity::CreateTask;
+use crate::integrations::{N8nIntegration, ProductivityIntegration};
+use crate::models::Bookmark;
+use crate::state::AppState;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Search results returned to the model.
+const SEARCH_RESULTS: usize = 5;
+
+/// The browser's tools: opening tabs, web search and bookmarks, plus tasks
+/// and n8n workflows when those integrations are configured.
+pub fn browser_tools(state: Arc<RwLock<AppState>>, n8n: Option<N8nIntegration>) -> ToolRegistry {
+    let mut tools = ToolRegistry::new();
+    tools.register(Arc::new(OpenTabTool { state: state.clone() }));
+    tools.register(Arc::new(SearchWebTool { engine: SearchEngine::new() }));
+    tools.register(Arc::new(BookmarkPageTool { state }));
+
+    let productivity = ProductivityIntegration::default();
+    if productivity.api_key.is_some() {
+        tools.register(Arc::new(CreateTaskTool { integration: productivity }));
+    }
+    if let Some(integration) = n8n {
+        tools.register(Arc::new(TriggerWorkflowTool { integration }));
+    }
+    tools
+}
+
+fn http_url(url: &str) -> Result<String, ThetaError> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(parsed.to_string()),
+        _ => Err(ThetaError::InvalidInput(format!("'{}' is not an http or https URL", url))),
+    }
+}
+
+struct OpenTabTool {
+    state: Arc<RwLock<AppState>>,
+}
+
+#[derive(Deserialize)]
+struct OpenTabArguments {
+    url: String,
+}
+
+#[async_trait]
+impl Tool for OpenTabTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "open_tab".to_string(),
+            description: "Opens a web page in a new browser tab.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "url": { "type": "string", "description": "Full http or https URL of the page" }
+                },
+                "required": ["url"]
+            }),
+        }
+    }
+
+    /// The URL may come from a web page or search result rather than the
+    /// user, so opening it needs their confirmation.
+    fn has_side_effects(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, arguments: Value) -> Result<Value, ThetaError> {
+        let arguments: OpenTabArguments = parse_arguments(arguments)?;
+        let url = http_url(&arguments.url)?;
+
+        let tab_id = self.state.write().await.create_tab(url.clone(), "Loading...".to_string());
+        Ok(json!({ "tab_id": tab_id, "url": url }))
+    }
+}
+
+struct SearchWebTool {
+    engine: SearchEngine,
+}
+
+#[derive(Deserialize)]
+struct SearchWebArguments {
+    query: String,
+    #[serde(default)]
+    engine: Option<String>,
+}
+
+#[async_trait]
+impl Tool for SearchWebTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "search_web".to_string(),
+            description: "Searches the web and returns the top results with their titles, URLs and snippets.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "engine": { "type": "string", "enum": ["duckduckgo", "google", "bing"] }
+                },
+                "required": ["query"]
+            }),
+        }
+    }
+
+    fn has_side_effects(&self) -> bool {
+        false
+    }
+
+    async fn call(&self, arguments: Value) -> Result<Value, ThetaError> {
+        let arguments: SearchWebArguments = parse_arguments(arguments)?;
+
+        let results = self.engine
+            .search(&arguments.query, arguments.engine.as_deref().unwrap_or("duckduckgo"))
+            .await?;
+        Ok(results
+            .into_iter()
+            .take(SEARCH_RESULTS)
+            .map(|result| json!({ "title": result.title, "url": result.url, "snippet": result.snippet }))
+            .collect())
+    }
+}
+
+struct BookmarkPageTool {
+    state: Arc<RwLock<AppState>>,
+}
+
+#[derive(Deserialize)]
+struct BookmarkPageArguments {
+    url: String,
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    folder: Option<String>,
+}
+
+#[async_trait]
+impl Tool for BookmarkPageTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "bookmark_page".to_string(),
+            description: "Saves a page to the user's bookmarks.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "url": { "type": "string" },
+                    "title": { "type": "string" },
+                    "description": { "type": "string" },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "folder": { "type": "string" }
+                },
+                "required": ["url", "title"]
+            }),
+        }
+    }
+
+    fn has_side_effects(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, arguments: Value) -> Result<Value, ThetaError> {
+        let arguments: BookmarkPageArguments = parse_arguments(arguments)?;
+
+        let bookmark = Bookmark {
+            id: Uuid::new_v4(),
+            title: arguments.title,
+            url: http_url(&arguments.url)?,
+            description: arguments.description,
+            tags: arguments.tags,
+            created_at: chrono::Utc::now(),
+            folder: arguments.folder,
+            ai_summary: None,
+        };
+        let bookmark = add_bookmark(&*self.state.read().await, bookmark).await?;
+        Ok(json!({ "bookmark_id": bookmark.id, "url": bookmark.url }))
+    }
+}
+
+struct CreateTaskTool {
+    integration: ProductivityIntegration,
+}
+
+#[derive(Deserialize)]
+struct CreateTaskArguments {
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    due_date: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    priority: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[async_trait]
+impl Tool for CreateTaskTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "create_task".to_string(),
+            description: format!("Creates a task in the user's {} task list.", self.integration.provider),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string" },
+                    "description": { "type": "string" },
+                    "due_date": { "type": "string", "description": "RFC 3339 date and time" },
+                    "priority": { "type": "string", "enum": ["low", "medium", "high"] },
+                    "tags": { "type": "array", "items": { "type": "string" } }
+                },
+                "required": ["title"]
+            }),
+        }
+    }
+
+    fn has_side_effects(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, arguments: Value) -> Result<Value, ThetaError> {
+        let arguments: CreateTaskArguments = parse_arguments(arguments)?;
+
+        let task = self.integration
+            .create_task(CreateTask {
+                title: arguments.title,
+                description: arguments.description,
+                due_date: arguments.due_date,
+                priority: arguments.priority.unwrap_or_else(|| "medium".to_string()),
+                tags: arguments.tags,
+            })
+            .await?;
+        Ok(json!({ "task_id": task.id, "title": task.title }))
+    }
+}
+
+struct TriggerWorkflowTool {
+    integration: N8nIntegration,
+}
+
+#[derive(Deserialize)]
+struct TriggerWorkflowArguments {
+    workflow_id: String,
+    #[serde(default)]
+    data: Value,
+}
+
+#[async_trait]
+impl Tool for TriggerWorkflowTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "trigger_workflow".to_string(),
+            description: "Runs an n8n automation workflow with the given input data.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "workflow_id": { "type": "string" },
+                    "data": { "type": "object", "description": "Input passed to the workflow" }
+                },
+                "required": ["workflow_id"]
+            }),
+        }
+    }
+
+    fn has_side_effects(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, arguments: Value) -> Result<Value, ThetaError> {
+        let arguments: TriggerWorkflowArguments = parse_arguments(arguments)?;
+
+        let execution = self.integration.execute_workflow(&arguments.workflow_id, arguments.data).await?;
+        Ok(json!({ "execution_id": execution.id, "status": execution.status, "data": execution.data }))
+    }
+}
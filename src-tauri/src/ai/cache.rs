@@ -0,0 +1,288 @@
+use crate::models::{AICacheStats, AIFeature};
+use lru::LruCache;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const KEY_PREFIX: &str = "theta:ai-cache:";
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const MEMORY_CAPACITY: usize = 512;
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+/// How long to stay on the in-memory cache after Redis could not be reached.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// SHA-256 of the content with whitespace collapsed, so trivially different
+/// extractions of the same page share a hash. Case is kept: an edit to an
+/// acronym or identifier is still an edit.
+pub fn content_hash(content: &str) -> String {
+    let normalized = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    hex_digest(normalized.as_bytes())
+}
+
+pub(crate) fn hex_digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Caches model output keyed by content, model and prompt version. Uses
+/// Redis when it is reachable and an in-process LRU otherwise.
+#[derive(Clone)]
+pub struct ResponseCache {
+    redis: Option<redis::Client>,
+    connection: Arc<tokio::sync::Mutex<RedisConnection>>,
+    memory: Arc<Mutex<LruCache<String, MemoryEntry>>>,
+    ttl: Duration,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+enum RedisConnection {
+    Disconnected,
+    Connected(MultiplexedConnection),
+    Unreachable { since: Instant },
+}
+
+struct MemoryEntry {
+    value: String,
+    expires_at: Instant,
+}
+
+impl ResponseCache {
+    pub fn new(redis: Option<redis::Client>) -> Self {
+        let ttl = std::env::var("AI_CACHE_TTL_SECONDS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TTL);
+
+        Self {
+            redis,
+            connection: Arc::new(tokio::sync::Mutex::new(RedisConnection::Disconnected)),
+            memory: Arc::new(Mutex::new(LruCache::new(
+                NonZeroUsize::new(MEMORY_CAPACITY).expect("capacity is non-zero"),
+            ))),
+            ttl,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Cache key for one feature's output. `prompt_version` must change
+    /// whenever the prompt does, and should include any parameter that
+    /// changes the output (such as a summary length).
+    pub fn key(feature: AIFeature, content_hash: &str, model: &str, prompt_version: &str) -> String {
+        let digest = hex_digest(format!("{}\n{}\n{}", content_hash, model, prompt_version).as_bytes());
+        format!("{}{}:{}", KEY_PREFIX, feature.as_str(), digest)
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let raw = match self.redis_connection().await {
+            Some(mut connection) => match connection.get::<_, Option<String>>(key).await {
+                Ok(value) => value,
+                Err(e) => {
+                    self.mark_unreachable(&e).await;
+                    self.memory_get(key)
+                }
+            },
+            None => self.memory_get(key),
+        };
+
+        // An entry written by an older build may no longer deserialize
+        let value = raw.and_then(|raw| serde_json::from_str(&raw).ok());
+        let counter = if value.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    pub async fn put<T: Serialize>(&self, key: &str, value: &T) {
+        let raw = match serde_json::to_string(value) {
+            Ok(raw) => raw,
+            Err(e) => {
+                log::warn!("Failed to serialize cached AI response: {}", e);
+                return;
+            }
+        };
+
+        if let Some(mut connection) = self.redis_connection().await {
+            match connection.set_ex::<_, _, ()>(key, &raw, self.ttl.as_secs()).await {
+                Ok(()) => return,
+                Err(e) => self.mark_unreachable(&e).await,
+            }
+        }
+
+        self.memory.lock().unwrap().put(
+            key.to_string(),
+            MemoryEntry { value: raw, expires_at: Instant::now() + self.ttl },
+        );
+    }
+
+    pub async fn stats(&self) -> AICacheStats {
+        let (backend, entries) = match self.redis_connection().await {
+            Some(mut connection) => match scan_keys(&mut connection).await {
+                Ok(keys) => ("redis", keys.len() as u64),
+                Err(e) => {
+                    self.mark_unreachable(&e).await;
+                    ("memory", self.memory_len())
+                }
+            },
+            None => ("memory", self.memory_len()),
+        };
+
+        AICacheStats {
+            backend: backend.to_string(),
+            entries,
+            ttl_seconds: self.ttl.as_secs(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Removes cached responses for `feature`, or all of them, from both
+    /// backends. Returns how many entries were removed.
+    pub async fn purge(&self, feature: Option<AIFeature>) -> Result<u64, redis::RedisError> {
+        let prefix = match feature {
+            Some(feature) => format!("{}{}:", KEY_PREFIX, feature.as_str()),
+            None => KEY_PREFIX.to_string(),
+        };
+
+        let mut removed = {
+            let mut memory = self.memory.lock().unwrap();
+            let keys: Vec<String> = memory
+                .iter()
+                .map(|(key, _)| key)
+                .filter(|key| key.starts_with(&prefix))
+                .cloned()
+                .collect();
+            for key in &keys {
+                memory.pop(key);
+            }
+            keys.len() as u64
+        };
+
+        if let Some(mut connection) = self.redis_connection().await {
+            let keys: Vec<String> = scan_keys(&mut connection)
+                .await?
+                .into_iter()
+                .filter(|key| key.starts_with(&prefix))
+                .collect();
+            if !keys.is_empty() {
+                removed += connection.del::<_, u64>(&keys).await?;
+            }
+        }
+
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+        Ok(removed)
+    }
+
+    /// The shared Redis connection, opening it on first use. Returns `None`
+    /// when Redis is not configured or failed recently.
+    async fn redis_connection(&self) -> Option<MultiplexedConnection> {
+        let client = self.redis.as_ref()?;
+        let mut connection = self.connection.lock().await;
+
+        match &*connection {
+            RedisConnection::Connected(existing) => return Some(existing.clone()),
+            RedisConnection::Unreachable { since } if since.elapsed() < RECONNECT_INTERVAL => return None,
+            _ => {}
+        }
+
+        match tokio::time::timeout(CONNECT_TIMEOUT, client.get_multiplexed_tokio_connection()).await {
+            Ok(Ok(opened)) => {
+                *connection = RedisConnection::Connected(opened.clone());
+                Some(opened)
+            }
+            Ok(Err(e)) => {
+                log::warn!("Redis unavailable, caching AI responses in memory: {}", e);
+                *connection = RedisConnection::Unreachable { since: Instant::now() };
+                None
+            }
+            Err(_) => {
+                log::warn!("Timed out connecting to Redis, caching AI responses in memory");
+                *connection = RedisConnection::Unreachable { since: Instant::now() };
+                None
+            }
+        }
+    }
+
+    async fn mark_unreachable(&self, error: &redis::RedisError) {
+        log::warn!("Redis cache request failed, falling back to memory: {}", error);
+        *self.connection.lock().await = RedisConnection::Unreachable { since: Instant::now() };
+    }
+
+    fn memory_get(&self, key: &str) -> Option<String> {
+        let mut memory = self.memory.lock().unwrap();
+        match memory.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.value.clone()),
+            Some(_) => {
+                memory.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn memory_len(&self) -> u64 {
+        let now = Instant::now();
+        self.memory
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, entry)| entry.expires_at > now)
+            .count() as u64
+    }
+}
+
+async fn scan_keys(connection: &mut MultiplexedConnection) -> Result<Vec<String>, redis::RedisError> {
+    let mut keys = Vec::new();
+    let mut iter = connection.scan_match::<_, String>(format!("{}*", KEY_PREFIX)).await?;
+    while let Some(key) = iter.next_item().await {
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_hash_ignores_whitespace_but_not_case() {
+        assert_eq!(content_hash("Hello   World\n"), content_hash("Hello World"));
+        assert_ne!(content_hash("Hello World"), content_hash("hello world"));
+        assert_ne!(content_hash("hello world"), content_hash("hello there"));
+        assert_eq!(content_hash("x").len(), 64);
+    }
+
+    #[test]
+    fn keys_differ_by_model_and_prompt_version() {
+        let hash = content_hash("some page");
+        let key = ResponseCache::key(AIFeature::Summary, &hash, "gpt-4o", "v1");
+
+        assert!(key.starts_with("theta:ai-cache:summary:"));
+        assert_ne!(key, ResponseCache::key(AIFeature::Summary, &hash, "llama3", "v1"));
+        assert_ne!(key, ResponseCache::key(AIFeature::Summary, &hash, "gpt-4o", "v2"));
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_memory_without_redis() {
+        let cache = ResponseCache::new(None);
+        let key = ResponseCache::key(AIFeature::Summary, &content_hash("page"), "m", "v1");
+
+        assert_eq!(cache.get::<String>(&key).await, None);
+        cache.put(&key, &"short summary".to_string()).await;
+        assert_eq!(cache.get::<String>(&key).await.as_deref(), Some("short summary"));
+
+        let stats = cache.stats().await;
+        assert_eq!((stats.backend.as_str(), stats.entries, stats.hits, stats.misses), ("memory", 1, 1, 1));
+
+        assert_eq!(cache.purge(Some(AIFeature::Analysis)).await.unwrap(), 0);
+        assert_eq!(cache.purge(None).await.unwrap(), 1);
+        assert_eq!(cache.get::<String>(&key).await, None);
+    }
+}
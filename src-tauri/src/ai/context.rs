@@ -0,0 +1,346 @@
+use super::models::{AIMessage, CompletionRequest};
+use super::providers::LlmProvider;
+use super::service::AIServiceManager;
+use crate::error::ThetaError;
+use crate::models::{AIChat, AIFeature, ChatMessage, ContextStrategy, MessageRole, RollingSummary};
+use once_cell::sync::Lazy;
+use tiktoken_rs::CoreBPE;
+
+// cl100k_base is exact for the OpenAI chat models and a close estimate for the others
+static TOKENIZER: Lazy<Option<CoreBPE>> = Lazy::new(|| tiktoken_rs::cl100k_base().ok());
+
+/// Framing tokens the chat format adds around every message (role, separators).
+const MESSAGE_OVERHEAD: u32 = 4;
+
+/// Room kept free in the prompt for the rolling summary itself.
+const SUMMARY_TOKENS: u32 = 512;
+
+pub fn count_tokens(text: &str) -> u32 {
+    match TOKENIZER.as_ref() {
+        Some(bpe) => bpe.encode_with_special_tokens(text).len() as u32,
+        None => (text.chars().count() as f32 / 4.0).ceil() as u32,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ContextBudget {
+    pub context_window: u32,
+    pub completion_tokens: u32,
+}
+
+/// The slice of a conversation that fits the budget. `dropped` counts the
+/// oldest non-system messages left out.
+#[derive(Debug, Clone)]
+pub struct ContextWindow {
+    pub system: Vec<ChatMessage>,
+    pub messages: Vec<ChatMessage>,
+    pub dropped: usize,
+    pub prompt_tokens: u32,
+}
+
+impl ContextBudget {
+    pub fn new(context_window: u32, completion_tokens: u32) -> Self {
+        Self {
+            context_window,
+            completion_tokens: completion_tokens.min(context_window / 2),
+        }
+    }
+
+    pub fn prompt_tokens(&self) -> u32 {
+        self.context_window.saturating_sub(self.completion_tokens)
+    }
+}
+
+pub fn message_tokens(provider: &dyn LlmProvider, message: &ChatMessage) -> u32 {
+    provider.count_tokens(&message.content) + MESSAGE_OVERHEAD
+}
+
+/// Keeps every system message plus as many of the most recent turns as fit in
+/// `available` tokens. Fails only when the newest message cannot fit on its own.
+pub fn fit_messages(
+    provider: &dyn LlmProvider,
+    history: &[ChatMessage],
+    available: u32,
+) -> Result<ContextWindow, ThetaError> {
+    let (system, turns): (Vec<ChatMessage>, Vec<ChatMessage>) = history
+        .iter()
+        .cloned()
+        .partition(|message| matches!(message.role, MessageRole::System));
+
+    let system_tokens: u32 = system.iter().map(|message| message_tokens(provider, message)).sum();
+    let mut remaining = available.checked_sub(system_tokens).ok_or_else(|| {
+        ThetaError::InvalidInput(format!(
+            "System prompt uses {} tokens but only {} are available",
+            system_tokens, available
+        ))
+    })?;
+
+    let mut kept = 0;
+    for message in turns.iter().rev() {
+        let tokens = message_tokens(provider, message);
+        if tokens > remaining {
+            break;
+        }
+        remaining -= tokens;
+        kept += 1;
+    }
+
+    if kept == 0 && !turns.is_empty() {
+        let newest = message_tokens(provider, &turns[turns.len() - 1]);
+        return Err(ThetaError::InvalidInput(format!(
+            "Message is too long for the model's context window ({} tokens, {} available)",
+            newest,
+            available.saturating_sub(system_tokens)
+        )));
+    }
+
+    let dropped = turns.len() - kept;
+    Ok(ContextWindow {
+        system,
+        messages: turns[dropped..].to_vec(),
+        dropped,
+        prompt_tokens: available - remaining,
+    })
+}
+
+/// Builds the request history for the next turn of `conversation`, trimming it
+/// to the model's window according to the conversation's strategy. With
+/// `RollingSummary` any newly dropped turns are folded into
+/// `conversation.rolling_summary`, which the caller is expected to persist.
+pub async fn build_context(
+    services: &AIServiceManager,
+    provider: &dyn LlmProvider,
+    conversation: &mut AIChat,
+    completion_tokens: u32,
+) -> Result<Vec<AIMessage>, ThetaError> {
+    let model = Some(conversation.model.as_str())
+        .filter(|model| !model.is_empty())
+        .unwrap_or(provider.default_model());
+    let budget = ContextBudget::new(provider.model_info(model).max_tokens, completion_tokens);
+
+    let available = match conversation.context_strategy {
+        ContextStrategy::DropOldest => budget.prompt_tokens(),
+        ContextStrategy::RollingSummary => budget.prompt_tokens().saturating_sub(SUMMARY_TOKENS + MESSAGE_OVERHEAD),
+    };
+    let mut window = fit_messages(provider, &conversation.messages, available)?;
+
+    if conversation.context_strategy == ContextStrategy::RollingSummary && window.dropped > 0 {
+        let turns: Vec<&ChatMessage> = conversation.messages
+            .iter()
+            .filter(|message| !matches!(message.role, MessageRole::System))
+            .collect();
+
+        let summarized = conversation.rolling_summary.as_ref().map_or(0, |summary| summary.message_count);
+        if summarized < window.dropped {
+            let previous = conversation.rolling_summary.as_ref().map(|summary| summary.content.as_str());
+            let content = summarize_turns(services, provider, model, previous, &turns[summarized..window.dropped]).await?;
+            conversation.rolling_summary = Some(RollingSummary {
+                content,
+                message_count: window.dropped,
+            });
+        } else if summarized > window.dropped {
+            // The summary already covers some turns that would fit; don't send them twice
+            let overlap = (summarized - window.dropped).min(window.messages.len().saturating_sub(1));
+            window.messages.drain(..overlap);
+        }
+    }
+
+    let mut messages: Vec<AIMessage> = window.system.iter().map(AIMessage::from).collect();
+    if window.dropped > 0 {
+        if let Some(summary) = &conversation.rolling_summary {
+            messages.push(AIMessage::new(
+                "system".to_string(),
+                format!("Summary of the earlier conversation:\n{}", summary.content),
+            ));
+        }
+    }
+    messages.extend(window.messages.iter().map(AIMessage::from));
+
+    Ok(messages)
+}
+
+async fn summarize_turns(
+    services: &AIServiceManager,
+    provider: &dyn LlmProvider,
+    model: &str,
+    previous: Option<&str>,
+    turns: &[&ChatMessage],
+) -> Result<String, ThetaError> {
+    let transcript: Vec<String> = turns
+        .iter()
+        .map(|message| format!("{}: {}", message.role.as_str(), message.content))
+        .collect();
+
+    let mut prompt = String::from(
+        "Summarize the following conversation so the summary can replace it as context for later turns. \
+         Keep facts, decisions, names and open questions; drop pleasantries.\n\n",
+    );
+    if let Some(previous) = previous {
+        prompt.push_str(&format!("Summary so far:\n{}\n\n", previous));
+    }
+    prompt.push_str(&format!("Conversation:\n{}", transcript.join("\n")));
+
+    let mut request = CompletionRequest::from_prompt(&prompt);
+    request.model = Some(model.to_string());
+    request.temperature = Some(0.2);
+    request.max_tokens = Some(SUMMARY_TOKENS);
+    request.feature = AIFeature::ConversationSummary;
+
+    let response = services.complete(provider.name(), &request).await?;
+    Ok(response.content.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::models::{AIModel, AIProvider};
+    use crate::ai::providers::ProviderError;
+    use crate::models::AIResponse;
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+    use uuid::Uuid;
+
+    /// Counts a token per word, has a small context window and answers
+    /// summary requests with "summary 1", "summary 2", ...
+    struct WordProvider {
+        config: AIProvider,
+        prompts: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl LlmProvider for WordProvider {
+        fn config(&self) -> &AIProvider {
+            &self.config
+        }
+
+        async fn complete(&self, request: &CompletionRequest) -> Result<AIResponse, ProviderError> {
+            let mut prompts = self.prompts.lock().unwrap();
+            prompts.push(request.messages.last().unwrap().content.clone());
+            Ok(AIResponse {
+                content: format!("summary {}", prompts.len()),
+                model: "words".to_string(),
+                provider: self.name().to_string(),
+                input_tokens: 0,
+                output_tokens: 0,
+                tokens_used: 0,
+                cost_usd: None,
+                processing_time: 0,
+                confidence: None,
+                tool_calls: Vec::new(),
+                run_id: None,
+                raw: None,
+            })
+        }
+
+        async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+            Ok(Vec::new())
+        }
+
+        fn count_tokens(&self, text: &str) -> u32 {
+            text.split_whitespace().count() as u32
+        }
+
+        fn model_info(&self, model: &str) -> AIModel {
+            AIModel { max_tokens: 1000, ..AIModel::for_name(model, self.name()) }
+        }
+    }
+
+    fn setup() -> (AIServiceManager, Arc<WordProvider>) {
+        let provider = Arc::new(WordProvider {
+            config: AIProvider::new("words".to_string(), "http://localhost:1".to_string(), None),
+            prompts: Mutex::new(Vec::new()),
+        });
+        let mut services = AIServiceManager::new();
+        services.add_service(provider.clone());
+        (services, provider)
+    }
+
+    /// Alternating user and assistant turns of `words` words each, starting
+    /// with "turn0", "turn1", ... for the turns in `range`.
+    fn turns(range: std::ops::Range<usize>, words: usize) -> Vec<ChatMessage> {
+        range
+            .map(|i| {
+                let role = if i % 2 == 0 { MessageRole::User } else { MessageRole::Assistant };
+                ChatMessage::new(role, format!("turn{}{}", i, " word".repeat(words - 1)))
+            })
+            .collect()
+    }
+
+    fn conversation(strategy: ContextStrategy, messages: Vec<ChatMessage>) -> AIChat {
+        let now = chrono::Utc::now();
+        AIChat {
+            id: Uuid::new_v4(),
+            title: None,
+            messages,
+            context: None,
+            model: "words".to_string(),
+            provider: "words".to_string(),
+            context_strategy: strategy,
+            rolling_summary: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn contents(messages: &[AIMessage]) -> Vec<String> {
+        messages.iter().map(|message| message.content.split_whitespace().next().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn fit_keeps_system_messages_and_the_newest_turns() {
+        let (_, provider) = setup();
+        let mut history = vec![ChatMessage::new(MessageRole::System, "Be brief.".to_string())];
+        history.extend(turns(0..3, 10));
+
+        // 6 tokens of system prompt and 14 per turn leave room for two turns
+        let window = fit_messages(provider.as_ref(), &history, 6 + 14 * 2 + 13).unwrap();
+        assert_eq!(window.system.len(), 1);
+        assert_eq!(window.messages.iter().map(|message| &message.content[..5]).collect::<Vec<_>>(), vec!["turn1", "turn2"]);
+        assert_eq!(window.dropped, 1);
+        assert_eq!(window.prompt_tokens, 6 + 14 * 2);
+
+        assert!(matches!(fit_messages(provider.as_ref(), &history, 6 + 13), Err(ThetaError::InvalidInput(_))));
+        assert!(matches!(fit_messages(provider.as_ref(), &history, 5), Err(ThetaError::InvalidInput(_))));
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_leaves_out_the_oldest_turns_without_summarizing() {
+        let (services, provider) = setup();
+        let mut chat = conversation(ContextStrategy::DropOldest, turns(0..9, 100));
+
+        // 1000 token window less 100 for the reply fits 8 turns of 104 tokens
+        let messages = build_context(&services, provider.as_ref(), &mut chat, 100).await.unwrap();
+        assert_eq!(contents(&messages), (1..9).map(|i| format!("turn{}", i)).collect::<Vec<_>>());
+        assert!(chat.rolling_summary.is_none());
+        assert!(provider.prompts.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rolling_summary_folds_dropped_turns_into_the_summary() {
+        let (services, provider) = setup();
+        let mut messages = vec![ChatMessage::new(MessageRole::System, "Be brief.".to_string())];
+        messages.extend(turns(0..6, 100));
+        let mut chat = conversation(ContextStrategy::RollingSummary, messages);
+
+        // Room kept for the summary leaves space for three turns
+        let request = build_context(&services, provider.as_ref(), &mut chat, 100).await.unwrap();
+        assert_eq!(contents(&request), vec!["Be", "Summary", "turn3", "turn4", "turn5"]);
+        assert!(request[1].content.ends_with("summary 1"));
+        assert_eq!(chat.rolling_summary.as_ref().unwrap().message_count, 3);
+        {
+            let prompts = provider.prompts.lock().unwrap();
+            assert!(prompts[0].contains("user: turn0") && prompts[0].contains("user: turn2"));
+            assert!(!prompts[0].contains("turn3") && !prompts[0].contains("Summary so far"));
+        }
+
+        // Later turns are summarized on top of the earlier summary
+        chat.messages.extend(turns(6..8, 100));
+        let request = build_context(&services, provider.as_ref(), &mut chat, 100).await.unwrap();
+        assert_eq!(contents(&request), vec!["Be", "Summary", "turn5", "turn6", "turn7"]);
+        assert_eq!(chat.rolling_summary.as_ref().unwrap().message_count, 5);
+        let prompts = provider.prompts.lock().unwrap();
+        assert!(prompts[1].contains("Summary so far:\nsummary 1"));
+        assert!(prompts[1].contains("assistant: turn3") && prompts[1].contains("user: turn4"));
+        assert!(!prompts[1].contains("turn2") && !prompts[1].contains("turn5"));
+    }
+}
@@ -0,0 +1,261 @@
+use super::providers::{check_status, is_local_endpoint, ProviderError, ProviderErrorKind};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+const OPENAI_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+const OLLAMA_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
+/// Size of the vectors `HashEmbeddings` produces.
+const HASH_DIMENSIONS: usize = 384;
+
+/// Turns text into vectors for similarity search. Implementations return one
+/// vector per input, in input order.
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn model(&self) -> &str;
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError>;
+
+    /// Whether text stays on this machine or the local network, which is
+    /// what privacy mode allows.
+    fn is_local(&self) -> bool;
+}
+
+/// OpenAI's `/embeddings` endpoint.
+pub struct OpenAIEmbeddings {
+    client: Client,
+    endpoint: String,
+    api_key: String,
+    model: String,
+}
+
+impl OpenAIEmbeddings {
+    pub fn new(client: Client, endpoint: String, api_key: String, model: String) -> Self {
+        Self { client, endpoint, api_key, model }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAIEmbeddings {
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+        let response = self.client
+            .post(format!("{}/embeddings", self.endpoint))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&serde_json::json!({ "model": self.model, "input": texts }))
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+        let response = check_status(self.name(), response).await?;
+        let json: Value = response.json().await.map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+
+        // Each item says which input it belongs to; don't rely on the order
+        let mut indexed: Vec<(usize, Vec<f32>)> = json["data"]
+            .as_array()
+            .ok_or_else(|| ProviderError::invalid_response(self.name(), "No embeddings in response"))?
+            .iter()
+            .map(|item| Ok((item["index"].as_u64().unwrap_or(0) as usize, parse_vector(self.name(), &item["embedding"])?)))
+            .collect::<Result<_, ProviderError>>()?;
+        indexed.sort_by_key(|(index, _)| *index);
+
+        expect_count(self.name(), texts.len(), indexed.into_iter().map(|(_, vector)| vector).collect())
+    }
+
+    fn is_local(&self) -> bool {
+        is_local_endpoint(&self.endpoint)
+    }
+}
+
+/// Ollama's `/api/embed` endpoint.
+pub struct OllamaEmbeddings {
+    client: Client,
+    endpoint: String,
+    model: String,
+}
+
+impl OllamaEmbeddings {
+    pub fn new(client: Client, endpoint: String, model: String) -> Self {
+        Self { client, endpoint, model }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaEmbeddings {
+    fn name(&self) -> &str {
+        "ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+        let response = self.client
+            .post(format!("{}/api/embed", self.endpoint))
+            .json(&serde_json::json!({ "model": self.model, "input": texts }))
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+        let response = check_status(self.name(), response).await?;
+        let json: Value = response.json().await.map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+
+        let vectors = json["embeddings"]
+            .as_array()
+            .ok_or_else(|| ProviderError::invalid_response(self.name(), "No embeddings in response"))?
+            .iter()
+            .map(|vector| parse_vector(self.name(), vector))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        expect_count(self.name(), texts.len(), vectors)
+    }
+
+    fn is_local(&self) -> bool {
+        is_local_endpoint(&self.endpoint)
+    }
+}
+
+/// Deterministic embeddings from hashed words, computed in-process. Texts
+/// sharing words get similar vectors, which is enough for tests and as an
+/// offline fallback, though it knows nothing about synonyms.
+pub struct HashEmbeddings {
+    dimensions: usize,
+}
+
+impl HashEmbeddings {
+    pub fn new(dimensions: usize) -> Self {
+        Self { dimensions: dimensions.max(1) }
+    }
+
+    pub fn embed_text(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimensions];
+
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| word.chars().count() > 1) {
+            let digest = Sha256::digest(word.to_lowercase().as_bytes());
+            let bucket = u64::from_le_bytes(digest[..8].try_into().unwrap()) as usize % self.dimensions;
+            // A hashed sign keeps colliding words from always adding up
+            vector[bucket] += if digest[8] & 1 == 0 { 1.0 } else { -1.0 };
+        }
+
+        let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|value| *value /= norm);
+        }
+        vector
+    }
+}
+
+impl Default for HashEmbeddings {
+    fn default() -> Self {
+        Self::new(HASH_DIMENSIONS)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashEmbeddings {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    fn model(&self) -> &str {
+        "hashed-words"
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+        Ok(texts.iter().map(|text| self.embed_text(text)).collect())
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+/// Picks the embedding provider named by `EMBEDDING_PROVIDER` (`openai`,
+/// `ollama` or `local`). Without it, OpenAI is used when `OPENAI_API_KEY` is
+/// set and the in-process `local` provider otherwise.
+pub fn embedding_provider_from_env(client: &Client) -> Result<Arc<dyn EmbeddingProvider>, ProviderError> {
+    let openai_key = std::env::var("OPENAI_API_KEY").ok().filter(|key| !key.trim().is_empty());
+    let provider = std::env::var("EMBEDDING_PROVIDER")
+        .ok()
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| if openai_key.is_some() { "openai" } else { "local" }.to_string());
+
+    match provider.as_str() {
+        "openai" => {
+            let api_key = openai_key.ok_or_else(|| {
+                ProviderError::new("openai", ProviderErrorKind::NotConfigured, "API key not set")
+            })?;
+            let model = std::env::var("OPENAI_EMBEDDING_MODEL").unwrap_or_else(|_| OPENAI_EMBEDDING_MODEL.to_string());
+            Ok(Arc::new(OpenAIEmbeddings::new(client.clone(), "https://api.openai.com/v1".to_string(), api_key, model)))
+        }
+        "ollama" => {
+            let endpoint = std::env::var("OLLAMA_ENDPOINT").unwrap_or_else(|_| "http://localhost:11434".to_string());
+            let model = std::env::var("OLLAMA_EMBEDDING_MODEL").unwrap_or_else(|_| OLLAMA_EMBEDDING_MODEL.to_string());
+            Ok(Arc::new(OllamaEmbeddings::new(client.clone(), endpoint.trim_end_matches('/').to_string(), model)))
+        }
+        "local" => Ok(Arc::new(HashEmbeddings::default())),
+        other => Err(ProviderError::new(
+            other,
+            ProviderErrorKind::NotConfigured,
+            "Unsupported embedding provider",
+        )),
+    }
+}
+
+fn parse_vector(provider: &str, value: &Value) -> Result<Vec<f32>, ProviderError> {
+    value
+        .as_array()
+        .and_then(|values| values.iter().map(|value| value.as_f64().map(|value| value as f32)).collect())
+        .ok_or_else(|| ProviderError::invalid_response(provider, "Embedding is not a list of numbers"))
+}
+
+fn expect_count(provider: &str, expected: usize, vectors: Vec<Vec<f32>>) -> Result<Vec<Vec<f32>>, ProviderError> {
+    if vectors.len() != expected {
+        return Err(ProviderError::invalid_response(
+            provider,
+            format!("Expected {} embeddings, got {}", expected, vectors.len()),
+        ));
+    }
+    Ok(vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(a, b)| a * b).sum()
+    }
+
+    #[test]
+    fn hash_embeddings_are_deterministic_and_normalized() {
+        let embeddings = HashEmbeddings::default();
+        let vector = embeddings.embed_text("Rust ownership and borrowing");
+
+        assert_eq!(vector, embeddings.embed_text("rust OWNERSHIP and borrowing!"));
+        assert!((dot(&vector, &vector) - 1.0).abs() < 1e-5);
+        assert!(embeddings.embed_text("  ").iter().all(|value| *value == 0.0));
+    }
+
+    #[test]
+    fn shared_words_score_higher() {
+        let embeddings = HashEmbeddings::default();
+        let query = embeddings.embed_text("borrow checker lifetimes");
+        let related = embeddings.embed_text("the borrow checker enforces lifetimes");
+        let unrelated = embeddings.embed_text("a recipe for sourdough bread");
+
+        assert!(dot(&query, &related) > dot(&query, &unrelated));
+    }
+}
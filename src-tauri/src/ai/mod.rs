@@ -1,7 +1,27 @@
 pub mod service;
 pub mod models;
 pub mod providers;
+pub mod openai;
+pub mod anthropic;
+pub mod ollama;
+pub mod streaming;
+pub mod context;
+pub mod analysis;
+pub mod pricing;
+pub mod usage;
+pub mod cache;
+pub mod prompts;
+pub mod summarize;
+pub mod tab_chat;
+pub mod embeddings;
+pub mod recorder;
+pub mod replay;
+pub mod tools;
+pub mod browser_tools;
 
 pub use service::AIServiceManager;
 pub use models::*;
-pub use providers::*; 
\ No newline at end of file
+pub use providers::*;
+pub use openai::OpenAIProvider;
+pub use anthropic::AnthropicProvider;
+pub use ollama::OllamaProvider;
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use crate::models::{AIFeature, AIRequest, ChatMessage, ToolCall};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AIProvider {
@@ -29,6 +30,58 @@ pub struct AIMessage {
     pub role: String,
     pub content: String,
     pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Tools an assistant message asked to run.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// For `tool` messages, the call whose result this is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionRequest {
+    pub messages: Vec<AIMessage>,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    /// When set, the provider is asked to reply with a single JSON object
+    /// matching this schema, returned as the response content.
+    #[serde(default)]
+    pub response_schema: Option<ResponseSchema>,
+    /// Recorded in the usage ledger; providers ignore it.
+    #[serde(default)]
+    pub feature: AIFeature,
+    /// Tools the model may ask to run instead of answering directly.
+    #[serde(default)]
+    pub tools: Vec<ToolDefinition>,
+}
+
+/// A tool offered to the model, with a JSON schema for its arguments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+/// Token counts as reported by a provider.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> u32 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseSchema {
+    pub name: String,
+    pub description: String,
+    pub schema: serde_json::Value,
 }
 
 impl AIProvider {
@@ -46,6 +99,30 @@ impl AIProvider {
     }
 }
 
+impl AIModel {
+    /// Context window sizes for the model families we know about. Unknown
+    /// models get a conservative 8k window.
+    pub fn for_name(name: &str, provider: &str) -> Self {
+        let max_tokens = match name {
+            n if n.starts_with("claude-") => 200_000,
+            n if n.starts_with("gpt-4o") || n.starts_with("gpt-4-turbo") || n.starts_with("gpt-4.1") => 128_000,
+            n if n.starts_with("gpt-4-32k") => 32_768,
+            n if n.starts_with("gpt-4") => 8_192,
+            n if n.starts_with("gpt-3.5-turbo") => 16_385,
+            _ => 8_192,
+        };
+
+        let temperature_range = if provider == "anthropic" { (0.0, 1.0) } else { (0.0, 2.0) };
+
+        Self {
+            name: name.to_string(),
+            provider: provider.to_string(),
+            max_tokens,
+            temperature_range,
+        }
+    }
+}
+
 impl AIContext {
     pub fn new(conversation_id: String, model: String, provider: String) -> Self {
         Self {
@@ -57,10 +134,84 @@ impl AIContext {
     }
 
     pub fn add_message(&mut self, role: String, content: String) {
-        self.messages.push(AIMessage {
+        self.messages.push(AIMessage::new(role, content));
+    }
+}
+
+impl AIMessage {
+    pub fn new(role: String, content: String) -> Self {
+        Self {
             role,
             content,
             timestamp: chrono::Utc::now(),
-        });
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
     }
-} 
\ No newline at end of file
+
+    /// An assistant turn that asked for tools to be run.
+    pub fn tool_request(content: String, tool_calls: Vec<ToolCall>) -> Self {
+        Self {
+            tool_calls,
+            ..Self::new("assistant".to_string(), content)
+        }
+    }
+
+    /// The result of running a tool, sent back to the model.
+    pub fn tool_result(tool_call_id: String, content: String) -> Self {
+        Self {
+            tool_call_id: Some(tool_call_id),
+            ..Self::new("tool".to_string(), content)
+        }
+    }
+}
+
+impl From<&ChatMessage> for AIMessage {
+    fn from(message: &ChatMessage) -> Self {
+        Self {
+            role: message.role.as_str().to_string(),
+            content: message.content.clone(),
+            timestamp: message.timestamp,
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+}
+
+impl CompletionRequest {
+    pub fn from_prompt(prompt: &str) -> Self {
+        Self {
+            messages: vec![AIMessage::new("user".to_string(), prompt.to_string())],
+            model: None,
+            temperature: None,
+            max_tokens: None,
+            response_schema: None,
+            feature: AIFeature::default(),
+            tools: Vec::new(),
+        }
+    }
+
+    pub fn model_or<'a>(&'a self, default: &'a str) -> &'a str {
+        self.model.as_deref().unwrap_or(default)
+    }
+}
+
+impl From<&AIRequest> for CompletionRequest {
+    fn from(request: &AIRequest) -> Self {
+        let mut messages = Vec::new();
+        if let Some(context) = &request.context {
+            messages.push(AIMessage::new("system".to_string(), context.clone()));
+        }
+        messages.push(AIMessage::new("user".to_string(), request.content.clone()));
+
+        Self {
+            messages,
+            model: request.model.clone(),
+            temperature: request.temperature,
+            max_tokens: request.max_tokens,
+            response_schema: None,
+            feature: AIFeature::default(),
+            tools: Vec::new(),
+        }
+    }
+}
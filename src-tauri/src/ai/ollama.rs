@@ -0,0 +1,265 @@
+use super::models::{AIMessage, AIModel, AIProvider, CompletionRequest, TokenUsage};
+use super::providers::{check_status, CompletionStream, LlmProvider, ProviderError};
+use super::streaming::{self, SseEvent, StreamUpdate};
+use crate::models::{AIResponse, ToolCall};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::Value;
+
+/// Context length requested from Ollama. Its own default (2-4k depending on
+/// version) is too small for conversation history.
+const CONTEXT_WINDOW: u32 = 8192;
+
+/// Client for Ollama's native `/api` endpoints.
+pub struct OllamaProvider {
+    config: AIProvider,
+    client: Client,
+}
+
+impl OllamaProvider {
+    pub fn new(config: AIProvider, client: Client) -> Self {
+        Self { config, client }
+    }
+
+    fn build_payload(&self, request: &CompletionRequest, stream: bool) -> Value {
+        let messages: Vec<Value> = request.messages.iter().map(message_json).collect();
+
+        let mut payload = serde_json::json!({
+            "model": request.model_or(self.default_model()),
+            "messages": messages,
+            "stream": stream,
+            "options": {
+                "temperature": request.temperature.unwrap_or(0.7),
+                "num_predict": request.max_tokens.unwrap_or(1000),
+                "num_ctx": CONTEXT_WINDOW
+            }
+        });
+
+        if let Some(schema) = &request.response_schema {
+            payload["format"] = schema.schema.clone();
+        }
+        if !request.tools.is_empty() {
+            payload["tools"] = request.tools
+                .iter()
+                .map(|tool| serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters
+                    }
+                }))
+                .collect();
+        }
+
+        payload
+    }
+}
+
+fn message_json(message: &AIMessage) -> Value {
+    let mut json = serde_json::json!({
+        "role": message.role,
+        "content": message.content
+    });
+    if !message.tool_calls.is_empty() {
+        json["tool_calls"] = message.tool_calls
+            .iter()
+            .map(|call| serde_json::json!({ "function": { "name": call.name, "arguments": call.arguments } }))
+            .collect();
+    }
+    json
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    fn config(&self) -> &AIProvider {
+        &self.config
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<AIResponse, ProviderError> {
+        let start_time = std::time::Instant::now();
+
+        let response = self.client
+            .post(format!("{}/api/chat", self.config.endpoint))
+            .json(&self.build_payload(request, false))
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+        let response = check_status(self.name(), response).await?;
+
+        let response_json: Value = response.json().await.map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+
+        let (content, tool_calls, usage) = parse_completion(self.name(), &response_json)?;
+
+        Ok(AIResponse {
+            content,
+            model: request.model_or(self.default_model()).to_string(),
+            provider: self.name().to_string(),
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            tokens_used: usage.total(),
+            cost_usd: None,
+            processing_time: start_time.elapsed().as_millis() as u64,
+            confidence: None,
+            tool_calls,
+            run_id: None,
+            raw: Some(response_json),
+        })
+    }
+
+    async fn stream(&self, request: &CompletionRequest) -> Result<CompletionStream, ProviderError> {
+        let start_time = std::time::Instant::now();
+
+        let response = self.client
+            .post(format!("{}/api/chat", self.config.endpoint))
+            .json(&self.build_payload(request, true))
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+        let response = check_status(self.name(), response).await?;
+
+        let model = request.model_or(self.default_model()).to_string();
+        Ok(streaming::completion_stream(streaming::ndjson_events(response), model, self.name().to_string(), start_time, parse_stream_line))
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+        let response = self.client
+            .get(format!("{}/api/tags", self.config.endpoint))
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+        let response = check_status(self.name(), response).await?;
+
+        let json: Value = response.json().await.map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+
+        let models = json["models"]
+            .as_array()
+            .ok_or_else(|| ProviderError::invalid_response(self.name(), "Response contained no model list"))?;
+
+        Ok(models
+            .iter()
+            .filter_map(|model| model["name"].as_str().map(|name| name.to_string()))
+            .collect())
+    }
+
+    fn model_info(&self, model: &str) -> AIModel {
+        AIModel {
+            name: model.to_string(),
+            provider: self.config.name.clone(),
+            max_tokens: CONTEXT_WINDOW,
+            temperature_range: (0.0, 2.0),
+        }
+    }
+}
+
+/// Ollama does not give tool calls ids, so they are numbered in order. The
+/// text may be empty only when tools were called.
+fn parse_completion(provider: &str, json: &Value) -> Result<(String, Vec<ToolCall>, TokenUsage), ProviderError> {
+    if let Some(error) = json["error"].as_str() {
+        return Err(ProviderError::invalid_response(provider, format!("Provider returned an error: {}", error)));
+    }
+
+    let tool_calls: Vec<ToolCall> = json["message"]["tool_calls"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .filter_map(|(index, call)| {
+            Some(ToolCall {
+                id: format!("call_{}", index),
+                name: call["function"]["name"].as_str()?.to_string(),
+                arguments: call["function"]["arguments"].clone(),
+            })
+        })
+        .collect();
+
+    let content = json["message"]["content"].as_str().unwrap_or_default();
+    if content.trim().is_empty() && tool_calls.is_empty() {
+        return Err(ProviderError::invalid_response(provider, "Response contained no message content"));
+    }
+
+    Ok((content.to_string(), tool_calls, usage(json)))
+}
+
+fn usage(json: &Value) -> TokenUsage {
+    TokenUsage {
+        input_tokens: json["prompt_eval_count"].as_u64().unwrap_or(0) as u32,
+        output_tokens: json["eval_count"].as_u64().unwrap_or(0) as u32,
+    }
+}
+
+fn parse_stream_line(event: &SseEvent) -> Result<StreamUpdate, String> {
+    let json: Value = serde_json::from_str(&event.data).map_err(|e| e.to_string())?;
+
+    if let Some(error) = json["error"].as_str() {
+        return Err(error.to_string());
+    }
+
+    let done = json["done"].as_bool().unwrap_or(false);
+
+    Ok(StreamUpdate {
+        delta: json["message"]["content"].as_str().map(|s| s.to_string()),
+        usage: if done { Some(usage(&json)) } else { None },
+        done,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::ProviderErrorKind;
+    use serde_json::json;
+
+    #[test]
+    fn parses_a_well_formed_reply() {
+        let body = json!({
+            "message": { "role": "assistant", "content": "Hello" },
+            "done": true,
+            "prompt_eval_count": 10,
+            "eval_count": 4
+        });
+
+        let (content, _, usage) = parse_completion("ollama", &body).unwrap();
+        assert_eq!(content, "Hello");
+        assert_eq!(usage, TokenUsage { input_tokens: 10, output_tokens: 4 });
+    }
+
+    #[test]
+    fn numbers_tool_calls() {
+        let body = json!({
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{ "function": { "name": "open_tab", "arguments": { "url": "https://example.com" } } }]
+            }
+        });
+
+        let (_, tool_calls, _) = parse_completion("ollama", &body).unwrap();
+        assert_eq!(tool_calls[0].id, "call_0");
+        assert_eq!(tool_calls[0].arguments["url"], "https://example.com");
+    }
+
+    #[test]
+    fn rejects_an_error_body() {
+        let body = json!({ "error": "model 'llama9' not found, try pulling it first" });
+
+        let error = parse_completion("ollama", &body).unwrap_err();
+        assert_eq!(error.kind, ProviderErrorKind::InvalidResponse);
+        assert!(error.message.contains("not found"));
+    }
+
+    #[test]
+    fn rejects_missing_or_empty_content() {
+        for body in [json!({}), json!({ "message": {} }), json!({ "message": { "content": "" } }), json!({ "message": null })] {
+            let error = parse_completion("ollama", &body).unwrap_err();
+            assert!(error.message.contains("no message content"), "{}", body);
+        }
+    }
+
+    #[test]
+    fn stream_error_lines_fail_the_stream() {
+        let event = SseEvent { event: None, data: r#"{"error":"out of memory"}"#.to_string() };
+
+        assert_eq!(parse_stream_line(&event).unwrap_err(), "out of memory");
+    }
+}
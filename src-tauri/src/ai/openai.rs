@@ -0,0 +1,431 @@
+use super::models::{AIMessage, AIProvider, CompletionRequest, TokenUsage};
+use super::providers::{check_status, CompletionStream, LlmProvider, ProviderError, ProviderErrorKind};
+use super::streaming::{self, SseEvent, StreamUpdate};
+use crate::models::{AIResponse, ToolCall};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::Value;
+
+/// Model families that accept `json_schema` response formats...
+const JSON_SCHEMA_MODELS: &[&str] = &["gpt-4o", "gpt-4.1", "gpt-4.5", "gpt-5", "o1", "o3", "o4"];
+/// ...apart from these snapshots, which predate structured outputs.
+const NO_JSON_SCHEMA_MODELS: &[&str] = &["gpt-4o-2024-05-13", "o1-mini", "o1-preview"];
+
+/// Chat completions client for OpenAI and for OpenAI-compatible servers
+/// (LM Studio, vLLM, llama.cpp) which usually run without an API key.
+pub struct OpenAIProvider {
+    config: AIProvider,
+    client: Client,
+}
+
+impl OpenAIProvider {
+    pub fn new(config: AIProvider, client: Client) -> Self {
+        Self { config, client }
+    }
+
+    /// Local servers may run without a key; hosted endpoints may not.
+    fn authorize(&self, request: reqwest::RequestBuilder) -> Result<reqwest::RequestBuilder, ProviderError> {
+        match self.config.api_key.as_deref().filter(|key| !key.trim().is_empty()) {
+            Some(key) => Ok(request.header("Authorization", format!("Bearer {}", key))),
+            None if self.is_local() => Ok(request),
+            None => Err(ProviderError::new(self.name(), ProviderErrorKind::NotConfigured, "API key not set")),
+        }
+    }
+
+    /// Whether `model` accepts `json_schema` structured outputs. `gpt-4`,
+    /// `gpt-3.5-turbo` and other older OpenAI models reject them with a 400;
+    /// OpenAI-compatible local servers enforce the schema for any model.
+    fn supports_json_schema(&self, model: &str) -> bool {
+        if self.is_local() {
+            return true;
+        }
+        let model = model.trim().to_ascii_lowercase();
+        JSON_SCHEMA_MODELS.iter().any(|prefix| model.starts_with(prefix))
+            && !NO_JSON_SCHEMA_MODELS.iter().any(|prefix| model.starts_with(prefix))
+    }
+
+    fn build_payload(&self, request: &CompletionRequest) -> Value {
+        let model = request.model_or(self.default_model());
+        let mut messages: Vec<Value> = request.messages.iter().map(message_json).collect();
+
+        let response_format = request.response_schema.as_ref().map(|schema| {
+            if self.supports_json_schema(model) {
+                return serde_json::json!({
+                    "type": "json_schema",
+                    "json_schema": {
+                        "name": schema.name,
+                        "description": schema.description,
+                        "schema": schema.schema,
+                        "strict": true
+                    }
+                });
+            }
+            // JSON mode only promises valid JSON, so the schema goes in the prompt
+            messages.push(serde_json::json!({
+                "role": "system",
+                "content": format!("Reply with a JSON object matching this JSON schema: {}", schema.schema)
+            }));
+            serde_json::json!({ "type": "json_object" })
+        });
+
+        let mut payload = serde_json::json!({
+            "model": model,
+            "messages": messages,
+            "temperature": request.temperature.unwrap_or(0.7),
+            "max_tokens": request.max_tokens.unwrap_or(1000)
+        });
+
+        if let Some(response_format) = response_format {
+            payload["response_format"] = response_format;
+        }
+
+        if !request.tools.is_empty() {
+            payload["tools"] = request.tools
+                .iter()
+                .map(|tool| serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters
+                    }
+                }))
+                .collect();
+        }
+
+        payload
+    }
+}
+
+fn message_json(message: &AIMessage) -> Value {
+    if let Some(tool_call_id) = &message.tool_call_id {
+        return serde_json::json!({
+            "role": "tool",
+            "tool_call_id": tool_call_id,
+            "content": message.content
+        });
+    }
+    if message.tool_calls.is_empty() {
+        return serde_json::json!({
+            "role": message.role,
+            "content": message.content
+        });
+    }
+
+    let tool_calls: Vec<Value> = message.tool_calls
+        .iter()
+        .map(|call| serde_json::json!({
+            "id": call.id,
+            "type": "function",
+            "function": { "name": call.name, "arguments": call.arguments.to_string() }
+        }))
+        .collect();
+    serde_json::json!({
+        "role": "assistant",
+        "content": Some(&message.content).filter(|content| !content.is_empty()),
+        "tool_calls": tool_calls
+    })
+}
+
+#[async_trait]
+impl LlmProvider for OpenAIProvider {
+    fn config(&self) -> &AIProvider {
+        &self.config
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<AIResponse, ProviderError> {
+        let start_time = std::time::Instant::now();
+
+        let response = self.authorize(self.client.post(format!("{}/chat/completions", self.config.endpoint)))?
+            .header("Content-Type", "application/json")
+            .json(&self.build_payload(request))
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+        let response = check_status(self.name(), response).await?;
+
+        let response_json: Value = response.json().await.map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+
+        let (content, tool_calls, usage) = parse_completion(self.name(), &response_json)?;
+
+        Ok(AIResponse {
+            content,
+            model: request.model_or(self.default_model()).to_string(),
+            provider: self.name().to_string(),
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            tokens_used: usage.total(),
+            cost_usd: None,
+            processing_time: start_time.elapsed().as_millis() as u64,
+            confidence: None,
+            tool_calls,
+            run_id: None,
+            raw: Some(response_json),
+        })
+    }
+
+    async fn stream(&self, request: &CompletionRequest) -> Result<CompletionStream, ProviderError> {
+        let start_time = std::time::Instant::now();
+
+        let mut payload = self.build_payload(request);
+        payload["stream"] = Value::Bool(true);
+        payload["stream_options"] = serde_json::json!({ "include_usage": true });
+
+        let response = self.authorize(self.client.post(format!("{}/chat/completions", self.config.endpoint)))?
+            .header("Content-Type", "application/json")
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+        let response = check_status(self.name(), response).await?;
+
+        let model = request.model_or(self.default_model()).to_string();
+        Ok(streaming::completion_stream(streaming::sse_events(response), model, self.name().to_string(), start_time, parse_stream_event))
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+        let response = self.authorize(self.client.get(format!("{}/models", self.config.endpoint)))?
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+        let response = check_status(self.name(), response).await?;
+
+        let json: Value = response.json().await.map_err(|e| ProviderError::from_reqwest(self.name(), e))?;
+
+        let models = json["data"]
+            .as_array()
+            .ok_or_else(|| ProviderError::invalid_response(self.name(), "Response contained no model list"))?;
+
+        Ok(models
+            .iter()
+            .filter_map(|model| model["id"].as_str().map(|id| id.to_string()))
+            .collect())
+    }
+}
+
+/// Pulls the reply text, requested tool calls and token counts out of a
+/// chat completion body. The text may be empty only when tools were called.
+fn parse_completion(provider: &str, json: &Value) -> Result<(String, Vec<ToolCall>, TokenUsage), ProviderError> {
+    if let Some(error) = json.get("error").filter(|error| !error.is_null()) {
+        let message = error["message"].as_str().or_else(|| error.as_str()).unwrap_or("unknown error");
+        return Err(ProviderError::invalid_response(provider, format!("Provider returned an error: {}", message)));
+    }
+
+    let choice = json["choices"]
+        .as_array()
+        .and_then(|choices| choices.first())
+        .ok_or_else(|| ProviderError::invalid_response(provider, "Response contained no choices"))?;
+
+    if let Some(refusal) = choice["message"]["refusal"].as_str() {
+        return Err(ProviderError::invalid_response(provider, format!("Model refused the request: {}", refusal)));
+    }
+
+    let tool_calls = parse_tool_calls(provider, &choice["message"]["tool_calls"])?;
+    let content = choice["message"]["content"].as_str().unwrap_or_default();
+    if content.trim().is_empty() && tool_calls.is_empty() {
+        return Err(ProviderError::invalid_response(provider, "Response contained no message content"));
+    }
+
+    Ok((content.to_string(), tool_calls, usage(&json["usage"]).unwrap_or_default()))
+}
+
+/// Tool call arguments arrive as a JSON-encoded string.
+fn parse_tool_calls(provider: &str, tool_calls: &Value) -> Result<Vec<ToolCall>, ProviderError> {
+    let Some(tool_calls) = tool_calls.as_array() else { return Ok(Vec::new()) };
+
+    tool_calls
+        .iter()
+        .map(|call| {
+            let name = call["function"]["name"]
+                .as_str()
+                .ok_or_else(|| ProviderError::invalid_response(provider, "Tool call has no function name"))?;
+            let arguments = match call["function"]["arguments"].as_str() {
+                Some(arguments) if !arguments.trim().is_empty() => serde_json::from_str(arguments).map_err(|e| {
+                    ProviderError::invalid_response(provider, format!("Arguments for tool '{}' are not JSON: {}", name, e))
+                })?,
+                _ => Value::Object(Default::default()),
+            };
+            Ok(ToolCall {
+                id: call["id"].as_str().unwrap_or_default().to_string(),
+                name: name.to_string(),
+                arguments,
+            })
+        })
+        .collect()
+}
+
+fn usage(usage: &Value) -> Option<TokenUsage> {
+    Some(TokenUsage {
+        input_tokens: usage["prompt_tokens"].as_u64()? as u32,
+        output_tokens: usage["completion_tokens"].as_u64()? as u32,
+    })
+}
+
+fn parse_stream_event(event: &SseEvent) -> Result<StreamUpdate, String> {
+    if event.data == "[DONE]" {
+        return Ok(StreamUpdate { done: true, ..Default::default() });
+    }
+
+    let json: Value = serde_json::from_str(&event.data).map_err(|e| e.to_string())?;
+
+    if let Some(message) = json["error"]["message"].as_str() {
+        return Err(message.to_string());
+    }
+
+    Ok(StreamUpdate {
+        delta: json["choices"][0]["delta"]["content"].as_str().map(|s| s.to_string()),
+        usage: usage(&json["usage"]),
+        done: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn provider(endpoint: &str) -> OpenAIProvider {
+        let mut config = AIProvider::new("openai".to_string(), endpoint.to_string(), Some("key".to_string()));
+        config.add_model("gpt-4".to_string());
+        OpenAIProvider::new(config, Client::new())
+    }
+
+    fn structured_request(model: Option<&str>) -> CompletionRequest {
+        let mut request = CompletionRequest::from_prompt("Analyze this");
+        request.model = model.map(str::to_string);
+        request.response_schema = Some(crate::ai::analysis::analysis_schema());
+        request
+    }
+
+    #[test]
+    fn sends_json_schema_only_to_models_that_support_it() {
+        let openai = provider("https://api.openai.com/v1");
+        for model in ["gpt-4o", "gpt-4o-mini", "gpt-4.1-mini", "gpt-5", "o3-mini"] {
+            let payload = openai.build_payload(&structured_request(Some(model)));
+            assert_eq!(payload["response_format"]["type"], "json_schema", "{}", model);
+            assert_eq!(payload["response_format"]["json_schema"]["strict"], true);
+            assert_eq!(payload["messages"].as_array().unwrap().len(), 1);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_json_mode_for_older_models() {
+        let openai = provider("https://api.openai.com/v1");
+        for model in [None, Some("gpt-4"), Some("gpt-3.5-turbo"), Some("gpt-4o-2024-05-13"), Some("o1-mini")] {
+            let payload = openai.build_payload(&structured_request(model));
+            assert_eq!(payload["response_format"], json!({ "type": "json_object" }), "{:?}", model);
+            let instructions = payload["messages"][1]["content"].as_str().unwrap();
+            assert!(instructions.contains("JSON schema") && instructions.contains("complexity_score"));
+        }
+    }
+
+    #[test]
+    fn local_servers_get_json_schema_for_any_model() {
+        let payload = provider("http://localhost:1234/v1").build_payload(&structured_request(Some("qwen2.5-7b-instruct")));
+        assert_eq!(payload["response_format"]["type"], "json_schema");
+    }
+
+    #[test]
+    fn parses_a_well_formed_completion() {
+        let body = json!({
+            "choices": [{ "message": { "role": "assistant", "content": "Hello" } }],
+            "usage": { "prompt_tokens": 9, "completion_tokens": 3, "total_tokens": 12 }
+        });
+
+        let (content, _, usage) = parse_completion("openai", &body).unwrap();
+        assert_eq!(content, "Hello");
+        assert_eq!(usage, TokenUsage { input_tokens: 9, output_tokens: 3 });
+        assert_eq!(usage.total(), 12);
+    }
+
+    #[test]
+    fn rejects_an_error_body() {
+        let body = json!({ "error": { "message": "Invalid API key", "type": "invalid_request_error" } });
+
+        let error = parse_completion("openai", &body).unwrap_err();
+        assert_eq!(error.kind, ProviderErrorKind::InvalidResponse);
+        assert!(error.message.contains("Invalid API key"));
+    }
+
+    #[test]
+    fn rejects_missing_or_empty_choices() {
+        for body in [json!({}), json!({ "choices": [] }), json!({ "choices": null })] {
+            let error = parse_completion("openai", &body).unwrap_err();
+            assert!(error.message.contains("no choices"), "{}", body);
+        }
+    }
+
+    #[test]
+    fn rejects_missing_or_empty_content() {
+        for message in [json!({}), json!({ "content": null }), json!({ "content": "  " }), json!({ "content": 42 })] {
+            let body = json!({ "choices": [{ "message": message }] });
+            let error = parse_completion("openai", &body).unwrap_err();
+            assert!(error.message.contains("no message content"), "{}", body);
+        }
+    }
+
+    #[test]
+    fn rejects_a_refusal() {
+        let body = json!({ "choices": [{ "message": { "content": null, "refusal": "I can't help with that" } }] });
+
+        let error = parse_completion("openai", &body).unwrap_err();
+        assert!(error.message.contains("refused"));
+    }
+
+    #[test]
+    fn missing_usage_counts_as_zero_tokens() {
+        let body = json!({ "choices": [{ "message": { "content": "Hi" } }] });
+
+        assert_eq!(parse_completion("openai", &body).unwrap().2, TokenUsage::default());
+    }
+
+    #[test]
+    fn parses_tool_calls_without_content() {
+        let body = json!({ "choices": [{ "message": {
+            "content": null,
+            "tool_calls": [{
+                "id": "call_1",
+                "type": "function",
+                "function": { "name": "open_tab", "arguments": "{\"url\":\"https://example.com\"}" }
+            }]
+        } }] });
+
+        let (content, tool_calls, _) = parse_completion("openai", &body).unwrap();
+        assert!(content.is_empty());
+        assert_eq!(tool_calls, vec![ToolCall {
+            id: "call_1".to_string(),
+            name: "open_tab".to_string(),
+            arguments: json!({ "url": "https://example.com" }),
+        }]);
+    }
+
+    #[test]
+    fn hosted_endpoints_require_an_api_key() {
+        let hosted = OpenAIProvider::new(
+            AIProvider::new("openai".to_string(), "https://api.openai.com/v1".to_string(), Some(String::new())),
+            Client::new(),
+        );
+        let error = hosted.authorize(Client::new().get("https://api.openai.com/v1/models")).unwrap_err();
+        assert_eq!(error.kind, ProviderErrorKind::NotConfigured);
+
+        let local = OpenAIProvider::new(
+            AIProvider::new("openai-compatible".to_string(), "http://localhost:1234/v1".to_string(), None),
+            Client::new(),
+        );
+        assert!(local.authorize(Client::new().get("http://localhost:1234/v1/models")).is_ok());
+    }
+
+    #[test]
+    fn stream_error_events_fail_the_stream() {
+        let event = SseEvent { event: None, data: r#"{"error":{"message":"overloaded"}}"#.to_string() };
+
+        assert_eq!(parse_stream_event(&event).unwrap_err(), "overloaded");
+    }
+
+    #[test]
+    fn malformed_stream_events_fail_the_stream() {
+        let event = SseEvent { event: None, data: "{not json".to_string() };
+
+        assert!(parse_stream_event(&event).is_err());
+    }
+}
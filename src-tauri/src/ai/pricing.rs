@@ -0,0 +1,77 @@
+use super::models::TokenUsage;
+
+/// List price in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+/// Model name prefixes and their list prices. More specific prefixes come
+/// first since the first match wins (`gpt-4o-mini` before `gpt-4o` before `gpt-4`).
+const PRICE_TABLE: &[(&str, ModelPrice)] = &[
+    ("gpt-4o-mini", ModelPrice { input_per_million: 0.15, output_per_million: 0.60 }),
+    ("gpt-4o", ModelPrice { input_per_million: 2.50, output_per_million: 10.00 }),
+    ("gpt-4.1-nano", ModelPrice { input_per_million: 0.10, output_per_million: 0.40 }),
+    ("gpt-4.1-mini", ModelPrice { input_per_million: 0.40, output_per_million: 1.60 }),
+    ("gpt-4.1", ModelPrice { input_per_million: 2.00, output_per_million: 8.00 }),
+    ("gpt-4-turbo", ModelPrice { input_per_million: 10.00, output_per_million: 30.00 }),
+    ("gpt-4-32k", ModelPrice { input_per_million: 60.00, output_per_million: 120.00 }),
+    ("gpt-4", ModelPrice { input_per_million: 30.00, output_per_million: 60.00 }),
+    ("gpt-3.5-turbo", ModelPrice { input_per_million: 0.50, output_per_million: 1.50 }),
+    ("claude-3-opus", ModelPrice { input_per_million: 15.00, output_per_million: 75.00 }),
+    ("claude-3-5-sonnet", ModelPrice { input_per_million: 3.00, output_per_million: 15.00 }),
+    ("claude-3-7-sonnet", ModelPrice { input_per_million: 3.00, output_per_million: 15.00 }),
+    ("claude-3-sonnet", ModelPrice { input_per_million: 3.00, output_per_million: 15.00 }),
+    ("claude-3-5-haiku", ModelPrice { input_per_million: 0.80, output_per_million: 4.00 }),
+    ("claude-3-haiku", ModelPrice { input_per_million: 0.25, output_per_million: 1.25 }),
+];
+
+pub fn model_price(model: &str) -> Option<ModelPrice> {
+    PRICE_TABLE
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, price)| *price)
+}
+
+/// Cost of one request. Local models are free; hosted models missing from
+/// the table have no known cost.
+pub fn request_cost(model: &str, is_local: bool, usage: TokenUsage) -> Option<f64> {
+    if is_local {
+        return Some(0.0);
+    }
+
+    model_price(model).map(|price| {
+        (usage.input_tokens as f64 * price.input_per_million
+            + usage.output_tokens as f64 * price.output_per_million)
+            / 1_000_000.0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn more_specific_prefixes_win() {
+        assert_eq!(model_price("gpt-4o-mini-2024-07-18").unwrap().input_per_million, 0.15);
+        assert_eq!(model_price("gpt-4o-2024-08-06").unwrap().input_per_million, 2.50);
+        assert_eq!(model_price("gpt-4-0613").unwrap().input_per_million, 30.00);
+    }
+
+    #[test]
+    fn prices_input_and_output_separately() {
+        let usage = TokenUsage { input_tokens: 1_000_000, output_tokens: 500_000 };
+        let cost = request_cost("claude-3-haiku-20240307", false, usage).unwrap();
+
+        assert!((cost - 0.875).abs() < 1e-9);
+    }
+
+    #[test]
+    fn local_models_are_free_and_unknown_models_unpriced() {
+        let usage = TokenUsage { input_tokens: 100, output_tokens: 100 };
+
+        assert_eq!(request_cost("llama3", true, usage), Some(0.0));
+        assert_eq!(request_cost("some-hosted-model", false, usage), None);
+    }
+}
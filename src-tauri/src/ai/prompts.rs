@@ -0,0 +1,420 @@
+use super::cache::hex_digest;
+use super::models::{AIMessage, CompletionRequest};
+use crate::error::ThetaError;
+use crate::models::AIFeature;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const CONTENT_ANALYSIS: &str = "content_analysis";
+pub const SUMMARY: &str = "summary";
+pub const SUMMARY_CHUNK: &str = "summary_chunk";
+pub const SUMMARY_REDUCE: &str = "summary_reduce";
+pub const TAB_CHAT: &str = "tab_chat";
+pub const TAB_CONTEXT: &str = "tab_context";
+pub const WORKSPACE_CHAT: &str = "workspace_chat";
+pub const BROWSER_AGENT: &str = "browser_agent";
+
+/// `user_data` category holding per-template overrides, keyed by template name.
+pub const OVERRIDE_CATEGORY: &str = "prompt_template";
+
+static VARIABLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").unwrap());
+
+/// Where the effective version of a template came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateSource {
+    Builtin,
+    File,
+    UserData,
+}
+
+/// A named prompt with `{{variable}}` placeholders and default request settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub version: u32,
+    pub description: Option<String>,
+    pub system: Option<String>,
+    pub template: String,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub source: TemplateSource,
+}
+
+/// A template file or `user_data` override. Fields left out keep the value
+/// of the template being overridden; a new template must set `template`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateOverride {
+    pub name: Option<String>,
+    pub version: Option<u32>,
+    pub description: Option<String>,
+    pub system: Option<String>,
+    pub template: Option<String>,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+}
+
+impl PromptTemplate {
+    /// Variable names used by the system and user prompts, in order of first use.
+    pub fn variables(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for text in self.system.iter().chain(std::iter::once(&self.template)) {
+            for captures in VARIABLE.captures_iter(text) {
+                let name = captures[1].to_string();
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Identifies this exact prompt for caching. Includes a digest of the
+    /// text so an edit that forgets to bump `version` still misses the cache.
+    pub fn cache_version(&self) -> String {
+        let digest = hex_digest(
+            format!(
+                "{:?}\n{}\n{:?}\n{:?}\n{:?}",
+                self.system, self.template, self.model, self.temperature, self.max_tokens
+            )
+            .as_bytes(),
+        );
+        format!("{}@v{}:{}", self.name, self.version, &digest[..12])
+    }
+
+    /// Fills in the placeholders. Every variable the template uses must be given.
+    pub fn render(&self, variables: &[(&str, &str)]) -> Result<Vec<AIMessage>, ThetaError> {
+        let fill = |text: &str| -> Result<String, ThetaError> {
+            let mut missing = None;
+            let rendered = VARIABLE.replace_all(text, |captures: &regex::Captures| {
+                let name = &captures[1];
+                match variables.iter().find(|(key, _)| *key == name) {
+                    Some((_, value)) => value.to_string(),
+                    None => {
+                        missing.get_or_insert_with(|| name.to_string());
+                        String::new()
+                    }
+                }
+            });
+            match missing {
+                Some(name) => Err(ThetaError::InvalidInput(format!(
+                    "Prompt template '{}' needs a value for '{}'",
+                    self.name, name
+                ))),
+                None => Ok(rendered.into_owned()),
+            }
+        };
+
+        let mut messages = Vec::new();
+        if let Some(system) = &self.system {
+            messages.push(AIMessage::new("system".to_string(), fill(system)?));
+        }
+        messages.push(AIMessage::new("user".to_string(), fill(&self.template)?));
+        Ok(messages)
+    }
+
+    /// A request using the template's default model, temperature and token limit.
+    pub fn request(&self, variables: &[(&str, &str)], feature: AIFeature) -> Result<CompletionRequest, ThetaError> {
+        Ok(CompletionRequest {
+            messages: self.render(variables)?,
+            model: self.model.clone(),
+            temperature: self.temperature,
+            max_tokens: self.max_tokens,
+            response_schema: None,
+            feature,
+            tools: Vec::new(),
+        })
+    }
+
+    fn apply(base: Option<&PromptTemplate>, name: &str, changes: TemplateOverride, source: TemplateSource) -> Result<Self, String> {
+        let template = match (changes.template, base) {
+            (Some(template), _) => template,
+            (None, Some(base)) => base.template.clone(),
+            (None, None) => return Err(format!("new template '{}' has no 'template' text", name)),
+        };
+        if template.trim().is_empty() {
+            return Err(format!("template '{}' is empty", name));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            version: changes.version.or(base.map(|base| base.version)).unwrap_or(1),
+            description: changes.description.or_else(|| base.and_then(|base| base.description.clone())),
+            system: changes.system.or_else(|| base.and_then(|base| base.system.clone())),
+            template,
+            model: changes.model.or_else(|| base.and_then(|base| base.model.clone())),
+            temperature: changes.temperature.or(base.and_then(|base| base.temperature)),
+            max_tokens: changes.max_tokens.or(base.and_then(|base| base.max_tokens)),
+            source,
+        })
+    }
+}
+
+/// Prompt templates by name: built-ins, then files from the templates
+/// directory, then overrides saved in `user_data`. Later sources win.
+#[derive(Debug, Clone)]
+pub struct PromptRegistry {
+    templates: HashMap<String, PromptTemplate>,
+    directory: Option<PathBuf>,
+}
+
+impl PromptRegistry {
+    pub fn new() -> Self {
+        let mut templates = HashMap::new();
+        for template in builtin_templates() {
+            templates.insert(template.name.clone(), template);
+        }
+        Self { templates, directory: None }
+    }
+
+    /// Builds the registry from every source. Unreadable or invalid
+    /// templates are logged and skipped so one bad file cannot hide the rest.
+    pub async fn load(directory: Option<PathBuf>, pool: Option<&PgPool>) -> Self {
+        let mut registry = Self::new();
+
+        if let Some(directory) = &directory {
+            registry.load_directory(directory);
+        }
+        registry.directory = directory;
+
+        if let Some(pool) = pool {
+            match fetch_overrides(pool).await {
+                Ok(overrides) => {
+                    for (name, value) in overrides {
+                        if let Err(e) = registry.apply_override(&name, value, TemplateSource::UserData) {
+                            log::warn!("Ignoring prompt template override '{}': {}", name, e);
+                        }
+                    }
+                }
+                Err(e) => log::warn!("Failed to load prompt template overrides: {}", e),
+            }
+        }
+
+        registry
+    }
+
+    pub fn directory(&self) -> Option<&Path> {
+        self.directory.as_deref()
+    }
+
+    pub fn get(&self, name: &str) -> Result<&PromptTemplate, ThetaError> {
+        self.templates
+            .get(name)
+            .ok_or_else(|| ThetaError::NotFound(format!("Prompt template '{}' not found", name)))
+    }
+
+    pub fn list(&self) -> Vec<PromptTemplate> {
+        let mut templates: Vec<PromptTemplate> = self.templates.values().cloned().collect();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        templates
+    }
+
+    /// Applies `value` on top of the current template called `name`.
+    pub fn apply_override(
+        &mut self,
+        name: &str,
+        value: serde_json::Value,
+        source: TemplateSource,
+    ) -> Result<&PromptTemplate, String> {
+        let changes: TemplateOverride = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        let template = PromptTemplate::apply(self.templates.get(name), name, changes, source)?;
+        self.templates.insert(name.to_string(), template);
+        Ok(&self.templates[name])
+    }
+
+    /// Reads every `*.json` file in `directory`. The file name is the template
+    /// name unless the file sets `name`.
+    fn load_directory(&mut self, directory: &Path) {
+        let entries = match std::fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                log::warn!("Failed to read prompt templates from {}: {}", directory.display(), e);
+                return;
+            }
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let result = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|raw| serde_json::from_str::<serde_json::Value>(&raw).map_err(|e| e.to_string()))
+                .and_then(|value| {
+                    let name = value
+                        .get("name")
+                        .and_then(|name| name.as_str())
+                        .map(str::to_string)
+                        .or_else(|| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+                        .unwrap_or_default();
+                    self.apply_override(&name, value, TemplateSource::File).map(|_| ())
+                });
+
+            if let Err(e) = result {
+                log::warn!("Ignoring prompt template {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+impl Default for PromptRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn fetch_overrides(pool: &PgPool) -> Result<Vec<(String, serde_json::Value)>, sqlx::Error> {
+    sqlx::query_as("SELECT key, value FROM user_data WHERE category = $1 ORDER BY key")
+        .bind(OVERRIDE_CATEGORY)
+        .fetch_all(pool)
+        .await
+}
+
+fn builtin_templates() -> Vec<PromptTemplate> {
+    vec![
+        PromptTemplate {
+            name: CONTENT_ANALYSIS.to_string(),
+            version: 1,
+            description: Some("Structured analysis of a web page".to_string()),
+            system: Some("You analyze web pages. Reply only with a JSON object matching the content_analysis schema.".to_string()),
+            template: "Analyze this content:\n\n{{content}}".to_string(),
+            model: None,
+            temperature: Some(0.3),
+            max_tokens: Some(1000),
+            source: TemplateSource::Builtin,
+        },
+        PromptTemplate {
+            name: SUMMARY.to_string(),
+            version: 1,
+            description: Some("Short summary of a web page".to_string()),
+            system: Some("Summary generation".to_string()),
+            template: "Summarize this content in {{max_words}} words or less: {{content}}".to_string(),
+            model: None,
+            temperature: Some(0.3),
+            // Sized from `max_words` by the caller unless a template sets it
+            max_tokens: None,
+            source: TemplateSource::Builtin,
+        },
+        PromptTemplate {
+            name: SUMMARY_CHUNK.to_string(),
+            version: 1,
+            description: Some("Summary of one section of a long page".to_string()),
+            system: Some("Summary generation".to_string()),
+            template: "This is section {{index}} of {{count}} of a longer document. Summarize it in {{max_words}} words or less, keeping names, figures and conclusions:\n\n{{content}}".to_string(),
+            model: None,
+            temperature: Some(0.3),
+            max_tokens: None,
+            source: TemplateSource::Builtin,
+        },
+        PromptTemplate {
+            name: SUMMARY_REDUCE.to_string(),
+            version: 1,
+            description: Some("Combines section summaries of a long page".to_string()),
+            system: Some("Summary generation".to_string()),
+            template: "These are summaries of consecutive sections of one document. Combine them into a single summary of {{max_words}} words or less:\n\n{{content}}".to_string(),
+            model: None,
+            temperature: Some(0.3),
+            max_tokens: None,
+            source: TemplateSource::Builtin,
+        },
+        PromptTemplate {
+            name: TAB_CHAT.to_string(),
+            version: 1,
+            description: Some("Answers a question about an open tab from its text".to_string()),
+            system: Some("You answer questions about the web page \"{{title}}\" ({{url}}) using only the numbered passages below. Cite the passages you rely on as [n]. If the passages do not answer the question, say so.\n\nEarlier in this conversation: {{conversation}}\n\n{{passages}}".to_string()),
+            template: "{{question}}".to_string(),
+            model: None,
+            temperature: Some(0.2),
+            max_tokens: None,
+            source: TemplateSource::Builtin,
+        },
+        PromptTemplate {
+            name: WORKSPACE_CHAT.to_string(),
+            version: 1,
+            description: Some("Answers a question from the tabs of a workspace".to_string()),
+            system: Some("You answer questions using only the numbered passages below, taken from the web pages open in the workspace \"{{workspace}}\". Each passage gives the title and URL of its page. Cite the passages you rely on as [n]. Where pages disagree, say so and cite each side. If the passages do not answer the question, say so.\n\nEarlier in this conversation: {{conversation}}\n\n{{passages}}".to_string()),
+            template: "{{question}}".to_string(),
+            model: None,
+            temperature: Some(0.2),
+            max_tokens: None,
+            source: TemplateSource::Builtin,
+        },
+        PromptTemplate {
+            name: BROWSER_AGENT.to_string(),
+            version: 1,
+            description: Some("Carries out a request with browser tools".to_string()),
+            system: Some("You help the user with their web browser. Use the tools provided to carry out the request: open pages, search the web, save bookmarks, create tasks or run workflows. Only call the tools the request needs, and only with values the user gave or that earlier tool results returned. Some tools ask the user to confirm first; if one is declined, do not retry it. When you are done, briefly say what you did and give any answer the user asked for.".to_string()),
+            template: "{{request}}".to_string(),
+            model: None,
+            temperature: Some(0.2),
+            max_tokens: None,
+            source: TemplateSource::Builtin,
+        },
+        PromptTemplate {
+            name: TAB_CONTEXT.to_string(),
+            version: 2,
+            description: Some("Running summary of a conversation about a tab or workspace".to_string()),
+            system: Some("Summary generation".to_string()),
+            template: "Update this summary of a conversation about one or more web pages with the latest exchange. Keep what the user is trying to find out and what has been answered, in {{max_words}} words or less. Reply with the summary only.\n\nSummary so far: {{summary}}\n\nQuestion: {{question}}\n\nAnswer: {{answer}}".to_string(),
+            model: None,
+            temperature: Some(0.2),
+            max_tokens: None,
+            source: TemplateSource::Builtin,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_variables_and_reports_missing_ones() {
+        let registry = PromptRegistry::new();
+        let summary = registry.get(SUMMARY).unwrap();
+
+        assert_eq!(summary.variables(), vec!["max_words", "content"]);
+
+        let messages = summary.render(&[("content", "Some page"), ("max_words", "50")]).unwrap();
+        assert_eq!(messages.last().unwrap().content, "Summarize this content in 50 words or less: Some page");
+
+        let error = summary.render(&[("content", "Some page")]).unwrap_err();
+        assert!(error.to_string().contains("max_words"));
+    }
+
+    #[test]
+    fn overrides_keep_unset_fields_and_change_the_cache_version() {
+        let mut registry = PromptRegistry::new();
+        let before = registry.get(SUMMARY).unwrap().clone();
+
+        let after = registry
+            .apply_override(SUMMARY, serde_json::json!({ "temperature": 0.1, "version": 2 }), TemplateSource::UserData)
+            .unwrap();
+
+        assert_eq!(after.template, before.template);
+        assert_eq!(after.temperature, Some(0.1));
+        assert_eq!(after.source, TemplateSource::UserData);
+        assert_ne!(after.cache_version(), before.cache_version());
+    }
+
+    #[test]
+    fn new_templates_need_text() {
+        let mut registry = PromptRegistry::new();
+
+        assert!(registry.apply_override("tone", serde_json::json!({ "version": 1 }), TemplateSource::File).is_err());
+        assert!(registry.apply_override(SUMMARY, serde_json::json!({ "tmperature": 0.1 }), TemplateSource::File).is_err());
+        assert!(registry.apply_override("tone", serde_json::json!({ "template": "Rewrite: {{text}}" }), TemplateSource::File).is_ok());
+        assert_eq!(registry.get("tone").unwrap().version, 1);
+    }
+}
@@ -1,60 +1,372 @@
-use super::models::{AIProvider, AIModel};
+use super::anthropic::AnthropicProvider;
+use super::context;
+use super::models::{AIModel, AIProvider, CompletionRequest};
+use super::ollama::OllamaProvider;
+use super::openai::OpenAIProvider;
+use crate::models::AIResponse;
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use reqwest::{Client, Url};
 use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
 
+/// How long startup waits for a local server to answer its model-list call.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A piece of a streamed completion. The final `Done` chunk carries the
+/// assembled response along with its usage metadata.
+#[derive(Debug, Clone)]
+pub enum StreamChunk {
+    Delta(String),
+    Done(AIResponse),
+}
+
+pub type CompletionStream = BoxStream<'static, Result<StreamChunk, ProviderError>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderErrorKind {
+    /// Connection failures and timeouts
+    Network,
+    /// HTTP 429
+    RateLimited,
+    /// 5xx and other overload responses
+    Server,
+    /// Rejected request: bad credentials, unknown model, invalid payload
+    Client,
+    /// The provider answered but the body was not what we expected
+    InvalidResponse,
+    /// Required credentials are missing, so no request was sent
+    NotConfigured,
+    /// The caller dropped a stream before it finished
+    Cancelled,
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{provider}: {message}")]
+pub struct ProviderError {
+    pub provider: String,
+    pub kind: ProviderErrorKind,
+    pub message: String,
+    pub status: Option<u16>,
+    pub retry_after: Option<Duration>,
+}
+
+impl ProviderError {
+    pub fn new(provider: &str, kind: ProviderErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            provider: provider.to_string(),
+            kind,
+            message: message.into(),
+            status: None,
+            retry_after: None,
+        }
+    }
+
+    pub fn invalid_response(provider: &str, message: impl Into<String>) -> Self {
+        Self::new(provider, ProviderErrorKind::InvalidResponse, message)
+    }
+
+    pub fn from_reqwest(provider: &str, error: reqwest::Error) -> Self {
+        let kind = if error.is_decode() {
+            ProviderErrorKind::InvalidResponse
+        } else {
+            ProviderErrorKind::Network
+        };
+        Self::new(provider, kind, error.to_string())
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind,
+            ProviderErrorKind::Network | ProviderErrorKind::RateLimited | ProviderErrorKind::Server
+        )
+    }
+}
+
+/// Passes successful responses through and turns error statuses into a
+/// `ProviderError`, keeping any `Retry-After` hint.
+pub async fn check_status(provider: &str, response: reqwest::Response) -> Result<reqwest::Response, ProviderError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = retry_after(response.headers());
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|json| {
+            json["error"]["message"]
+                .as_str()
+                .or_else(|| json["error"].as_str())
+                .map(|message| message.to_string())
+        })
+        .unwrap_or(body);
+
+    let kind = match status.as_u16() {
+        429 => ProviderErrorKind::RateLimited,
+        // 529 is Anthropic's "overloaded"
+        408 | 409 | 500..=599 => ProviderErrorKind::Server,
+        _ => ProviderErrorKind::Client,
+    };
+
+    Err(ProviderError {
+        provider: provider.to_string(),
+        kind,
+        message: format!("{} {}", status, message),
+        status: Some(status.as_u16()),
+        retry_after,
+    })
+}
+
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    // OpenAI sends a millisecond-precision variant alongside the standard header
+    if let Some(ms) = headers.get("retry-after-ms").and_then(|value| value.to_str().ok()) {
+        if let Ok(ms) = ms.trim().parse::<f64>() {
+            return Some(Duration::from_millis(ms as u64));
+        }
+    }
+
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
+
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    fn config(&self) -> &AIProvider;
+
+    fn name(&self) -> &str {
+        &self.config().name
+    }
+
+    fn default_model(&self) -> &str {
+        self.config().models.first().map(String::as_str).unwrap_or("")
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<AIResponse, ProviderError>;
+
+    /// Providers without native streaming yield the whole completion as a
+    /// single delta.
+    async fn stream(&self, request: &CompletionRequest) -> Result<CompletionStream, ProviderError> {
+        let response = self.complete(request).await?;
+        let chunks = vec![
+            Ok(StreamChunk::Delta(response.content.clone())),
+            Ok(StreamChunk::Done(response)),
+        ];
+        Ok(stream::iter(chunks).boxed())
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, ProviderError>;
+
+    fn count_tokens(&self, text: &str) -> u32 {
+        context::count_tokens(text)
+    }
+
+    fn model_info(&self, model: &str) -> AIModel {
+        AIModel::for_name(model, self.name())
+    }
+
+    /// Whether requests stay on this machine or the local network, which is
+    /// what privacy mode allows.
+    fn is_local(&self) -> bool {
+        is_local_endpoint(&self.config().endpoint)
+    }
+}
+
+/// Whether `endpoint` is on this machine or a private network: `localhost`,
+/// an mDNS `.local` name, or a loopback, private or link-local address.
+pub fn is_local_endpoint(endpoint: &str) -> bool {
+    let endpoint = endpoint.trim();
+    let parsed = if endpoint.contains("://") {
+        Url::parse(endpoint)
+    } else {
+        Url::parse(&format!("http://{}", endpoint))
+    };
+    let Some(host) = parsed.ok().and_then(|url| url.host_str().map(str::to_ascii_lowercase)) else {
+        return false;
+    };
+
+    match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => is_local_ipv4(ip),
+        Ok(IpAddr::V6(ip)) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_local_ipv4(ip),
+            // fc00::/7 is unique local, fe80::/10 link-local
+            None => ip.is_loopback() || (ip.segments()[0] & 0xfe00) == 0xfc00 || (ip.segments()[0] & 0xffc0) == 0xfe80,
+        },
+        Err(_) => host == "localhost" || host.ends_with(".localhost") || host.ends_with(".local"),
+    }
+}
+
+fn is_local_ipv4(ip: Ipv4Addr) -> bool {
+    ip.is_loopback() || ip.is_private() || ip.is_link_local()
+}
+
+#[derive(Clone)]
 pub struct ProviderManager {
-    providers: HashMap<String, AIProvider>,
+    providers: HashMap<String, Arc<dyn LlmProvider>>,
+    order: Vec<String>,
 }
 
 impl ProviderManager {
     pub fn new() -> Self {
-        let mut manager = Self {
+        Self {
             providers: HashMap::new(),
-        };
-        
-        // Initialize default providers
-        manager.init_default_providers();
-        manager
+            order: Vec::new(),
+        }
     }
 
-    fn init_default_providers(&mut self) {
+    pub fn register_default_providers(&mut self, client: &Client) {
         // OpenAI
-        let mut openai = AIProvider::new(
-            "openai".to_string(),
-            "https://api.openai.com/v1".to_string(),
-            std::env::var("OPENAI_API_KEY").ok(),
-        );
-        openai.add_model("gpt-4".to_string());
-        openai.add_model("gpt-3.5-turbo".to_string());
-        self.providers.insert("openai".to_string(), openai);
+        if let Ok(key) = std::env::var("OPENAI_API_KEY") {
+            let mut openai = AIProvider::new(
+                "openai".to_string(),
+                "https://api.openai.com/v1".to_string(),
+                Some(key),
+            );
+            openai.add_model("gpt-4".to_string());
+            openai.add_model("gpt-3.5-turbo".to_string());
+            self.add_provider(Arc::new(OpenAIProvider::new(openai, client.clone())));
+        }
 
         // Anthropic
-        let mut anthropic = AIProvider::new(
-            "anthropic".to_string(),
-            "https://api.anthropic.com/v1".to_string(),
-            std::env::var("ANTHROPIC_API_KEY").ok(),
-        );
-        anthropic.add_model("claude-3-sonnet-20240229".to_string());
-        anthropic.add_model("claude-3-haiku-20240307".to_string());
-        self.providers.insert("anthropic".to_string(), anthropic);
+        if let Ok(key) = std::env::var("ANTHROPIC_API_KEY") {
+            let mut anthropic = AIProvider::new(
+                "anthropic".to_string(),
+                "https://api.anthropic.com/v1".to_string(),
+                Some(key),
+            );
+            anthropic.add_model("claude-3-sonnet-20240229".to_string());
+            anthropic.add_model("claude-3-haiku-20240307".to_string());
+            self.add_provider(Arc::new(AnthropicProvider::new(anthropic, client.clone())));
+        }
     }
 
-    pub fn get_provider(&self, name: &str) -> Option<&AIProvider> {
-        self.providers.get(name)
+    /// Registers self-hosted backends that answer a model-list call: Ollama at
+    /// `OLLAMA_ENDPOINT` (default `http://localhost:11434`) and an
+    /// OpenAI-compatible server at `OPENAI_COMPATIBLE_ENDPOINT`.
+    pub async fn discover_local_providers(&mut self, client: &Client) {
+        let ollama_endpoint = std::env::var("OLLAMA_ENDPOINT")
+            .unwrap_or_else(|_| "http://localhost:11434".to_string());
+        let ollama = AIProvider::new("ollama".to_string(), ollama_endpoint, None);
+        self.register_discovered(ollama, client, |config, client| {
+            Arc::new(OllamaProvider::new(config, client))
+        })
+        .await;
+
+        if let Ok(endpoint) = std::env::var("OPENAI_COMPATIBLE_ENDPOINT") {
+            let mut compatible = AIProvider::new(
+                "openai-compatible".to_string(),
+                endpoint.trim_end_matches('/').to_string(),
+                std::env::var("OPENAI_COMPATIBLE_API_KEY").ok(),
+            );
+            if let Ok(model) = std::env::var("OPENAI_COMPATIBLE_MODEL") {
+                compatible.add_model(model);
+            }
+            self.register_discovered(compatible, client, |config, client| {
+                Arc::new(OpenAIProvider::new(config, client))
+            })
+            .await;
+        }
+    }
+
+    /// Asks the server for its models and registers it with that list, or
+    /// skips it when it is unreachable or has nothing installed.
+    async fn register_discovered(
+        &mut self,
+        mut config: AIProvider,
+        client: &Client,
+        build: fn(AIProvider, Client) -> Arc<dyn LlmProvider>,
+    ) {
+        let probe = build(config.clone(), client.clone());
+        let models = match tokio::time::timeout(DISCOVERY_TIMEOUT, probe.list_models()).await {
+            Ok(Ok(models)) => models,
+            Ok(Err(e)) => {
+                log::info!("Local AI provider '{}' not available: {}", config.name, e);
+                return;
+            }
+            Err(_) => {
+                log::info!("Local AI provider '{}' did not respond", config.name);
+                return;
+            }
+        };
+
+        for model in models {
+            if !config.models.contains(&model) {
+                config.add_model(model);
+            }
+        }
+
+        if config.models.is_empty() {
+            log::info!("Local AI provider '{}' has no models installed", config.name);
+            return;
+        }
+
+        log::info!("Discovered local AI provider '{}' with {} models", config.name, config.models.len());
+        self.add_provider(build(config, client.clone()));
     }
 
-    pub fn add_provider(&mut self, name: String, provider: AIProvider) {
+    pub fn get_provider(&self, name: &str) -> Option<Arc<dyn LlmProvider>> {
+        self.providers.get(name).cloned()
+    }
+
+    pub fn add_provider(&mut self, provider: Arc<dyn LlmProvider>) {
+        let name = provider.name().to_string();
+        if !self.order.contains(&name) {
+            self.order.push(name.clone());
+        }
         self.providers.insert(name, provider);
     }
 
+    /// Provider names in registration order.
     pub fn list_providers(&self) -> Vec<&String> {
-        self.providers.keys().collect()
+        self.order.iter().collect()
     }
 
     pub fn get_available_models(&self, provider_name: &str) -> Vec<&String> {
         if let Some(provider) = self.providers.get(provider_name) {
-            provider.models.iter().collect()
+            provider.config().models.iter().collect()
         } else {
             vec![]
         }
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_endpoints_are_checked_by_address_not_prefix() {
+        for local in [
+            "http://localhost:11434",
+            "localhost:1234/v1",
+            "http://127.0.0.1:8080",
+            "http://[::1]:11434/api",
+            "http://10.0.0.5",
+            "http://172.20.1.2:8000",
+            "http://192.168.1.10:11434",
+            "http://[fd12::1]",
+            "http://llm-box.local:11434",
+        ] {
+            assert!(is_local_endpoint(local), "{} should be local", local);
+        }
+        for remote in [
+            "https://api.openai.com/v1",
+            "http://10.example.com",
+            "http://localhost.evil.com",
+            "http://172.32.0.1",
+            "http://[2001:db8::1]",
+            "http://192.168.example.org",
+        ] {
+            assert!(!is_local_endpoint(remote), "{} should not be local", remote);
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use super::models::CompletionRequest;
+use super::providers::ProviderError;
+use crate::error::ThetaError;
+use crate::models::{AIFeature, AIResponse, AIRunSummary};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Runs kept in the log; older ones are deleted when the app starts.
+const MAX_RUNS: usize = 1000;
+
+/// One request sent to a provider, as recorded in the run log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedCall {
+    pub run_id: String,
+    pub recorded_at: chrono::DateTime<chrono::Utc>,
+    pub provider: String,
+    pub model: String,
+    pub feature: AIFeature,
+    /// Messages, parameters and tools exactly as passed to the provider.
+    pub request: CompletionRequest,
+    /// Unset when the call failed.
+    pub response: Option<AIResponse>,
+    /// The provider's response body, when it returned a single one.
+    pub raw_response: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub latency_ms: u64,
+}
+
+impl RecordedCall {
+    pub fn new(
+        run_id: &str,
+        provider: &str,
+        request: &CompletionRequest,
+        result: &Result<AIResponse, ProviderError>,
+        latency: Duration,
+    ) -> Self {
+        let (response, error) = match result {
+            Ok(response) => (Some(response.clone()), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        Self {
+            run_id: run_id.to_string(),
+            recorded_at: chrono::Utc::now(),
+            provider: provider.to_string(),
+            model: response.as_ref().map(|response| response.model.clone()).or_else(|| request.model.clone()).unwrap_or_default(),
+            feature: request.feature,
+            request: request.clone(),
+            raw_response: response.as_ref().and_then(|response| response.raw.clone()),
+            response,
+            error,
+            latency_ms: latency.as_millis() as u64,
+        }
+    }
+}
+
+/// Writes every provider call to a local log, one JSON Lines file per run,
+/// so a wrong answer can be traced back to what was sent and received.
+#[derive(Clone)]
+pub struct RunRecorder {
+    directory: PathBuf,
+    /// Keeps concurrent calls of a run from interleaving their lines.
+    writing: Arc<Mutex<()>>,
+}
+
+impl RunRecorder {
+    /// Logs to `directory`, creating it and deleting the oldest runs beyond
+    /// the ones kept.
+    pub async fn open(directory: PathBuf) -> Result<Self, ThetaError> {
+        tokio::fs::create_dir_all(&directory)
+            .await
+            .map_err(|e| ThetaError::NotConfigured(format!("Cannot create the AI run log at {}: {}", directory.display(), e)))?;
+
+        let recorder = Self { directory, writing: Arc::new(Mutex::new(())) };
+        for (path, _) in recorder.run_files().await.into_iter().skip(MAX_RUNS) {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+        Ok(recorder)
+    }
+
+    /// Appends a call to its run. Failing to record never fails the call.
+    pub async fn record(&self, call: &RecordedCall) {
+        let Ok(mut line) = serde_json::to_string(call) else { return };
+        line.push('\n');
+
+        let _writing = self.writing.lock().await;
+        let written = async {
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.path(&call.run_id)?)
+                .await?;
+            file.write_all(line.as_bytes()).await
+        };
+        if let Err(e) = written.await {
+            log::warn!("Failed to record AI run {}: {}", call.run_id, e);
+        }
+    }
+
+    /// The most recent runs, newest first.
+    pub async fn list_runs(&self, limit: usize) -> Result<Vec<AIRunSummary>, ThetaError> {
+        let mut runs = Vec::new();
+        for (path, _) in self.run_files().await.into_iter().take(limit) {
+            let Some(run_id) = path.file_stem().and_then(|stem| stem.to_str()) else { continue };
+            let calls = self.load_run(run_id).await?;
+            if let Some(summary) = summarize(run_id, &calls) {
+                runs.push(summary);
+            }
+        }
+        Ok(runs)
+    }
+
+    /// Every recorded call of a run, in the order they were made.
+    pub async fn load_run(&self, run_id: &str) -> Result<Vec<RecordedCall>, ThetaError> {
+        let path = self.path(run_id).map_err(|e| ThetaError::InvalidInput(e.to_string()))?;
+        let text = match tokio::fs::read_to_string(&path).await {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(ThetaError::NotFound(format!("AI run {} not found", run_id)));
+            }
+            Err(e) => return Err(ThetaError::ai(format!("Failed to read AI run {}: {}", run_id, e))),
+        };
+
+        parse_run(&text)
+    }
+
+    /// Run ids are UUIDs, which also keeps them from naming paths outside
+    /// the log directory.
+    fn path(&self, run_id: &str) -> Result<PathBuf, std::io::Error> {
+        let run_id = Uuid::parse_str(run_id)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("'{}' is not a run id", run_id)))?;
+        Ok(self.directory.join(format!("{}.jsonl", run_id)))
+    }
+
+    /// Run files, most recently written first.
+    async fn run_files(&self) -> Vec<(PathBuf, std::time::SystemTime)> {
+        let mut files = Vec::new();
+        let Ok(mut entries) = tokio::fs::read_dir(&self.directory).await else { return files };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().is_some_and(|extension| extension == "jsonl") {
+                if let Ok(modified) = entry.metadata().await.and_then(|metadata| metadata.modified()) {
+                    files.push((path, modified));
+                }
+            }
+        }
+        files.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+        files
+    }
+}
+
+/// Reads a run from its JSON Lines log, e.g. a fixture copied from one.
+pub fn parse_run(text: &str) -> Result<Vec<RecordedCall>, ThetaError> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| ThetaError::InvalidInput(format!("Line {} of the AI run is invalid: {}", index + 1, e)))
+        })
+        .collect()
+}
+
+fn summarize(run_id: &str, calls: &[RecordedCall]) -> Option<AIRunSummary> {
+    let first = calls.first()?;
+
+    let mut providers: Vec<String> = Vec::new();
+    for call in calls {
+        if !providers.contains(&call.provider) {
+            providers.push(call.provider.clone());
+        }
+    }
+
+    Some(AIRunSummary {
+        run_id: run_id.to_string(),
+        feature: first.feature,
+        started_at: first.recorded_at,
+        calls: calls.len(),
+        failed_calls: calls.iter().filter(|call| call.error.is_some()).count(),
+        providers,
+        total_tokens: calls.iter().filter_map(|call| call.response.as_ref()).map(|response| response.tokens_used).sum(),
+        latency_ms: calls.iter().map(|call| call.latency_ms).sum(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::models::AIProvider;
+    use crate::ai::{AIServiceManager, CompletionStream, LlmProvider, ProviderErrorKind, StreamChunk};
+    use async_trait::async_trait;
+    use futures::StreamExt;
+
+    fn response(content: &str) -> AIResponse {
+        AIResponse {
+            content: content.to_string(),
+            model: "llama3.2".to_string(),
+            provider: "ollama".to_string(),
+            input_tokens: 3,
+            output_tokens: 2,
+            tokens_used: 5,
+            cost_usd: None,
+            processing_time: 12,
+            confidence: None,
+            tool_calls: Vec::new(),
+            run_id: None,
+            raw: Some(serde_json::json!({ "message": { "content": content } })),
+        }
+    }
+
+    #[tokio::test]
+    async fn records_calls_by_run_and_lists_newest_first() {
+        let directory = std::env::temp_dir().join(format!("theta-runs-{}", Uuid::new_v4()));
+        let recorder = RunRecorder::open(directory.clone()).await.unwrap();
+        let request = CompletionRequest::from_prompt("Hello");
+        let (first, second) = (Uuid::new_v4().to_string(), Uuid::new_v4().to_string());
+
+        let failure = Err(ProviderError::new("openai", ProviderErrorKind::RateLimited, "slow down"));
+        recorder.record(&RecordedCall::new(&first, "openai", &request, &failure, Duration::from_millis(40))).await;
+        recorder.record(&RecordedCall::new(&first, "ollama", &request, &Ok(response("Hi")), Duration::from_millis(60))).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        recorder.record(&RecordedCall::new(&second, "ollama", &request, &Ok(response("Hey")), Duration::from_millis(5))).await;
+
+        let calls = recorder.load_run(&first).await.unwrap();
+        assert_eq!(calls.len(), 2);
+        assert!(calls[0].error.as_deref().unwrap().contains("slow down"));
+        assert_eq!(calls[1].raw_response.as_ref().unwrap()["message"]["content"], "Hi");
+        assert_eq!(calls[1].request.messages[0].content, "Hello");
+
+        let runs = recorder.list_runs(10).await.unwrap();
+        assert_eq!(runs.iter().map(|run| run.run_id.as_str()).collect::<Vec<_>>(), vec![second.as_str(), first.as_str()]);
+        assert_eq!(runs[1].failed_calls, 1);
+        assert_eq!(runs[1].providers, vec!["openai", "ollama"]);
+        assert_eq!(runs[1].latency_ms, 100);
+
+        assert!(matches!(recorder.load_run("../secrets").await, Err(ThetaError::InvalidInput(_))));
+        let _ = std::fs::remove_dir_all(directory);
+    }
+
+    /// Streams "Hel", "lo" and then either finishes or fails.
+    struct StreamingProvider {
+        config: AIProvider,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl LlmProvider for StreamingProvider {
+        fn config(&self) -> &AIProvider {
+            &self.config
+        }
+
+        async fn complete(&self, _request: &CompletionRequest) -> Result<AIResponse, ProviderError> {
+            Ok(response("Hello"))
+        }
+
+        async fn stream(&self, request: &CompletionRequest) -> Result<CompletionStream, ProviderError> {
+            let end = if self.fail {
+                Err(ProviderError::new(self.name(), ProviderErrorKind::Server, "connection reset"))
+            } else {
+                Ok(StreamChunk::Done(self.complete(request).await?))
+            };
+            Ok(futures::stream::iter(vec![Ok(StreamChunk::Delta("Hel".to_string())), Ok(StreamChunk::Delta("lo".to_string())), end]).boxed())
+        }
+
+        async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn records_failed_and_cancelled_streams() {
+        let directory = std::env::temp_dir().join(format!("theta-runs-{}", Uuid::new_v4()));
+        let recorder = RunRecorder::open(directory.clone()).await.unwrap();
+        let request = CompletionRequest::from_prompt("Hello");
+
+        let mut outcomes = Vec::new();
+        for (fail, chunks_read) in [(true, 3), (false, 1), (false, 3)] {
+            let run_id = Uuid::new_v4().to_string();
+            let mut services = AIServiceManager::new();
+            services.add_service(Arc::new(StreamingProvider {
+                config: AIProvider::new("ollama".to_string(), "http://localhost:11434".to_string(), None),
+                fail,
+            }));
+            services.recorder = Some(recorder.clone());
+            let services = services.in_run(&run_id);
+
+            let mut stream = services.stream_with_failover(None, &request).await.unwrap();
+            for _ in 0..chunks_read {
+                stream.next().await;
+            }
+            drop(stream);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+
+            let calls = recorder.load_run(&run_id).await.unwrap();
+            assert_eq!(calls.len(), 1);
+            outcomes.push((calls[0].error.clone(), calls[0].response.as_ref().map(|response| response.content.clone())));
+        }
+
+        assert!(outcomes[0].0.as_deref().unwrap().contains("connection reset"));
+        assert!(outcomes[1].0.as_deref().unwrap().contains("stopped before it finished"));
+        assert_eq!(outcomes[2], (None, Some("Hello".to_string())));
+        let _ = std::fs::remove_dir_all(directory);
+    }
+}
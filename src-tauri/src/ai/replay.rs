@@ -0,0 +1,276 @@
+use super::models::{AIMessage, AIProvider, CompletionRequest, ToolDefinition};
+use super::providers::{LlmProvider, ProviderError, ProviderErrorKind};
+use super::recorder::{parse_run, RecordedCall};
+use super::service::AIServiceManager;
+use super::tools::{run_with_tools, Tool, ToolConfirmation, ToolRegistry, DEFAULT_MAX_STEPS, STEP_LIMIT_NOTE};
+use crate::error::ThetaError;
+use crate::models::{AIFeature, AIResponse, AIRunReplay, ToolCall};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// A mock provider answering with the responses of a recorded run, in
+/// order, and noting every request that differs from the recorded one.
+/// Calls that failed in the recording are left out, so the replay gets the
+/// answer the recording eventually got.
+pub struct ReplayProvider {
+    config: AIProvider,
+    calls: Mutex<VecDeque<RecordedCall>>,
+    replayed: Mutex<usize>,
+    divergences: Mutex<Vec<String>>,
+}
+
+impl ReplayProvider {
+    pub fn new(calls: &[RecordedCall]) -> Self {
+        Self {
+            config: AIProvider::new("replay".to_string(), "http://localhost/replay".to_string(), None),
+            calls: Mutex::new(calls.iter().filter(|call| call.response.is_some()).cloned().collect()),
+            replayed: Mutex::new(0),
+            divergences: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Loads a run log file saved as a test fixture.
+    pub fn from_fixture(text: &str) -> Result<Self, ThetaError> {
+        Ok(Self::new(&parse_run(text)?))
+    }
+
+    pub fn replayed_calls(&self) -> usize {
+        *self.replayed.lock().unwrap()
+    }
+
+    pub fn divergences(&self) -> Vec<String> {
+        self.divergences.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl LlmProvider for ReplayProvider {
+    fn config(&self) -> &AIProvider {
+        &self.config
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<AIResponse, ProviderError> {
+        let number = {
+            let mut replayed = self.replayed.lock().unwrap();
+            *replayed += 1;
+            *replayed
+        };
+
+        let Some(recorded) = self.calls.lock().unwrap().pop_front() else {
+            let message = format!("Call {} was not in the recording", number);
+            self.divergences.lock().unwrap().push(message.clone());
+            return Err(ProviderError::new(self.name(), ProviderErrorKind::InvalidResponse, message));
+        };
+        if let Some(difference) = compare(&recorded.request, request) {
+            self.divergences.lock().unwrap().push(format!("Call {}: {}", number, difference));
+        }
+
+        let mut response = recorded.response.unwrap_or_else(|| unreachable!("failed calls are not replayed"));
+        response.run_id = None;
+        Ok(response)
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+        Ok(Vec::new())
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+/// Re-executes a recorded run against its recorded responses. Agent runs go
+/// through the tool loop again, with each tool returning its recorded
+/// result rather than acting on the browser; other runs re-send their
+/// recorded requests in order.
+pub async fn replay_run(run_id: &str, calls: &[RecordedCall]) -> Result<AIRunReplay, ThetaError> {
+    let recorded: Vec<&RecordedCall> = calls.iter().filter(|call| call.response.is_some()).collect();
+    let first = *recorded
+        .first()
+        .ok_or_else(|| ThetaError::InvalidInput(format!("AI run {} has no successful calls to replay", run_id)))?;
+
+    let provider = Arc::new(ReplayProvider::new(calls));
+    let mut services = AIServiceManager::new();
+    services.add_service(provider.clone());
+    // A replayed failure is a divergence, not something to wait out
+    services.retry_policy.max_retries = 0;
+
+    let answer = if first.feature == AIFeature::Agent {
+        let last = recorded[recorded.len() - 1];
+        let stopped_at_limit = last.request.messages.last().is_some_and(|message| message.content == STEP_LIMIT_NOTE);
+        let max_steps = if stopped_at_limit { recorded.len() - 1 } else { DEFAULT_MAX_STEPS.max(recorded.len()) };
+
+        run_with_tools(
+            &services,
+            None,
+            first.request.clone(),
+            &recorded_tools(&first.request.tools, &last.request.messages),
+            &ApproveAll,
+            max_steps,
+            &|_| {},
+        )
+        .await
+        .map(|run| run.answer)
+    } else {
+        let mut answer = Err(ThetaError::ai("Nothing was replayed"));
+        for call in &recorded {
+            answer = services.complete_with_failover(None, &call.request).await.map(|response| response.content);
+            if answer.is_err() {
+                break;
+            }
+        }
+        answer
+    };
+
+    let mut divergences = provider.divergences();
+    let answer = match answer {
+        Ok(answer) => Some(answer),
+        Err(e) => {
+            divergences.push(format!("The replay failed: {}", e));
+            None
+        }
+    };
+    if provider.replayed_calls() < recorded.len() {
+        divergences.push(format!("Only {} of {} recorded calls were made", provider.replayed_calls(), recorded.len()));
+    }
+
+    Ok(AIRunReplay {
+        run_id: run_id.to_string(),
+        recorded_calls: recorded.len(),
+        replayed_calls: provider.replayed_calls(),
+        divergences,
+        answer,
+    })
+}
+
+/// What differs between a recorded request and a replayed one, if anything.
+fn compare(recorded: &CompletionRequest, replayed: &CompletionRequest) -> Option<String> {
+    if recorded.messages.len() != replayed.messages.len() {
+        return Some(format!(
+            "{} messages were sent where {} were recorded",
+            replayed.messages.len(),
+            recorded.messages.len()
+        ));
+    }
+    let same_message = |a: &AIMessage, b: &AIMessage| {
+        a.role == b.role && a.content == b.content && a.tool_calls == b.tool_calls && a.tool_call_id == b.tool_call_id
+    };
+    if let Some(index) = recorded.messages.iter().zip(&replayed.messages).position(|(a, b)| !same_message(a, b)) {
+        return Some(format!("message {} ({}) differs from the recording", index + 1, replayed.messages[index].role));
+    }
+
+    let tool_names = |request: &CompletionRequest| request.tools.iter().map(|tool| tool.name.clone()).collect::<Vec<_>>();
+    if tool_names(recorded) != tool_names(replayed) {
+        return Some(format!("tools {:?} were offered where {:?} were recorded", tool_names(replayed), tool_names(recorded)));
+    }
+    if recorded.model != replayed.model || recorded.temperature != replayed.temperature || recorded.max_tokens != replayed.max_tokens {
+        return Some("the model, temperature or token limit differs from the recording".to_string());
+    }
+    None
+}
+
+/// Tools that return the results recorded for them, in order. The last
+/// request of a run holds every tool call and result of the run. Call ids
+/// are only unique within a turn (Ollama numbers them from 0 each time), so
+/// results are matched to the calls of the turn before them.
+fn recorded_tools(definitions: &[ToolDefinition], messages: &[AIMessage]) -> ToolRegistry {
+    let mut outputs: HashMap<&str, VecDeque<Value>> = HashMap::new();
+    let mut turn: &[ToolCall] = &[];
+    for message in messages {
+        if !message.tool_calls.is_empty() {
+            turn = &message.tool_calls;
+            continue;
+        }
+        let Some(call) = message.tool_call_id.as_deref().and_then(|id| turn.iter().find(|call| call.id == id)) else { continue };
+        let output = serde_json::from_str(&message.content).unwrap_or_else(|_| Value::String(message.content.clone()));
+        outputs.entry(call.name.as_str()).or_default().push_back(output);
+    }
+
+    let mut tools = ToolRegistry::new();
+    for definition in definitions {
+        let outputs = outputs.remove(definition.name.as_str()).unwrap_or_default();
+        tools.register(Arc::new(RecordedTool { definition: definition.clone(), outputs: Mutex::new(outputs) }));
+    }
+    tools
+}
+
+/// Stands in for a tool during a replay, so replaying never repeats an action.
+struct RecordedTool {
+    definition: ToolDefinition,
+    outputs: Mutex<VecDeque<Value>>,
+}
+
+#[async_trait]
+impl Tool for RecordedTool {
+    fn definition(&self) -> ToolDefinition {
+        self.definition.clone()
+    }
+
+    fn has_side_effects(&self) -> bool {
+        false
+    }
+
+    async fn call(&self, _arguments: Value) -> Result<Value, ThetaError> {
+        self.outputs
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| ThetaError::InvalidInput(format!("No recorded result for another call of '{}'", self.definition.name)))
+    }
+}
+
+struct ApproveAll;
+
+#[async_trait]
+impl ToolConfirmation for ApproveAll {
+    async fn confirm(&self, _call: &ToolCall, _tool: &ToolDefinition) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AGENT_RUN: &str = include_str!("../../tests/fixtures/runs/agent_open_rust_book.jsonl");
+
+    #[tokio::test]
+    async fn a_recorded_agent_run_replays_exactly() {
+        let calls = parse_run(AGENT_RUN).unwrap();
+
+        let replay = replay_run("agent_open_rust_book", &calls).await.unwrap();
+
+        assert_eq!(replay.divergences, Vec::<String>::new());
+        assert_eq!(replay.recorded_calls, 3);
+        assert_eq!(replay.replayed_calls, 3);
+        assert_eq!(replay.answer.as_deref(), Some("I found The Rust Programming Language and opened it in a new tab."));
+    }
+
+    #[tokio::test]
+    async fn reports_where_a_replay_diverges() {
+        let mut calls = parse_run(AGENT_RUN).unwrap();
+        // As if the loop had started sending a different temperature on its second call
+        let second = calls.iter_mut().filter(|call| call.response.is_some()).nth(1).unwrap();
+        second.request.temperature = Some(0.9);
+
+        let replay = replay_run("agent_open_rust_book", &calls).await.unwrap();
+
+        assert_eq!(replay.divergences.len(), 1);
+        assert!(replay.divergences[0].starts_with("Call 2: the model, temperature"), "{:?}", replay.divergences);
+        assert!(replay.answer.is_some());
+    }
+
+    #[tokio::test]
+    async fn fixtures_work_as_a_mock_provider() {
+        let provider = Arc::new(ReplayProvider::from_fixture(AGENT_RUN).unwrap());
+        let mut services = AIServiceManager::new();
+        services.add_service(provider.clone());
+
+        let response = services.complete_with_failover(None, &CompletionRequest::from_prompt("Something else")).await.unwrap();
+
+        assert_eq!(response.tool_calls[0].name, "search_web");
+        assert_eq!(provider.divergences().len(), 1);
+    }
+}
@@ -1,84 +1,381 @@
-use reqwest::Client;
-use serde_json::Value;
-use std::collections::HashMap;
+use super::models::{CompletionRequest, TokenUsage};
+use super::pricing;
+use super::recorder::{RecordedCall, RunRecorder};
+use super::providers::{CompletionStream, LlmProvider, ProviderError, ProviderErrorKind, ProviderManager, StreamChunk};
+use super::usage::UsageLedger;
+use crate::error::ThetaError;
+use crate::models::{AIFeature, AIResponse};
+use futures::StreamExt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
 
-pub struct AIServiceManager {
-    pub services: HashMap<String, AIService>,
-    pub client: Client,
+/// How transient provider failures are retried before failing over.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(8),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff for the given retry (0-based), or the provider's
+    /// `Retry-After` hint when it sent one.
+    fn delay(&self, retry: u32, error: &ProviderError) -> Duration {
+        error.retry_after.unwrap_or_else(|| {
+            self.base_delay
+                .saturating_mul(2u32.saturating_pow(retry))
+                .min(self.max_delay)
+        })
+    }
 }
 
 #[derive(Clone)]
-pub struct AIService {
-    pub name: String,
-    pub endpoint: String,
-    pub api_key: Option<String>,
-    pub model: String,
+pub struct AIServiceManager {
+    pub providers: ProviderManager,
+    /// When set, only providers running locally may be used.
+    pub privacy_mode: bool,
+    pub retry_policy: RetryPolicy,
+    /// Provider names tried first, in order. Providers not listed follow in
+    /// registration order.
+    pub priority: Vec<String>,
+    /// Where completed requests are recorded; unset until the database is up.
+    pub ledger: Option<UsageLedger>,
+    /// Where every provider call is logged for debugging; unset when disabled.
+    pub recorder: Option<RunRecorder>,
+    /// Run that calls are logged under. When unset each request is its own run.
+    pub run_id: Option<String>,
 }
 
 impl AIServiceManager {
     pub fn new() -> Self {
         Self {
-            services: HashMap::new(),
-            client: Client::new(),
+            providers: ProviderManager::new(),
+            privacy_mode: false,
+            retry_policy: RetryPolicy::default(),
+            priority: Vec::new(),
+            ledger: None,
+            recorder: None,
+            run_id: None,
         }
     }
 
-    pub fn add_service(&mut self, name: String, service: AIService) {
-        self.services.insert(name, service);
+    /// A copy that logs every call it makes under `run_id`, so the calls
+    /// behind one answer can be inspected and replayed together.
+    pub fn in_run(&self, run_id: &str) -> Self {
+        Self { run_id: Some(run_id.to_string()), ..self.clone() }
     }
 
-    pub async fn call_service(&self, service_name: &str, prompt: &str) -> Result<String, String> {
-        if let Some(service) = self.services.get(service_name) {
-            match service.name.as_str() {
-                "openai" => self.call_openai(service, prompt).await,
-                "anthropic" => self.call_anthropic(service, prompt).await,
-                _ => Err("Unknown service".to_string()),
+    pub fn add_service(&mut self, provider: Arc<dyn LlmProvider>) {
+        self.providers.add_provider(provider);
+    }
+
+    pub fn get_service(&self, service_name: &str) -> Result<Arc<dyn LlmProvider>, ThetaError> {
+        let provider = self.providers
+            .get_provider(service_name)
+            .ok_or_else(|| ThetaError::AiUnavailable(format!("AI service '{}' not available", service_name)))?;
+
+        if self.privacy_mode && !provider.is_local() {
+            return Err(ThetaError::AiUnavailable(format!(
+                "AI service '{}' is not available in privacy mode",
+                service_name
+            )));
+        }
+
+        Ok(provider)
+    }
+
+    /// Usable providers in priority order, then registration order.
+    pub fn ordered_services(&self) -> Vec<Arc<dyn LlmProvider>> {
+        let registered = self.providers.list_providers();
+        let mut names: Vec<&String> = self.priority
+            .iter()
+            .filter(|name| registered.contains(name))
+            .collect();
+        for name in registered {
+            if !names.contains(&name) {
+                names.push(name);
             }
-        } else {
-            Err("Service not found".to_string())
-        }
-    }
-
-    async fn call_openai(&self, service: &AIService, prompt: &str) -> Result<String, String> {
-        let payload = serde_json::json!({
-            "model": service.model,
-            "messages": [{"role": "user", "content": prompt}],
-            "temperature": 0.7
-        });
-
-        let response = self.client
-            .post(&format!("{}/chat/completions", service.endpoint))
-            .header("Authorization", format!("Bearer {}", service.api_key.as_ref().unwrap_or(&"".to_string())))
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
-
-        let json: Value = response.json().await.map_err(|e| e.to_string())?;
-        
-        Ok(json["choices"][0]["message"]["content"].as_str().unwrap_or("").to_string())
-    }
-
-    async fn call_anthropic(&self, service: &AIService, prompt: &str) -> Result<String, String> {
-        let payload = serde_json::json!({
-            "model": service.model,
-            "max_tokens": 1000,
-            "messages": [{"role": "user", "content": prompt}]
-        });
-
-        let response = self.client
-            .post(&format!("{}/messages", service.endpoint))
-            .header("x-api-key", service.api_key.as_ref().unwrap_or(&"".to_string()))
-            .header("anthropic-version", "2023-06-01")
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
-
-        let json: Value = response.json().await.map_err(|e| e.to_string())?;
-        
-        Ok(json["content"][0]["text"].as_str().unwrap_or("").to_string())
-    }
-} 
\ No newline at end of file
+        }
+
+        names
+            .into_iter()
+            .filter_map(|name| self.providers.get_provider(name))
+            .filter(|provider| !self.privacy_mode || provider.is_local())
+            .collect()
+    }
+
+    /// The highest-priority provider usable under the current privacy setting.
+    pub fn default_service(&self) -> Result<Arc<dyn LlmProvider>, ThetaError> {
+        self.ordered_services()
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                ThetaError::AiUnavailable(if self.privacy_mode {
+                    "No local AI service available in privacy mode".to_string()
+                } else {
+                    "No AI service available".to_string()
+                })
+            })
+    }
+
+    pub fn resolve(&self, service_name: Option<&str>) -> Result<Arc<dyn LlmProvider>, ThetaError> {
+        match service_name {
+            Some(name) => self.get_service(name),
+            None => self.default_service(),
+        }
+    }
+
+    /// `preferred` first if given, then every other usable provider.
+    fn candidates(&self, preferred: Option<&str>) -> Result<Vec<Arc<dyn LlmProvider>>, ThetaError> {
+        let first = self.resolve(preferred)?;
+        let mut candidates = vec![first.clone()];
+        candidates.extend(
+            self.ordered_services()
+                .into_iter()
+                .filter(|provider| provider.name() != first.name()),
+        );
+        Ok(candidates)
+    }
+
+    /// Sends `request` to `service_name` only, retrying transient failures,
+    /// for requests fitted to that provider such as its context window.
+    pub async fn complete(&self, service_name: &str, request: &CompletionRequest) -> Result<AIResponse, ThetaError> {
+        let provider = self.get_service(service_name)?;
+        let run_id = self.current_run();
+        let mut response = self.with_retry(provider.as_ref(), |provider| self.call_provider(provider, request, &run_id)).await?;
+        self.settle(provider.as_ref(), request.feature, &mut response);
+        Ok(response)
+    }
+
+    pub async fn call_service(&self, service_name: &str, prompt: &str) -> Result<String, ThetaError> {
+        let response = self.complete(service_name, &CompletionRequest::from_prompt(prompt)).await?;
+        Ok(response.content)
+    }
+
+    /// Sends `request` to `preferred` (or the default provider), retrying
+    /// transient failures and then failing over down the priority list. The
+    /// response's `provider` field names whichever provider answered.
+    pub async fn complete_with_failover(
+        &self,
+        preferred: Option<&str>,
+        request: &CompletionRequest,
+    ) -> Result<AIResponse, ThetaError> {
+        let mut errors: Vec<ProviderError> = Vec::new();
+        let run_id = self.current_run();
+
+        for (index, provider) in self.candidates(preferred)?.into_iter().enumerate() {
+            let request = Self::request_for(index, request);
+            match self.with_retry(provider.as_ref(), |provider| self.call_provider(provider, &request, &run_id)).await {
+                Ok(mut response) => {
+                    self.settle(provider.as_ref(), request.feature, &mut response);
+                    return Ok(response);
+                }
+                Err(e) => {
+                    log::warn!("AI provider failed, trying next: {}", e);
+                    errors.push(e);
+                }
+            }
+        }
+
+        Err(Self::failover_error(errors))
+    }
+
+    /// Like `complete_with_failover` for streams. Only starting the stream is
+    /// retried; once deltas have reached the caller a failure is final.
+    pub async fn stream_with_failover(
+        &self,
+        preferred: Option<&str>,
+        request: &CompletionRequest,
+    ) -> Result<CompletionStream, ThetaError> {
+        let mut errors: Vec<ProviderError> = Vec::new();
+        let run_id = self.current_run();
+
+        for (index, provider) in self.candidates(preferred)?.into_iter().enumerate() {
+            let request = Self::request_for(index, request);
+            match self.with_retry(provider.as_ref(), |provider| self.start_stream(provider, &request, &run_id)).await {
+                Ok((stream, started)) => {
+                    let services = self.clone();
+                    let feature = request.feature;
+                    let mut recording = self.recorder.clone().map(|recorder| StreamRecording {
+                        recorder,
+                        run_id: run_id.clone(),
+                        provider: provider.name().to_string(),
+                        request: request.clone(),
+                        started,
+                        finished: false,
+                    });
+                    return Ok(stream
+                        .map(move |chunk| match chunk {
+                            Ok(StreamChunk::Done(mut response)) => {
+                                if let Some(recording) = &mut recording {
+                                    recording.finish(&Ok(response.clone()));
+                                    response.run_id = Some(recording.run_id.clone());
+                                }
+                                services.settle(provider.as_ref(), feature, &mut response);
+                                Ok(StreamChunk::Done(response))
+                            }
+                            Err(e) => {
+                                if let Some(recording) = &mut recording {
+                                    recording.finish(&Err(e.clone()));
+                                }
+                                Err(e)
+                            }
+                            other => other,
+                        })
+                        .boxed());
+                }
+                Err(e) => {
+                    log::warn!("AI provider failed, trying next: {}", e);
+                    errors.push(e);
+                }
+            }
+        }
+
+        Err(Self::failover_error(errors))
+    }
+
+    /// Sends one request to one provider and logs the call, whatever its outcome.
+    async fn call_provider(
+        &self,
+        provider: &dyn LlmProvider,
+        request: &CompletionRequest,
+        run_id: &str,
+    ) -> Result<AIResponse, ProviderError> {
+        let started = Instant::now();
+        let mut result = provider.complete(request).await;
+
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&RecordedCall::new(run_id, provider.name(), request, &result, started.elapsed())).await;
+            if let Ok(response) = &mut result {
+                response.run_id = Some(run_id.to_string());
+            }
+        }
+        result
+    }
+
+    /// Starts a stream from one provider, logging the call if it fails to
+    /// start. Streams that start are logged by `StreamRecording` once they end.
+    async fn start_stream(
+        &self,
+        provider: &dyn LlmProvider,
+        request: &CompletionRequest,
+        run_id: &str,
+    ) -> Result<(CompletionStream, Instant), ProviderError> {
+        let started = Instant::now();
+        match provider.stream(request).await {
+            Ok(stream) => Ok((stream, started)),
+            Err(e) => {
+                if let Some(recorder) = &self.recorder {
+                    recorder.record(&RecordedCall::new(run_id, provider.name(), request, &Err(e.clone()), started.elapsed())).await;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// The run a new request is logged under.
+    fn current_run(&self) -> String {
+        self.run_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string())
+    }
+
+    /// Prices a finished response and records it in the usage ledger.
+    fn settle(&self, provider: &dyn LlmProvider, feature: AIFeature, response: &mut AIResponse) {
+        let usage = TokenUsage {
+            input_tokens: response.input_tokens,
+            output_tokens: response.output_tokens,
+        };
+        response.cost_usd = pricing::request_cost(&response.model, provider.is_local(), usage);
+
+        if let Some(ledger) = &self.ledger {
+            ledger.record(feature, response);
+        }
+    }
+
+    fn failover_error(errors: Vec<ProviderError>) -> ThetaError {
+        if errors.len() == 1 {
+            return errors.into_iter().next().map(ThetaError::from).unwrap_or_else(|| ThetaError::ai("No AI service answered"));
+        }
+
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        ThetaError::Ai {
+            message: format!("All AI services failed: {}", messages.join("; ")),
+            retryable: errors.iter().any(|e| e.is_retryable()),
+        }
+    }
+
+    /// A model name only means something to the provider it was chosen for,
+    /// so fallback providers use their own default.
+    fn request_for(index: usize, request: &CompletionRequest) -> CompletionRequest {
+        let mut request = request.clone();
+        if index > 0 {
+            request.model = None;
+        }
+        request
+    }
+
+    async fn with_retry<'a, T, F, Fut>(&self, provider: &'a dyn LlmProvider, mut call: F) -> Result<T, ProviderError>
+    where
+        F: FnMut(&'a dyn LlmProvider) -> Fut,
+        Fut: std::future::Future<Output = Result<T, ProviderError>>,
+    {
+        let mut retry = 0;
+        loop {
+            match call(provider).await {
+                Ok(value) => return Ok(value),
+                Err(e) if e.is_retryable() && retry < self.retry_policy.max_retries => {
+                    let delay = self.retry_policy.delay(retry, &e);
+                    // Waiting out a long rate limit is worse than asking another provider
+                    if delay > self.retry_policy.max_delay {
+                        return Err(e);
+                    }
+                    log::info!("Retrying {} in {:?}: {}", provider.name(), delay, e);
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Logs a streamed call once it ends: with its response, with the error that
+/// ended it, or as cancelled if the stream is dropped before either.
+struct StreamRecording {
+    recorder: RunRecorder,
+    run_id: String,
+    provider: String,
+    request: CompletionRequest,
+    started: Instant,
+    finished: bool,
+}
+
+impl StreamRecording {
+    fn finish(&mut self, result: &Result<AIResponse, ProviderError>) {
+        if std::mem::replace(&mut self.finished, true) {
+            return;
+        }
+        let call = RecordedCall::new(&self.run_id, &self.provider, &self.request, result, self.started.elapsed());
+        let recorder = self.recorder.clone();
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move { recorder.record(&call).await });
+        }
+    }
+}
+
+impl Drop for StreamRecording {
+    fn drop(&mut self) {
+        let cancelled = ProviderError::new(&self.provider, ProviderErrorKind::Cancelled, "Stream stopped before it finished");
+        self.finish(&Err(cancelled));
+    }
+}
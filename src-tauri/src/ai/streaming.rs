@@ -0,0 +1,235 @@
+use super::models::TokenUsage;
+use super::providers::{CompletionStream, ProviderError, ProviderErrorKind, StreamChunk};
+use crate::models::AIResponse;
+use futures::stream::{self, BoxStream, StreamExt};
+use std::time::Instant;
+
+/// A single server-sent event as delivered by the OpenAI and Anthropic
+/// streaming APIs. Newline-delimited JSON streams (Ollama) are carried as
+/// events with only `data` set.
+#[derive(Debug, Clone, Default)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// What a provider-specific parser extracted from one SSE event.
+#[derive(Debug, Default)]
+pub struct StreamUpdate {
+    pub delta: Option<String>,
+    /// Counts are cumulative, so later updates only ever raise them.
+    pub usage: Option<TokenUsage>,
+    pub done: bool,
+}
+
+struct EventState {
+    bytes: BoxStream<'static, reqwest::Result<Vec<u8>>>,
+    buffer: Vec<u8>,
+    finished: bool,
+}
+
+pub fn sse_events(response: reqwest::Response) -> BoxStream<'static, Result<SseEvent, String>> {
+    event_stream(response, take_event)
+}
+
+pub fn ndjson_events(response: reqwest::Response) -> BoxStream<'static, Result<SseEvent, String>> {
+    event_stream(response, take_line)
+}
+
+fn event_stream(
+    response: reqwest::Response,
+    take: fn(&mut Vec<u8>, bool) -> Option<SseEvent>,
+) -> BoxStream<'static, Result<SseEvent, String>> {
+    let state = EventState {
+        bytes: response.bytes_stream().map(|chunk| chunk.map(|bytes| bytes.to_vec())).boxed(),
+        buffer: Vec::new(),
+        finished: false,
+    };
+
+    stream::unfold(state, move |mut state| async move {
+        loop {
+            if let Some(event) = take(&mut state.buffer, state.finished) {
+                return Some((Ok(event), state));
+            }
+            if state.finished {
+                return None;
+            }
+
+            match state.bytes.next().await {
+                Some(Ok(chunk)) => {
+                    // Normalize CRLF so events are always separated by a blank "\n\n" line
+                    state.buffer.extend(chunk.iter().filter(|byte| **byte != b'\r'));
+                }
+                Some(Err(e)) => {
+                    state.finished = true;
+                    state.buffer.clear();
+                    return Some((Err(format!("Stream interrupted: {}", e)), state));
+                }
+                None => state.finished = true,
+            }
+        }
+    })
+    .boxed()
+}
+
+fn take_event(buffer: &mut Vec<u8>, flush: bool) -> Option<SseEvent> {
+    loop {
+        let block = match buffer.windows(2).position(|window| window == b"\n\n") {
+            Some(end) => buffer.drain(..end + 2).collect::<Vec<u8>>(),
+            None if flush && !buffer.is_empty() => std::mem::take(buffer),
+            None => return None,
+        };
+
+        if let Some(event) = parse_event(&String::from_utf8_lossy(&block)) {
+            return Some(event);
+        }
+    }
+}
+
+fn take_line(buffer: &mut Vec<u8>, flush: bool) -> Option<SseEvent> {
+    loop {
+        let line = match buffer.iter().position(|byte| *byte == b'\n') {
+            Some(end) => buffer.drain(..end + 1).collect::<Vec<u8>>(),
+            None if flush && !buffer.is_empty() => std::mem::take(buffer),
+            None => return None,
+        };
+
+        let data = String::from_utf8_lossy(&line).trim().to_string();
+        if !data.is_empty() {
+            return Some(SseEvent { event: None, data });
+        }
+    }
+}
+
+fn parse_event(block: &str) -> Option<SseEvent> {
+    let mut event = SseEvent::default();
+    let mut data_lines = Vec::new();
+
+    for line in block.lines() {
+        if line.is_empty() || line.starts_with(':') {
+            continue;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+
+        match field {
+            "event" => event.event = Some(value.to_string()),
+            "data" => data_lines.push(value),
+            _ => {}
+        }
+    }
+
+    if data_lines.is_empty() && event.event.is_none() {
+        return None;
+    }
+
+    event.data = data_lines.join("\n");
+    Some(event)
+}
+
+struct CompletionState<F> {
+    events: BoxStream<'static, Result<SseEvent, String>>,
+    parse: F,
+    content: String,
+    usage: TokenUsage,
+    model: String,
+    provider: String,
+    start_time: Instant,
+    done: bool,
+    finished: bool,
+}
+
+impl<F> CompletionState<F> {
+    /// An empty reply is reported as an error rather than a successful
+    /// response with no text.
+    fn finish(&mut self) -> Result<StreamChunk, ProviderError> {
+        self.finished = true;
+        if self.content.trim().is_empty() {
+            return Err(ProviderError::invalid_response(&self.provider, "Stream ended without any content"));
+        }
+
+        Ok(StreamChunk::Done(AIResponse {
+            content: std::mem::take(&mut self.content),
+            model: self.model.clone(),
+            provider: self.provider.clone(),
+            input_tokens: self.usage.input_tokens,
+            output_tokens: self.usage.output_tokens,
+            tokens_used: self.usage.total(),
+            cost_usd: None,
+            processing_time: self.start_time.elapsed().as_millis() as u64,
+            confidence: None,
+            tool_calls: Vec::new(),
+            run_id: None,
+            raw: None,
+        }))
+    }
+}
+
+/// Turns a provider's event stream into a `CompletionStream`, accumulating the
+/// deltas so the closing `Done` chunk carries the full response.
+pub fn completion_stream<F>(
+    events: BoxStream<'static, Result<SseEvent, String>>,
+    model: String,
+    provider: String,
+    start_time: Instant,
+    parse: F,
+) -> CompletionStream
+where
+    F: FnMut(&SseEvent) -> Result<StreamUpdate, String> + Send + 'static,
+{
+    let state = CompletionState {
+        events,
+        parse,
+        content: String::new(),
+        usage: TokenUsage::default(),
+        model,
+        provider,
+        start_time,
+        done: false,
+        finished: false,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if state.finished {
+                return None;
+            }
+            if state.done {
+                let done = state.finish();
+                return Some((done, state));
+            }
+
+            let update = match state.events.next().await {
+                Some(Ok(event)) => (state.parse)(&event)
+                    .map_err(|e| ProviderError::new(&state.provider, ProviderErrorKind::InvalidResponse, e)),
+                Some(Err(e)) => Err(ProviderError::new(&state.provider, ProviderErrorKind::Network, e)),
+                None => {
+                    let done = state.finish();
+                    return Some((done, state));
+                }
+            };
+
+            match update {
+                Ok(update) => {
+                    if let Some(usage) = update.usage {
+                        state.usage.input_tokens = state.usage.input_tokens.max(usage.input_tokens);
+                        state.usage.output_tokens = state.usage.output_tokens.max(usage.output_tokens);
+                    }
+                    state.done = update.done;
+                    if let Some(delta) = update.delta.filter(|delta| !delta.is_empty()) {
+                        state.content.push_str(&delta);
+                        return Some((Ok(StreamChunk::Delta(delta)), state));
+                    }
+                }
+                Err(e) => {
+                    state.finished = true;
+                    return Some((Err(e), state));
+                }
+            }
+        }
+    })
+    .boxed()
+}
@@ -0,0 +1,287 @@
+use super::prompts::{self, PromptRegistry, PromptTemplate};
+use super::providers::LlmProvider;
+use super::service::AIServiceManager;
+use crate::error::ThetaError;
+use crate::models::{AIFeature, SummaryStage};
+use futures::{stream, StreamExt, TryStreamExt};
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Upper bound on a chunk even for models with very large context windows;
+/// smaller chunks keep each section summary faithful.
+const MAX_CHUNK_TOKENS: u32 = 3000;
+
+/// Chunks summarized at once.
+const MAX_PARALLEL_CHUNKS: usize = 4;
+
+/// Longest a section summary gets, or the requested summary's length if longer.
+const SECTION_SUMMARY_WORDS: u32 = 150;
+
+/// Shortest a section summary gets however many sections there are.
+const MIN_SECTION_WORDS: u32 = 40;
+
+/// Called with the stage, the number of prompts finished and the total.
+pub type ProgressFn<'a> = &'a (dyn Fn(SummaryStage, usize, usize) + Send + Sync);
+
+/// A finished summary and the providers that answered the prompts it was
+/// built from, which differ from the default one after a failover.
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub text: String,
+    pub providers: BTreeSet<String>,
+}
+
+/// Summarizes `content` in at most `max_words` words. Content that fits one
+/// prompt is summarized directly; longer content is split into chunks that
+/// are summarized separately and then combined.
+pub async fn summarize(
+    services: &AIServiceManager,
+    prompts: &PromptRegistry,
+    content: &str,
+    max_words: u32,
+    progress: ProgressFn<'_>,
+) -> Result<Summary, ThetaError> {
+    let provider = services.default_service()?;
+    let providers = Mutex::new(BTreeSet::new());
+    let finish = |text: String| Summary { text, providers: std::mem::take(&mut *providers.lock().unwrap()) };
+    let chunk_tokens = chunk_budget(provider.as_ref());
+    let count = |text: &str| provider.count_tokens(text);
+
+    let chunks = split_into_chunks(content, chunk_tokens, &count);
+    if chunks.len() <= 1 {
+        progress(SummaryStage::Reduce, 0, 1);
+        let summary = complete(services, prompts.get(prompts::SUMMARY)?, content, max_words, &[], &providers).await?;
+        progress(SummaryStage::Reduce, 1, 1);
+        return Ok(finish(summary));
+    }
+
+    let words = section_words(chunk_tokens, chunks.len(), max_words);
+    let chunk_template = prompts.get(prompts::SUMMARY_CHUNK)?;
+    let mut summaries = summarize_chunks(services, chunk_template, chunks, words, SummaryStage::Map, progress, &providers).await?;
+
+    let reduce = prompts.get(prompts::SUMMARY_REDUCE)?;
+    loop {
+        let combined = summaries.join("\n\n");
+        let parts = split_into_chunks(&combined, chunk_tokens, &count);
+
+        if parts.len() <= 1 {
+            progress(SummaryStage::Reduce, 0, 1);
+            let summary = complete(services, reduce, &combined, max_words, &[], &providers).await?;
+            progress(SummaryStage::Reduce, 1, 1);
+            return Ok(finish(summary));
+        }
+        if parts.len() >= summaries.len() {
+            return Err(ThetaError::ai("Section summaries are too long to combine"));
+        }
+
+        // Still too long for one prompt: combine neighbouring summaries first
+        let words = section_words(chunk_tokens, parts.len(), max_words);
+        summaries = summarize_chunks(services, reduce, parts, words, SummaryStage::Reduce, progress, &providers).await?;
+    }
+}
+
+/// Tokens per chunk for the default model, leaving half the context window
+/// for the prompt framing and the reply.
+fn chunk_budget(provider: &dyn LlmProvider) -> u32 {
+    let context_window = provider.model_info(provider.default_model()).max_tokens;
+    (context_window / 2).clamp(256, MAX_CHUNK_TOKENS)
+}
+
+/// Words per section summary, so that `sections` of them fit in one chunk
+/// together and can be combined in a single prompt.
+fn section_words(chunk_tokens: u32, sections: usize, max_words: u32) -> u32 {
+    // English runs at about four tokens to three words
+    let fitting = (chunk_tokens as usize * 3 / 4 / sections.max(1)) as u32;
+    fitting.clamp(MIN_SECTION_WORDS, max_words.max(SECTION_SUMMARY_WORDS))
+}
+
+async fn summarize_chunks(
+    services: &AIServiceManager,
+    template: &PromptTemplate,
+    chunks: Vec<String>,
+    max_words: u32,
+    stage: SummaryStage,
+    progress: ProgressFn<'_>,
+    providers: &Mutex<BTreeSet<String>>,
+) -> Result<Vec<String>, ThetaError> {
+    let total = chunks.len();
+    let finished = AtomicUsize::new(0);
+    progress(stage, 0, total);
+
+    let mut summaries: Vec<(usize, String)> = stream::iter(chunks.into_iter().enumerate())
+        .map(|(index, chunk)| {
+            let finished = &finished;
+            async move {
+                let position = (index + 1).to_string();
+                let count = total.to_string();
+                let summary = complete(
+                    services,
+                    template,
+                    &chunk,
+                    max_words,
+                    &[("index", &position), ("count", &count)],
+                    providers,
+                )
+                .await?;
+                progress(stage, finished.fetch_add(1, Ordering::SeqCst) + 1, total);
+                Ok::<_, ThetaError>((index, summary))
+            }
+        })
+        .buffer_unordered(MAX_PARALLEL_CHUNKS)
+        .try_collect()
+        .await?;
+
+    summaries.sort_by_key(|(index, _)| *index);
+    Ok(summaries.into_iter().map(|(_, summary)| summary).collect())
+}
+
+async fn complete(
+    services: &AIServiceManager,
+    template: &PromptTemplate,
+    content: &str,
+    max_words: u32,
+    extra: &[(&str, &str)],
+    providers: &Mutex<BTreeSet<String>>,
+) -> Result<String, ThetaError> {
+    let max_words_value = max_words.to_string();
+    let mut variables = vec![("content", content), ("max_words", max_words_value.as_str())];
+    variables.extend_from_slice(extra);
+
+    let mut request = template.request(&variables, AIFeature::Summary)?;
+    request.max_tokens = request.max_tokens.or(Some(max_words.saturating_mul(2)));
+
+    let response = services.complete_with_failover(None, &request).await?;
+    providers.lock().unwrap().insert(response.provider);
+    Ok(response.content)
+}
+
+/// Splits text into chunks of at most `max_tokens`, breaking between
+/// paragraphs where possible, then between sentences, and only splitting
+/// inside a sentence when it is longer than a chunk on its own.
+pub fn split_into_chunks(text: &str, max_tokens: u32, count_tokens: &dyn Fn(&str) -> u32) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_tokens = 0;
+
+    for paragraph in text.split("\n\n").map(str::trim).filter(|paragraph| !paragraph.is_empty()) {
+        let paragraph_tokens = count_tokens(paragraph);
+        let pieces = if paragraph_tokens <= max_tokens {
+            vec![(paragraph.to_string(), paragraph_tokens)]
+        } else {
+            split_paragraph(paragraph, max_tokens, count_tokens)
+        };
+
+        for (index, (piece, tokens)) in pieces.into_iter().enumerate() {
+            let separator = if index == 0 { "\n\n" } else { " " };
+            if !current.is_empty() && current_tokens + tokens > max_tokens {
+                chunks.push(std::mem::take(&mut current));
+                current_tokens = 0;
+            }
+            if !current.is_empty() {
+                current.push_str(separator);
+            }
+            current.push_str(&piece);
+            current_tokens += tokens;
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// A paragraph too long for one chunk, as sentences, with any sentence that
+/// is itself too long cut between words.
+fn split_paragraph(paragraph: &str, max_tokens: u32, count_tokens: &dyn Fn(&str) -> u32) -> Vec<(String, u32)> {
+    let mut pieces = Vec::new();
+
+    for sentence in sentences(paragraph) {
+        let tokens = count_tokens(sentence);
+        if tokens <= max_tokens {
+            pieces.push((sentence.to_string(), tokens));
+            continue;
+        }
+
+        let mut piece = String::new();
+        let mut piece_tokens = 0;
+        for word in sentence.split_whitespace() {
+            let word_tokens = count_tokens(word).max(1);
+            if !piece.is_empty() && piece_tokens + word_tokens > max_tokens {
+                pieces.push((std::mem::take(&mut piece), piece_tokens));
+                piece_tokens = 0;
+            }
+            if !piece.is_empty() {
+                piece.push(' ');
+            }
+            piece.push_str(word);
+            piece_tokens += word_tokens;
+        }
+        if !piece.is_empty() {
+            pieces.push((piece, piece_tokens));
+        }
+    }
+
+    pieces
+}
+
+/// Splits after `.`, `!` or `?` when followed by whitespace.
+fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        if matches!(c, '.' | '!' | '?') && chars.peek().is_some_and(|(_, next)| next.is_whitespace()) {
+            let end = index + c.len_utf8();
+            sentences.push(text[start..end].trim());
+            start = end;
+        }
+    }
+    sentences.push(text[start..].trim());
+
+    sentences.retain(|sentence| !sentence.is_empty());
+    sentences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> u32 {
+        text.split_whitespace().count() as u32
+    }
+
+    #[test]
+    fn short_text_is_one_chunk() {
+        assert_eq!(split_into_chunks("One. Two.\n\nThree.", 10, &words), vec!["One. Two.\n\nThree."]);
+        assert!(split_into_chunks("  \n\n ", 10, &words).is_empty());
+    }
+
+    #[test]
+    fn prefers_paragraph_then_sentence_boundaries() {
+        let text = "a b c. d e f.\n\ng h i. j k l. m n o.";
+        let chunks = split_into_chunks(text, 6, &words);
+
+        assert_eq!(chunks, vec!["a b c. d e f.", "g h i. j k l.", "m n o."]);
+    }
+
+    #[test]
+    fn section_summaries_fit_one_chunk_together() {
+        assert_eq!(section_words(3000, 4, 150), 150);
+        // A long requested summary does not make the sections too long to combine
+        assert_eq!(section_words(3000, 4, 2000), 562);
+        assert!(section_words(3000, 12, 2000) * 12 <= 3000 * 3 / 4);
+        assert_eq!(section_words(3000, 500, 150), MIN_SECTION_WORDS);
+    }
+
+    #[test]
+    fn cuts_long_sentences_between_words() {
+        let text = "one two three four five six seven";
+        let chunks = split_into_chunks(text, 3, &words);
+
+        assert_eq!(chunks, vec!["one two three", "four five six", "seven"]);
+        assert!(chunks.iter().all(|chunk| words(chunk) <= 3));
+    }
+}
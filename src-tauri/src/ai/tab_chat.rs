@@ -0,0 +1,349 @@
+use super::models::CompletionRequest;
+use super::prompts::{self, PromptRegistry};
+use super::providers::LlmProvider;
+use super::service::AIServiceManager;
+use super::summarize::split_into_chunks;
+use crate::error::ThetaError;
+use crate::models::{AIFeature, AIResponse, TabCitation};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use uuid::Uuid;
+
+/// Size of a citable passage.
+const PASSAGE_TOKENS: u32 = 120;
+
+/// Page text sent with a question, at most; smaller for small context windows.
+const MAX_PAGE_TOKENS: u32 = 6000;
+
+/// Length of the running summary kept in `ai_context`.
+const CONTEXT_WORDS: u32 = 150;
+
+const REPLY_TOKENS: u32 = 1000;
+
+/// Words of a passage used to link back to it with a text fragment.
+const FRAGMENT_WORDS: usize = 8;
+
+static CITATION: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[(\d+)\]").unwrap());
+
+/// The text of one tab.
+#[derive(Debug, Clone)]
+pub struct SourceDocument {
+    pub tab_id: Uuid,
+    pub url: String,
+    pub title: String,
+    pub content: String,
+}
+
+/// A numbered slice of a document the model can cite as `[n]`. Numbers run
+/// on across documents so each one is unique within a question.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Passage {
+    pub number: usize,
+    /// Index of the document it came from.
+    pub source: usize,
+    pub text: String,
+}
+
+/// Answers a question about one tab from the page's own text. Returns the
+/// response and the passages it cites.
+pub async fn answer_tab(
+    services: &AIServiceManager,
+    prompts: &PromptRegistry,
+    provider: Option<&str>,
+    document: &SourceDocument,
+    ai_context: Option<&str>,
+    question: &str,
+) -> Result<(AIResponse, Vec<TabCitation>), ThetaError> {
+    let service = services.resolve(provider)?;
+    let count = |text: &str| service.count_tokens(text);
+
+    let documents = std::slice::from_ref(document);
+    let passages = split_passages(documents, &count);
+    let selected = select_passages(&passages, question, page_budget(service.as_ref()), &count);
+    let numbered = selected
+        .iter()
+        .map(|passage| format!("[{}] {}", passage.number, passage.text))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let template = prompts.get(prompts::TAB_CHAT)?;
+    let request = template.request(
+        &[
+            ("title", &document.title),
+            ("url", &document.url),
+            ("passages", &numbered),
+            ("conversation", ai_context.unwrap_or("(none yet)")),
+            ("question", question),
+        ],
+        AIFeature::TabChat,
+    )?;
+
+    complete(services, provider, request, &selected, documents).await
+}
+
+/// Answers a question from the text of several tabs, e.g. to compare
+/// documentation pages. Citations say which tab each passage came from.
+pub async fn answer_workspace(
+    services: &AIServiceManager,
+    prompts: &PromptRegistry,
+    provider: Option<&str>,
+    workspace: &str,
+    documents: &[SourceDocument],
+    ai_context: Option<&str>,
+    question: &str,
+) -> Result<(AIResponse, Vec<TabCitation>), ThetaError> {
+    let service = services.resolve(provider)?;
+    let count = |text: &str| service.count_tokens(text);
+
+    let passages = split_passages(documents, &count);
+    let selected = select_passages(&passages, question, page_budget(service.as_ref()), &count);
+    let numbered = selected
+        .iter()
+        .map(|passage| {
+            let document = &documents[passage.source];
+            format!("[{}] ({} - {}) {}", passage.number, document.title, document.url, passage.text)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let template = prompts.get(prompts::WORKSPACE_CHAT)?;
+    let request = template.request(
+        &[
+            ("workspace", workspace),
+            ("passages", &numbered),
+            ("conversation", ai_context.unwrap_or("(none yet)")),
+            ("question", question),
+        ],
+        AIFeature::WorkspaceChat,
+    )?;
+
+    complete(services, provider, request, &selected, documents).await
+}
+
+async fn complete(
+    services: &AIServiceManager,
+    provider: Option<&str>,
+    mut request: CompletionRequest,
+    selected: &[&Passage],
+    documents: &[SourceDocument],
+) -> Result<(AIResponse, Vec<TabCitation>), ThetaError> {
+    request.max_tokens = request.max_tokens.or(Some(REPLY_TOKENS));
+
+    let response = services.complete_with_failover(provider, &request).await?;
+    let citations = cite(&response.content, selected, documents);
+    Ok((response, citations))
+}
+
+/// Folds the latest exchange into the running summary of a conversation
+/// about one or more tabs.
+pub async fn update_context(
+    services: &AIServiceManager,
+    prompts: &PromptRegistry,
+    provider: &str,
+    previous: Option<&str>,
+    question: &str,
+    answer: &str,
+) -> Result<String, ThetaError> {
+    let max_words = CONTEXT_WORDS.to_string();
+    let template = prompts.get(prompts::TAB_CONTEXT)?;
+    let mut request = template.request(
+        &[
+            ("summary", previous.unwrap_or("(none yet)")),
+            ("question", question),
+            ("answer", answer),
+            ("max_words", &max_words),
+        ],
+        AIFeature::ConversationSummary,
+    )?;
+    request.max_tokens = request.max_tokens.or(Some(CONTEXT_WORDS * 2));
+
+    Ok(services.complete_with_failover(Some(provider), &request).await?.content.trim().to_string())
+}
+
+fn page_budget(provider: &dyn LlmProvider) -> u32 {
+    let context_window = provider.model_info(provider.default_model()).max_tokens;
+    (context_window / 2).saturating_sub(REPLY_TOKENS).clamp(PASSAGE_TOKENS, MAX_PAGE_TOKENS)
+}
+
+pub fn split_passages(documents: &[SourceDocument], count_tokens: &dyn Fn(&str) -> u32) -> Vec<Passage> {
+    documents
+        .iter()
+        .enumerate()
+        .flat_map(|(source, document)| {
+            split_into_chunks(&document.content, PASSAGE_TOKENS, count_tokens)
+                .into_iter()
+                .map(move |text| (source, text))
+        })
+        .enumerate()
+        .map(|(index, (source, text))| Passage { number: index + 1, source, text })
+        .collect()
+}
+
+/// Every passage when they fit in `budget` tokens. Otherwise the passages
+/// sharing the most words with the question, starting with the best one
+/// from each document so no relevant tab is crowded out, kept in order.
+pub fn select_passages<'a>(
+    passages: &'a [Passage],
+    question: &str,
+    budget: u32,
+    count_tokens: &dyn Fn(&str) -> u32,
+) -> Vec<&'a Passage> {
+    let tokens: Vec<u32> = passages.iter().map(|passage| count_tokens(&passage.text)).collect();
+    if tokens.iter().sum::<u32>() <= budget {
+        return passages.iter().collect();
+    }
+
+    let terms: Vec<String> = question
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| term.chars().count() > 2)
+        .map(str::to_lowercase)
+        .collect();
+
+    let mut ranked: Vec<(usize, usize)> = passages
+        .iter()
+        .enumerate()
+        .map(|(index, passage)| {
+            let text = passage.text.to_lowercase();
+            (index, terms.iter().filter(|term| text.contains(term.as_str())).count())
+        })
+        .collect();
+    // Stable sort, so equally relevant passages keep their order
+    ranked.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    // Unrelated passages only fill the budget when nothing matched at all
+    if ranked.first().is_some_and(|&(_, score)| score > 0) {
+        ranked.retain(|&(_, score)| score > 0);
+    }
+
+    let mut best_per_source: Vec<usize> = Vec::new();
+    for &(index, _) in &ranked {
+        if !best_per_source.iter().any(|&best| passages[best].source == passages[index].source) {
+            best_per_source.push(index);
+        }
+    }
+
+    let mut used = 0;
+    let mut chosen: Vec<usize> = Vec::new();
+    for index in best_per_source.into_iter().chain(ranked.into_iter().map(|(index, _)| index)) {
+        if !chosen.contains(&index) && used + tokens[index] <= budget {
+            used += tokens[index];
+            chosen.push(index);
+        }
+    }
+    chosen.sort_unstable();
+    chosen.into_iter().map(|index| &passages[index]).collect()
+}
+
+/// Citations for every `[n]` in the answer that names a passage that was
+/// sent, in order of first mention.
+pub fn cite(answer: &str, passages: &[&Passage], documents: &[SourceDocument]) -> Vec<TabCitation> {
+    let mut citations: Vec<TabCitation> = Vec::new();
+
+    for captures in CITATION.captures_iter(answer) {
+        let Ok(number) = captures[1].parse::<usize>() else { continue };
+        if citations.iter().any(|citation| citation.passage == number) {
+            continue;
+        }
+        if let Some(passage) = passages.iter().find(|passage| passage.number == number) {
+            let document = &documents[passage.source];
+            citations.push(TabCitation {
+                passage: number,
+                tab_id: document.tab_id,
+                title: document.title.clone(),
+                text: passage.text.clone(),
+                url: fragment_link(&document.url, &passage.text),
+            });
+        }
+    }
+
+    citations
+}
+
+/// Links to the passage with a text fragment, which browsers scroll to and highlight.
+fn fragment_link(url: &str, text: &str) -> String {
+    let start: Vec<&str> = text.split_whitespace().take(FRAGMENT_WORDS).collect();
+    let base = url.split('#').next().unwrap_or(url);
+    format!("{}#:~:text={}", base, urlencoding::encode(&start.join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> u32 {
+        text.split_whitespace().count() as u32
+    }
+
+    fn passage(number: usize, source: usize, text: &str) -> Passage {
+        Passage { number, source, text: text.to_string() }
+    }
+
+    fn document(url: &str, content: &str) -> SourceDocument {
+        SourceDocument {
+            tab_id: Uuid::new_v4(),
+            url: url.to_string(),
+            title: url.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn numbers_passages_across_documents() {
+        let documents = vec![document("a", "One. Two."), document("b", "Three.")];
+        let passages = split_passages(&documents, &words);
+        let numbered: Vec<(usize, usize)> = passages.iter().map(|passage| (passage.number, passage.source)).collect();
+
+        assert_eq!(numbered, vec![(1, 0), (2, 1)]);
+    }
+
+    #[test]
+    fn keeps_everything_when_it_fits() {
+        let passages = vec![passage(1, 0, "rust is fast"), passage(2, 0, "go is simple")];
+
+        assert_eq!(select_passages(&passages, "anything", 6, &words).len(), 2);
+    }
+
+    #[test]
+    fn picks_passages_matching_the_question_in_page_order() {
+        let passages = vec![
+            passage(1, 0, "the borrow checker tracks lifetimes"),
+            passage(2, 0, "unrelated cooking recipe text"),
+            passage(3, 0, "lifetimes and the borrow checker again"),
+        ];
+
+        let selected = select_passages(&passages, "How does the borrow checker work?", 14, &words);
+        let numbers: Vec<usize> = selected.iter().map(|passage| passage.number).collect();
+
+        assert_eq!(numbers, vec![1, 3]);
+    }
+
+    #[test]
+    fn every_relevant_document_gets_a_passage() {
+        let passages = vec![
+            passage(1, 0, "tokio spawn runs a task on the runtime"),
+            passage(2, 0, "tokio spawn task Send"),
+            passage(3, 1, "async-std spawn runs a task"),
+        ];
+
+        let selected = select_passages(&passages, "how does tokio spawn a task", 14, &words);
+        let numbers: Vec<usize> = selected.iter().map(|passage| passage.number).collect();
+
+        assert_eq!(numbers, vec![1, 3]);
+    }
+
+    #[test]
+    fn cites_only_passages_that_were_sent() {
+        let documents = vec![document("https://example.com/rust#intro", "")];
+        let first = passage(1, 0, "Rust was first released in 2015.");
+        let third = passage(3, 0, "It has no garbage collector.");
+        let sent = vec![&first, &third];
+
+        let citations = cite("Released in 2015 [1], no GC [3][1], see also [2].", &sent, &documents);
+
+        assert_eq!(citations.iter().map(|citation| citation.passage).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(citations[0].tab_id, documents[0].tab_id);
+        assert_eq!(
+            citations[0].url,
+            "https://example.com/rust#:~:text=Rust%20was%20first%20released%20in%202015."
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use super::models::{AIMessage, CompletionRequest, ToolDefinition};
+use super::service::AIServiceManager;
+use crate::error::ThetaError;
+use crate::models::{AgentRun, ToolCall, ToolCallStatus, ToolStep};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Rounds of tool calls a run may make before the model must answer.
+pub const DEFAULT_MAX_STEPS: usize = 8;
+
+/// Told to the model on the last round, when it may no longer call tools.
+pub(crate) const STEP_LIMIT_NOTE: &str = "You have reached the limit of tool calls for this request. Do not call any more tools; answer with what you have.";
+
+/// Called with each tool call as it finishes.
+pub type StepFn<'a> = &'a (dyn Fn(&ToolStep) + Send + Sync);
+
+/// Something the model can do in the browser.
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn definition(&self) -> ToolDefinition;
+
+    /// Whether a call changes something outside the conversation, such as
+    /// saving a bookmark or triggering a workflow. Such calls only run once
+    /// the user confirms them.
+    fn has_side_effects(&self) -> bool;
+
+    /// Runs the tool; the result is sent back to the model as JSON.
+    async fn call(&self, arguments: Value) -> Result<Value, ThetaError>;
+}
+
+/// Asks the user whether a tool with side effects may run.
+#[async_trait]
+pub trait ToolConfirmation: Send + Sync {
+    async fn confirm(&self, call: &ToolCall, tool: &ToolDefinition) -> bool;
+}
+
+/// The tools offered to the model for a run.
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a tool, replacing any registered under the same name.
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        let name = tool.definition().name;
+        self.tools.retain(|known| known.definition().name != name);
+        self.tools.push(tool);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.iter().find(|tool| tool.definition().name == name).cloned()
+    }
+
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools.iter().map(|tool| tool.definition()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+}
+
+/// Deserializes a tool's arguments, reporting what the model got wrong.
+pub fn parse_arguments<T: DeserializeOwned>(arguments: Value) -> Result<T, ThetaError> {
+    serde_json::from_value(arguments).map_err(|e| ThetaError::InvalidInput(format!("Invalid tool arguments: {}", e)))
+}
+
+/// Completes `request`, running the tools the model asks for and sending
+/// their results back until it answers. After `max_steps` rounds of tool
+/// calls the model is told to stop; if it still asks for tools the run ends
+/// with whatever text it gave. Failed, unknown and declined calls are
+/// reported to the model as errors rather than ending the run.
+pub async fn run_with_tools(
+    services: &AIServiceManager,
+    provider: Option<&str>,
+    mut request: CompletionRequest,
+    tools: &ToolRegistry,
+    confirmation: &dyn ToolConfirmation,
+    max_steps: usize,
+    on_step: StepFn<'_>,
+) -> Result<AgentRun, ThetaError> {
+    request.tools = tools.definitions();
+    let mut steps = Vec::new();
+
+    for round in 0..=max_steps {
+        if round == max_steps {
+            request.messages.push(AIMessage::new("user".to_string(), STEP_LIMIT_NOTE.to_string()));
+        }
+
+        let response = services.complete_with_failover(provider, &request).await?;
+        if response.tool_calls.is_empty() || round == max_steps {
+            return Ok(AgentRun {
+                answer: response.content,
+                hit_step_limit: !response.tool_calls.is_empty(),
+                steps,
+                provider: response.provider,
+                model: response.model,
+                run_id: response.run_id,
+            });
+        }
+
+        request.messages.push(AIMessage::tool_request(response.content, response.tool_calls.clone()));
+        for call in response.tool_calls {
+            let step = run_tool(tools, confirmation, call).await;
+            on_step(&step);
+            request.messages.push(AIMessage::tool_result(step.call.id.clone(), step.output.to_string()));
+            steps.push(step);
+        }
+    }
+
+    unreachable!("the last round always returns")
+}
+
+async fn run_tool(tools: &ToolRegistry, confirmation: &dyn ToolConfirmation, call: ToolCall) -> ToolStep {
+    let failed = |call: ToolCall, status: ToolCallStatus, error: String| ToolStep {
+        call,
+        status,
+        output: serde_json::json!({ "error": error }),
+    };
+
+    let Some(tool) = tools.get(&call.name) else {
+        let error = format!("There is no tool named '{}'", call.name);
+        return failed(call, ToolCallStatus::Failed, error);
+    };
+    if tool.has_side_effects() && !confirmation.confirm(&call, &tool.definition()).await {
+        return failed(call, ToolCallStatus::Declined, "The user declined this action".to_string());
+    }
+
+    match tool.call(call.arguments.clone()).await {
+        Ok(output) => ToolStep { call, status: ToolCallStatus::Completed, output },
+        Err(e) => failed(call, ToolCallStatus::Failed, e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::models::AIProvider;
+    use crate::ai::providers::{LlmProvider, ProviderError};
+    use crate::models::AIResponse;
+    use serde::Deserialize;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Replies with the queued responses in order and keeps the requests.
+    struct ScriptedProvider {
+        config: AIProvider,
+        replies: Mutex<Vec<(String, Vec<ToolCall>)>>,
+        requests: Mutex<Vec<CompletionRequest>>,
+    }
+
+    #[async_trait]
+    impl LlmProvider for ScriptedProvider {
+        fn config(&self) -> &AIProvider {
+            &self.config
+        }
+
+        async fn complete(&self, request: &CompletionRequest) -> Result<AIResponse, ProviderError> {
+            self.requests.lock().unwrap().push(request.clone());
+            let (content, tool_calls) = self.replies.lock().unwrap().remove(0);
+            Ok(AIResponse {
+                content,
+                model: "scripted".to_string(),
+                provider: self.name().to_string(),
+                input_tokens: 0,
+                output_tokens: 0,
+                tokens_used: 0,
+                cost_usd: None,
+                processing_time: 0,
+                confidence: None,
+                tool_calls,
+                run_id: None,
+                raw: None,
+            })
+        }
+
+        async fn list_models(&self) -> Result<Vec<String>, ProviderError> {
+            Ok(Vec::new())
+        }
+    }
+
+    struct OpenTab;
+
+    #[derive(Deserialize)]
+    struct OpenTabArguments {
+        url: String,
+    }
+
+    #[async_trait]
+    impl Tool for OpenTab {
+        fn definition(&self) -> ToolDefinition {
+            ToolDefinition {
+                name: "open_tab".to_string(),
+                description: "Opens a tab".to_string(),
+                parameters: json!({ "type": "object", "properties": { "url": { "type": "string" } }, "required": ["url"] }),
+            }
+        }
+
+        fn has_side_effects(&self) -> bool {
+            false
+        }
+
+        async fn call(&self, arguments: Value) -> Result<Value, ThetaError> {
+            let arguments: OpenTabArguments = parse_arguments(arguments)?;
+            Ok(json!({ "opened": arguments.url }))
+        }
+    }
+
+    struct SaveBookmark;
+
+    #[async_trait]
+    impl Tool for SaveBookmark {
+        fn definition(&self) -> ToolDefinition {
+            ToolDefinition {
+                name: "bookmark_page".to_string(),
+                description: "Saves a bookmark".to_string(),
+                parameters: json!({ "type": "object" }),
+            }
+        }
+
+        fn has_side_effects(&self) -> bool {
+            true
+        }
+
+        async fn call(&self, _arguments: Value) -> Result<Value, ThetaError> {
+            Ok(json!({ "saved": true }))
+        }
+    }
+
+    struct Answer(bool);
+
+    #[async_trait]
+    impl ToolConfirmation for Answer {
+        async fn confirm(&self, _call: &ToolCall, _tool: &ToolDefinition) -> bool {
+            self.0
+        }
+    }
+
+    fn call(id: &str, name: &str, arguments: Value) -> ToolCall {
+        ToolCall { id: id.to_string(), name: name.to_string(), arguments }
+    }
+
+    fn setup(replies: Vec<(&str, Vec<ToolCall>)>) -> (AIServiceManager, Arc<ScriptedProvider>, ToolRegistry) {
+        let provider = Arc::new(ScriptedProvider {
+            config: AIProvider::new("scripted".to_string(), "http://localhost:1".to_string(), None),
+            replies: Mutex::new(replies.into_iter().map(|(content, calls)| (content.to_string(), calls)).collect()),
+            requests: Mutex::new(Vec::new()),
+        });
+        let mut services = AIServiceManager::new();
+        services.add_service(provider.clone());
+
+        let mut tools = ToolRegistry::new();
+        tools.register(Arc::new(OpenTab));
+        tools.register(Arc::new(SaveBookmark));
+        (services, provider, tools)
+    }
+
+    #[tokio::test]
+    async fn runs_tools_and_sends_results_back() {
+        let (services, provider, tools) = setup(vec![
+            ("", vec![call("1", "open_tab", json!({ "url": "https://example.com" }))]),
+            ("Opened it.", Vec::new()),
+        ]);
+        let seen = Mutex::new(0);
+
+        let run = run_with_tools(
+            &services,
+            None,
+            CompletionRequest::from_prompt("Open example.com"),
+            &tools,
+            &Answer(true),
+            DEFAULT_MAX_STEPS,
+            &|_| *seen.lock().unwrap() += 1,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(run.answer, "Opened it.");
+        assert!(!run.hit_step_limit);
+        assert_eq!(run.steps.len(), 1);
+        assert_eq!(run.steps[0].status, ToolCallStatus::Completed);
+        assert_eq!(*seen.lock().unwrap(), 1);
+
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests[0].tools.len(), 2);
+        let result = requests[1].messages.last().unwrap();
+        assert_eq!(result.tool_call_id.as_deref(), Some("1"));
+        assert_eq!(serde_json::from_str::<Value>(&result.content).unwrap(), json!({ "opened": "https://example.com" }));
+    }
+
+    #[tokio::test]
+    async fn declined_and_invalid_calls_are_reported_to_the_model() {
+        let (services, provider, tools) = setup(vec![
+            ("", vec![
+                call("1", "bookmark_page", json!({})),
+                call("2", "open_tab", json!({ "link": "https://example.com" })),
+                call("3", "delete_history", json!({})),
+            ]),
+            ("I could not do that.", Vec::new()),
+        ]);
+
+        let run = run_with_tools(
+            &services,
+            None,
+            CompletionRequest::from_prompt("Bookmark this"),
+            &tools,
+            &Answer(false),
+            DEFAULT_MAX_STEPS,
+            &|_| {},
+        )
+        .await
+        .unwrap();
+
+        let statuses: Vec<ToolCallStatus> = run.steps.iter().map(|step| step.status).collect();
+        assert_eq!(statuses, vec![ToolCallStatus::Declined, ToolCallStatus::Failed, ToolCallStatus::Failed]);
+        assert!(run.steps[1].output["error"].as_str().unwrap().contains("url"));
+
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests[1].messages.iter().filter(|message| message.tool_call_id.is_some()).count(), 3);
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_step_limit() {
+        let open = || vec![call("1", "open_tab", json!({ "url": "https://example.com" }))];
+        let (services, provider, tools) = setup(vec![("", open()), ("", open()), ("Still going", open())]);
+
+        let run = run_with_tools(
+            &services,
+            None,
+            CompletionRequest::from_prompt("Open it forever"),
+            &tools,
+            &Answer(true),
+            2,
+            &|_| {},
+        )
+        .await
+        .unwrap();
+
+        assert!(run.hit_step_limit);
+        assert_eq!(run.answer, "Still going");
+        assert_eq!(run.steps.len(), 2);
+        assert_eq!(provider.requests.lock().unwrap()[2].messages.last().unwrap().content, STEP_LIMIT_NOTE);
+    }
+}
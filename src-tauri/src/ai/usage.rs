@@ -0,0 +1,90 @@
+use crate::models::{AIFeature, AIResponse, AIUsageSummary};
+use sqlx::PgPool;
+
+/// Appends one row per completed request to `ai_usage`.
+#[derive(Clone)]
+pub struct UsageLedger {
+    pool: PgPool,
+}
+
+impl UsageLedger {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Records in the background; a failed write is logged rather than
+    /// failing the request that was already answered.
+    pub fn record(&self, feature: AIFeature, response: &AIResponse) {
+        let pool = self.pool.clone();
+        let provider = response.provider.clone();
+        let model = response.model.clone();
+        let (input_tokens, output_tokens, cost_usd) = (response.input_tokens, response.output_tokens, response.cost_usd);
+
+        tokio::spawn(async move {
+            let result = sqlx::query(
+                "INSERT INTO ai_usage (provider, model, feature, input_tokens, output_tokens, cost_usd)
+                 VALUES ($1, $2, $3, $4, $5, $6)",
+            )
+            .bind(&provider)
+            .bind(&model)
+            .bind(feature.as_str())
+            .bind(input_tokens as i32)
+            .bind(output_tokens as i32)
+            .bind(cost_usd)
+            .execute(&pool)
+            .await;
+
+            if let Err(e) = result {
+                log::warn!("Failed to record AI usage for {}: {}", provider, e);
+            }
+        });
+    }
+}
+
+type UsageRow = (chrono::NaiveDate, String, String, i64, i64, i64, i64, f64, i64);
+
+/// Usage per UTC day, provider and feature between `since` and `until`,
+/// newest day first.
+pub async fn usage_summary(
+    pool: &PgPool,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    until: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<Vec<AIUsageSummary>, sqlx::Error> {
+    let rows: Vec<UsageRow> = sqlx::query_as(
+        "SELECT (created_at AT TIME ZONE 'UTC')::date AS day,
+                provider,
+                feature,
+                COUNT(*),
+                COALESCE(SUM(input_tokens), 0)::BIGINT,
+                COALESCE(SUM(output_tokens), 0)::BIGINT,
+                COALESCE(SUM(input_tokens + output_tokens), 0)::BIGINT,
+                COALESCE(SUM(cost_usd), 0)::DOUBLE PRECISION,
+                COUNT(*) FILTER (WHERE cost_usd IS NULL)
+         FROM ai_usage
+         WHERE ($1::timestamptz IS NULL OR created_at >= $1)
+           AND ($2::timestamptz IS NULL OR created_at < $2)
+         GROUP BY day, provider, feature
+         ORDER BY day DESC, provider, feature",
+    )
+    .bind(since)
+    .bind(until)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(day, provider, feature, requests, input_tokens, output_tokens, total_tokens, cost_usd, unpriced_requests)| {
+            AIUsageSummary {
+                day,
+                provider,
+                feature: AIFeature::parse(&feature),
+                requests,
+                input_tokens,
+                output_tokens,
+                total_tokens,
+                cost_usd,
+                unpriced_requests,
+            }
+        })
+        .collect())
+}
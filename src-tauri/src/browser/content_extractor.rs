@@ -1,132 +1,322 @@
+use super::links::LinkResolver;
+use super::markdown;
+use super::metadata::PageMetadata;
+use super::readability::{self, MainContent};
+use crate::error::ThetaError;
+use crate::models::{ContentFormat, PageImage, PageLink};
+use chrono::{DateTime, Utc};
+use ego_tree::{NodeId, NodeRef};
+use once_cell::sync::Lazy;
 use reqwest::Client;
-use regex::Regex;
-use std::collections::HashMap;
+use scraper::{Html, Node, Selector};
+use std::collections::HashSet;
+
+static TITLE: Lazy<Selector> = Lazy::new(|| Selector::parse("head > title").unwrap());
+
+/// Elements whose text is never part of the page content.
+pub(super) const SKIPPED_ELEMENTS: &[&str] = &["head", "script", "style", "noscript", "template", "iframe", "svg", "math"];
+
+/// Elements that start a new line when rendered, so their text must not run
+/// into that of their neighbours.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "br", "caption", "dd", "details", "dialog", "div", "dl", "dt",
+    "fieldset", "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr",
+    "li", "main", "nav", "ol", "p", "pre", "section", "summary", "table", "td", "th", "tr", "ul",
+];
 
 pub struct ContentExtractor {
     client: Client,
-    title_regex: Regex,
-    meta_regex: Regex,
-    link_regex: Regex,
+    format: ContentFormat,
 }
 
 #[derive(Debug, Clone)]
 pub struct ExtractedContent {
     pub title: String,
+    /// The page's article as plain text, without navigation, sidebars,
+    /// footers and other boilerplate.
     pub content: String,
+    /// The same article as Markdown, if the extractor's format asks for it.
+    pub markdown: Option<String>,
     pub meta_description: Option<String>,
     pub meta_keywords: Option<String>,
-    pub links: Vec<String>,
-    pub images: Vec<String>,
+    pub links: Vec<PageLink>,
+    pub images: Vec<PageImage>,
     pub word_count: usize,
     pub reading_time: u32,
+    pub byline: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub lead_image: Option<String>,
+    pub metadata: PageMetadata,
 }
 
 impl ContentExtractor {
     pub fn new() -> Self {
-        Self {
-            client: Client::new(),
-            title_regex: Regex::new(r"<title[^>]*>([^<]+)</title>").unwrap(),
-            meta_regex: Regex::new(r#"<meta[^>]*name=["']([^"']+)["'][^>]*content=["']([^"']+)["'][^>]*>"#).unwrap(),
-            link_regex: Regex::new(r#"<a[^>]*href=["']([^"']+)["'][^>]*>"#).unwrap(),
-        }
+        Self::with_client(Client::new())
     }
 
-    pub async fn extract_from_url(&self, url: &str) -> Result<ExtractedContent, String> {
+    /// Shares an existing HTTP client, e.g. the one in `AppState`.
+    pub fn with_client(client: Client) -> Self {
+        Self { client, format: ContentFormat::default() }
+    }
+
+    /// With `ContentFormat::Markdown`, `markdown` is filled in alongside the
+    /// plain text `content`.
+    pub fn with_format(mut self, format: ContentFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub async fn extract_from_url(&self, url: &str) -> Result<ExtractedContent, ThetaError> {
         let response = self.client
             .get(url)
             .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
             .send()
             .await
-            .map_err(|e| format!("Failed to fetch URL: {}", e))?;
+            .map_err(|e| ThetaError::extraction_request("Failed to fetch URL", e))?;
 
-        let html = response.text().await.map_err(|e| format!("Failed to read response: {}", e))?;
-        Ok(self.extract_from_html(&html))
+        // Relative links resolve against where any redirects ended up
+        let page_url = response.url().to_string();
+        let html = response.text().await.map_err(|e| ThetaError::extraction_request("Failed to read response", e))?;
+        Ok(self.extract_from_html(&html, &page_url))
     }
 
-    pub fn extract_from_html(&self, html: &str) -> ExtractedContent {
-        let title = self.extract_title(html);
-        let content = self.extract_main_content(html);
-        let meta_description = self.extract_meta_description(html);
-        let meta_keywords = self.extract_meta_keywords(html);
-        let links = self.extract_links(html);
-        let images = self.extract_images(html);
+    /// Parses `html` the way a browser would, so entities are decoded and
+    /// unclosed or misnested tags are repaired before anything is read.
+    /// Links, images and the URLs in the metadata are resolved against
+    /// `page_url`.
+    pub fn extract_from_html(&self, html: &str, page_url: &str) -> ExtractedContent {
+        let document = Html::parse_document(html);
+        let resolver = LinkResolver::new(&document, page_url);
+        let main_content = MainContent::find(&document);
+
+        let title = self.extract_title(&document);
+        let content = main_content.text();
+        let links = resolver.links(&document);
+        let images = resolver.images(&document);
         let word_count = self.count_words(&content);
         let reading_time = self.calculate_reading_time(word_count);
+        let metadata = PageMetadata::from_document(&document, &resolver);
+        let byline = readability::byline(&document).or_else(|| metadata.author());
+        let published_at = readability::published_at(&document).or_else(|| metadata.published_at());
+        let lead_image = readability::lead_image(&document, &main_content).and_then(|image| resolver.resolve(&image));
+        let markdown = match self.format {
+            ContentFormat::Text => None,
+            ContentFormat::Markdown => Some(markdown::to_markdown(&main_content, &resolver)),
+        };
 
         ExtractedContent {
             title,
             content,
-            meta_description,
-            meta_keywords,
+            markdown,
+            meta_description: metadata.description.clone(),
+            meta_keywords: metadata.keywords.clone(),
             links,
             images,
             word_count,
             reading_time,
+            byline,
+            published_at,
+            lead_image,
+            metadata,
         }
     }
 
-    fn extract_title(&self, html: &str) -> String {
-        if let Some(captures) = self.title_regex.captures(html) {
-            captures.get(1).map_or("Untitled".to_string(), |m| m.as_str().trim().to_string())
-        } else {
-            "Untitled".to_string()
+    fn extract_title(&self, document: &Html) -> String {
+        document
+            .select(&TITLE)
+            .next()
+            .map(|title| collapse_whitespace(&title.text().collect::<String>()))
+            .filter(|title| !title.is_empty())
+            .unwrap_or_else(|| "Untitled".to_string())
+    }
+
+    fn count_words(&self, text: &str) -> usize {
+        text.split_whitespace().count()
+    }
+
+    fn calculate_reading_time(&self, word_count: usize) -> u32 {
+        // Average reading speed is ~200 words per minute
+        ((word_count as f32 / 200.0).ceil() as u32).max(1)
+    }
+}
+
+/// The text a reader would see in `node`, leaving out the `removed` nodes.
+/// Scripts, styles, `<noscript>` fallbacks and inline SVG are never
+/// included; comments and CDATA sections are not text nodes in HTML, so
+/// they never appear.
+pub(super) fn visible_text(node: NodeRef<'_, Node>, removed: &HashSet<NodeId>) -> String {
+    let mut text = String::new();
+    collect_text(node, removed, &mut text);
+    collapse_whitespace(&text)
+}
+
+fn collect_text(node: NodeRef<'_, Node>, removed: &HashSet<NodeId>, text: &mut String) {
+    if removed.contains(&node.id()) {
+        return;
+    }
+    match node.value() {
+        Node::Text(value) => text.push_str(value),
+        Node::Element(element) => {
+            let name = element.name();
+            if SKIPPED_ELEMENTS.contains(&name) {
+                return;
+            }
+            let block = BLOCK_ELEMENTS.contains(&name);
+            if block {
+                text.push(' ');
+            }
+            for child in node.children() {
+                collect_text(child, removed, text);
+            }
+            if block {
+                text.push(' ');
+            }
+        }
+        Node::Document | Node::Fragment => {
+            for child in node.children() {
+                collect_text(child, removed, text);
+            }
         }
+        _ => {}
+    }
+}
+
+pub(super) fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEWS_ARTICLE: (&str, &str) = (
+        include_str!("../../tests/fixtures/pages/news_article.html"),
+        "https://www.dailyledger.example/world/2026/jul/14/heatwave-europe-grid",
+    );
+    const DOCS_PAGE: (&str, &str) = (
+        include_str!("../../tests/fixtures/pages/docs_page.html"),
+        "https://docs.ferrous.example/guide/v2/error-handling.html",
+    );
+    const MALFORMED: (&str, &str) = (include_str!("../../tests/fixtures/pages/malformed.html"), "http://vintageradio.example/bob/");
+    const WEBFORMS: (&str, &str) = (
+        include_str!("../../tests/fixtures/pages/webforms.html"),
+        "https://www.westbrook.gov.example/news/news-item.aspx?id=4821",
+    );
+
+    fn extract((html, url): (&str, &str)) -> ExtractedContent {
+        ContentExtractor::new().extract_from_html(html, url)
     }
 
-    fn extract_main_content(&self, html: &str) -> String {
-        // Remove script and style tags
-        let script_regex = Regex::new(r"<script[^>]*>.*?</script>").unwrap();
-        let style_regex = Regex::new(r"<style[^>]*>.*?</style>").unwrap();
-        let tag_regex = Regex::new(r"<[^>]*>").unwrap();
-        
-        let mut content = script_regex.replace_all(html, "").to_string();
-        content = style_regex.replace_all(&content, "").to_string();
-        content = tag_regex.replace_all(&content, " ").to_string();
-        
-        // Clean up whitespace
-        let whitespace_regex = Regex::new(r"\s+").unwrap();
-        content = whitespace_regex.replace_all(&content, " ").to_string();
-        
-        content.trim().to_string()
+    #[test]
+    fn decodes_entities_and_reads_meta_in_any_attribute_order() {
+        let page = extract(NEWS_ARTICLE);
+
+        assert_eq!(page.title, "Heatwave pushes Europe’s grid to the limit – The Daily Ledger");
+        assert_eq!(
+            page.meta_description.as_deref(),
+            Some("Record demand for air conditioning left operators in France, Italy and Spain scrambling to import power as temperatures passed 40°C.")
+        );
+        assert_eq!(page.meta_keywords.as_deref(), Some("energy, heatwave, europe, electricity grid"));
+        assert!(page.content.contains("“We are managing the situation hour by hour,” said a spokesperson for Italy’s transmission operator"));
+        assert!(page.content.contains("along the Rhône were running"));
     }
 
-    fn extract_meta_description(&self, html: &str) -> Option<String> {
-        let description_regex = Regex::new(r#"<meta[^>]*name=["']description["'][^>]*content=["']([^"']+)["'][^>]*>"#).unwrap();
-        description_regex.captures(html)
-            .and_then(|cap| cap.get(1))
-            .map(|m| m.as_str().to_string())
+    #[test]
+    fn keeps_plain_text_content_when_markdown_is_asked_for() {
+        let (html, url) = DOCS_PAGE;
+        let page = ContentExtractor::new().with_format(ContentFormat::Markdown).extract_from_html(html, url);
+
+        assert_eq!(page.content, extract(DOCS_PAGE).content);
+        assert!(page.markdown.is_some_and(|markdown| markdown.contains("```")));
+        assert!(extract(DOCS_PAGE).markdown.is_none());
+    }
+
+    #[test]
+    fn leaves_out_scripts_styles_noscript_and_svg() {
+        for page in [NEWS_ARTICLE, DOCS_PAGE, MALFORMED].map(extract) {
+            for hidden in ["dataLayer", "localStorage", "not content", "text-decoration", "googletagmanager", "window.open", "visitor map"] {
+                assert!(!page.content.contains(hidden), "{:?} leaked into {:?}", hidden, page.title);
+            }
+        }
+        let docs = extract(DOCS_PAGE);
+        assert!(!docs.content.contains("Menu"));
+        assert!(docs.content.contains("fn read_username() -> Result<String, io::Error> {"));
     }
 
-    fn extract_meta_keywords(&self, html: &str) -> Option<String> {
-        let keywords_regex = Regex::new(r#"<meta[^>]*name=["']keywords["'][^>]*content=["']([^"']+)["'][^>]*>"#).unwrap();
-        keywords_regex.captures(html)
-            .and_then(|cap| cap.get(1))
-            .map(|m| m.as_str().to_string())
+    #[test]
+    fn repairs_malformed_markup() {
+        let page = extract(MALFORMED);
+
+        assert_eq!(page.title, "Bob's Vintage Radio Page — Repairs & Parts");
+        assert_eq!(page.meta_description.as_deref(), Some("Valve radio repairs, parts and schematics since 1998"));
+        assert_eq!(page.meta_keywords.as_deref(), Some("valve radio,tube radio,repairs,schematics"));
+        assert!(page.content.starts_with("Welcome to my radio page! I have been restoring valve radios since 1998."));
+        assert!(page.content.contains("Prices start at £45 for a basic service, which includes: Replacing the wax capacitors Checking every valve"));
+        assert!(page.content.contains("Send your set by courier only, please."));
+        assert!(page.content.contains("Update 2003: new schematics section! You are visitor number 00412"));
+        // Comments, conditional comments and CDATA sections are not text
+        assert!(!page.content.contains("counter hidden"));
+        assert!(!page.content.contains("not text in HTML"));
+
+        let links: Vec<&str> = page.links.iter().map(|link| link.url.as_str()).collect();
+        assert_eq!(links, vec![
+            "http://vintageradio.example/bob/index.html",
+            "http://vintageradio.example/bob/repairs.html",
+            "http://vintageradio.example/bob/parts.html",
+            "mailto:bob@vintageradio.example?subject=Repair%20quote",
+            "http://vintageradio.example/bob/schematics/index.html",
+        ]);
+        assert_eq!(page.links[2].text, "Parts & valves");
+        assert_eq!(page.images[0].url, "http://vintageradio.example/bob/images/banner.gif");
     }
 
-    fn extract_links(&self, html: &str) -> Vec<String> {
-        self.link_regex
-            .captures_iter(html)
-            .filter_map(|cap| cap.get(1))
-            .map(|m| m.as_str().to_string())
-            .collect()
+    #[test]
+    fn keeps_the_article_and_drops_the_boilerplate_around_it() {
+        let page = extract(NEWS_ARTICLE);
+
+        assert!(page.content.starts_with("Heatwave pushes Europe’s grid to the limit Record demand for air conditioning"));
+        assert!(page.content.ends_with("a second spell of hot weather is possible later in the month."));
+        for boilerplate in ["Skip to main content", "We use cookies", "Related", "Most read", "Advertisement", "Topics:", "By Marta", "All rights reserved"] {
+            assert!(!page.content.contains(boilerplate), "{:?} was kept", boilerplate);
+        }
+        assert_eq!(page.byline.as_deref(), Some("Marta Lindqvist"));
+        assert_eq!(page.published_at, Some("2026-07-14T05:30:00Z".parse().unwrap()));
+        assert_eq!(page.lead_image.as_deref(), Some("https://media.dailyledger.example/img/2026/07/grid-heatwave-1200.jpg"));
+
+        let docs = extract(DOCS_PAGE);
+        assert!(docs.content.starts_with("Error handling Rust groups errors"));
+        assert!(!docs.content.contains("Introduction") && !docs.content.contains("Traits →"));
+        assert_eq!(docs.lead_image.as_deref(), Some("https://docs.ferrous.example/guide/v2/images/error-flow.png"));
+        assert_eq!((docs.byline, docs.published_at), (None, None));
     }
 
-    fn extract_images(&self, html: &str) -> Vec<String> {
-        let img_regex = Regex::new(r#"<img[^>]*src=["']([^"']+)["'][^>]*>"#).unwrap();
-        img_regex
-            .captures_iter(html)
-            .filter_map(|cap| cap.get(1))
-            .map(|m| m.as_str().to_string())
-            .collect()
+    #[test]
+    fn resolves_metadata_urls_against_the_base_href() {
+        let docs = extract(DOCS_PAGE);
+
+        assert_eq!(docs.metadata.icons[0].href, "https://docs.ferrous.example/favicon-32x32.png");
+        assert_eq!(docs.metadata.feeds[0].href, "https://docs.ferrous.example/releases.atom");
     }
 
-    fn count_words(&self, text: &str) -> usize {
-        text.split_whitespace().count()
+    #[test]
+    fn keeps_articles_inside_a_page_wide_form() {
+        let page = extract(WEBFORMS);
+
+        assert!(page.content.contains("Council approves new cycle lanes for Harbour Road Published 9 March 2026 The planning committee"));
+        assert!(page.content.ends_with("or in the planning section of this website."));
+        for boilerplate in ["Search the site", "Bins and recycling", "Was this page useful?", "Accessibility", "__doPostBack"] {
+            assert!(!page.content.contains(boilerplate), "{:?} was kept", boilerplate);
+        }
+        assert!(page.word_count > 100);
     }
 
-    fn calculate_reading_time(&self, word_count: usize) -> u32 {
-        // Average reading speed is ~200 words per minute
-        ((word_count as f32 / 200.0).ceil() as u32).max(1)
+    #[test]
+    fn counts_words_of_the_visible_text() {
+        let page = extract(("<html><head><title> \n </title></head><body><p>one two</p><p>three</p><script>four five</script></body></html>", "about:blank"));
+
+        assert_eq!(page.title, "Untitled");
+        assert_eq!(page.content, "one two three");
+        assert_eq!(page.word_count, 3);
+        assert_eq!(page.reading_time, 1);
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,247 @@
+use super::content_extractor::{collapse_whitespace, visible_text};
+use crate::models::{LinkKind, PageImage, PageLink};
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use scraper::{ElementRef, Html, Selector};
+use std::collections::{HashMap, HashSet};
+
+static BASE: Lazy<Selector> = Lazy::new(|| Selector::parse("base[href]").unwrap());
+static LINK: Lazy<Selector> = Lazy::new(|| Selector::parse("a[href], area[href]").unwrap());
+static IMAGE: Lazy<Selector> = Lazy::new(|| Selector::parse("img, picture > source").unwrap());
+static LINKED_IMAGE: Lazy<Selector> = Lazy::new(|| Selector::parse("img[alt]").unwrap());
+
+/// Extensions of files a link downloads rather than opens as a page.
+const DOWNLOAD_EXTENSIONS: &[&str] = &[
+    "7z", "apk", "csv", "deb", "dmg", "doc", "docx", "epub", "exe", "gz", "iso", "msi", "pdf", "pkg", "ppt", "pptx",
+    "rar", "rpm", "tar", "tgz", "xls", "xlsx", "xz", "zip",
+];
+
+/// Resolves the URLs of a page the way a browser would: against its
+/// `<base href>` if it has one, else against the page's own URL.
+pub struct LinkResolver {
+    page: Option<Url>,
+    base: Option<Url>,
+}
+
+impl LinkResolver {
+    pub fn new(document: &Html, page_url: &str) -> Self {
+        let page = Url::parse(page_url).ok();
+        let base = document
+            .select(&BASE)
+            .next()
+            .and_then(|base| base.value().attr("href"))
+            .and_then(|href| match &page {
+                Some(page) => page.join(href.trim()).ok(),
+                None => Url::parse(href.trim()).ok(),
+            })
+            .or_else(|| page.clone());
+        Self { page, base }
+    }
+
+    /// The absolute, normalized form of `href`, or `None` for script links
+    /// and empty values. Links to a fragment stay on the page itself, even
+    /// under a `<base href>`. URLs that cannot be resolved are kept as
+    /// written.
+    pub fn resolve(&self, href: &str) -> Option<String> {
+        let href = href.trim();
+        if href.is_empty() || href.to_ascii_lowercase().starts_with("javascript:") {
+            return None;
+        }
+        let base = if href.starts_with('#') { self.page.as_ref().or(self.base.as_ref()) } else { self.base.as_ref() };
+        let resolved = match base {
+            Some(base) => base.join(href),
+            None => Url::parse(href),
+        };
+        match resolved {
+            Ok(mut url) => {
+                if url.fragment() == Some("") {
+                    url.set_fragment(None);
+                }
+                Some(url.to_string())
+            }
+            Err(_) => Some(href.to_string()),
+        }
+    }
+
+    /// Every link of the page once, in the order they first appear, with
+    /// its text and whether it is marked `rel=nofollow`.
+    pub fn links(&self, document: &Html) -> Vec<PageLink> {
+        let mut links: Vec<PageLink> = Vec::new();
+        let mut seen: HashMap<String, usize> = HashMap::new();
+
+        for element in document.select(&LINK) {
+            let Some(url) = element.value().attr("href").and_then(|href| self.resolve(href)) else { continue };
+            let text = link_text(element);
+            if let Some(&index) = seen.get(&url) {
+                if links[index].text.is_empty() {
+                    links[index].text = text;
+                }
+                continue;
+            }
+
+            let rel = element.value().attr("rel").unwrap_or_default().to_ascii_lowercase();
+            let link = PageLink {
+                kind: self.classify(element, &url),
+                nofollow: rel.split_whitespace().any(|relation| relation == "nofollow"),
+                url: url.clone(),
+                text,
+            };
+            seen.insert(url, links.len());
+            links.push(link);
+        }
+        links
+    }
+
+    /// Every image of the page once: `src`, lazy-loading `data-src` and the
+    /// largest `srcset` candidate of each `<img>` and `<picture>` source.
+    /// Inline `data:` placeholders are left out.
+    pub fn images(&self, document: &Html) -> Vec<PageImage> {
+        let mut images: Vec<PageImage> = Vec::new();
+        let mut seen = HashSet::new();
+
+        for element in document.select(&IMAGE) {
+            let value = element.value();
+            let alt = value.attr("alt").map(collapse_whitespace).filter(|alt| !alt.is_empty());
+            let sources = [value.attr("data-src"), value.attr("src")]
+                .into_iter()
+                .flatten()
+                .map(str::to_string)
+                .chain([value.attr("data-srcset"), value.attr("srcset")].into_iter().flatten().filter_map(largest_candidate));
+
+            for source in sources {
+                if source.trim().starts_with("data:") {
+                    continue;
+                }
+                let Some(url) = self.resolve(&source) else { continue };
+                if seen.insert(url.clone()) {
+                    images.push(PageImage { url, alt: alt.clone() });
+                }
+            }
+        }
+        images
+    }
+
+    fn classify(&self, element: ElementRef<'_>, url: &str) -> LinkKind {
+        let href = element.value().attr("href").unwrap_or_default().trim();
+        let Ok(parsed) = Url::parse(url) else {
+            return if href.starts_with('#') { LinkKind::Anchor } else { LinkKind::Other };
+        };
+
+        match parsed.scheme() {
+            "mailto" => return LinkKind::Mailto,
+            "http" | "https" => {}
+            _ => return LinkKind::Other,
+        }
+        if href.starts_with('#') || self.page.as_ref().is_some_and(|page| parsed.fragment().is_some() && same_document(page, &parsed)) {
+            return LinkKind::Anchor;
+        }
+        let extension = parsed.path().rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase());
+        if element.value().attr("download").is_some() || extension.is_some_and(|extension| DOWNLOAD_EXTENSIONS.contains(&extension.as_str())) {
+            return LinkKind::Download;
+        }
+
+        let site = |url: &Url| url.host_str().map(|host| host.trim_start_matches("www.").to_string());
+        match &self.page {
+            Some(page) if site(page) == site(&parsed) => LinkKind::Internal,
+            _ => LinkKind::External,
+        }
+    }
+}
+
+/// What a link says: its text, else the alt text of its image, else its title.
+fn link_text(element: ElementRef<'_>) -> String {
+    let text = visible_text(*element, &HashSet::new());
+    if !text.is_empty() {
+        return text;
+    }
+    element
+        .select(&LINKED_IMAGE)
+        .filter_map(|image| image.value().attr("alt"))
+        .chain(element.value().attr("title"))
+        .map(collapse_whitespace)
+        .find(|text| !text.is_empty())
+        .unwrap_or_default()
+}
+
+fn same_document(a: &Url, b: &Url) -> bool {
+    let mut a = a.clone();
+    let mut b = b.clone();
+    a.set_fragment(None);
+    b.set_fragment(None);
+    a == b
+}
+
+/// The widest (or densest) candidate of a `srcset`, e.g. the 1200w one of
+/// `a.jpg 480w, b.jpg 1200w`.
+fn largest_candidate(srcset: &str) -> Option<String> {
+    srcset
+        .split(',')
+        .filter_map(|candidate| {
+            let mut parts = candidate.split_whitespace();
+            let url = parts.next()?;
+            let size = parts
+                .next()
+                .and_then(|descriptor| descriptor.trim_end_matches(['w', 'x']).parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((url, size))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(url, _)| url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEWS_URL: &str = "https://www.dailyledger.example/world/2026/jul/14/heatwave-europe-grid";
+
+    fn page(html: &str, url: &str) -> (Vec<PageLink>, Vec<PageImage>) {
+        let document = Html::parse_document(html);
+        let resolver = LinkResolver::new(&document, url);
+        (resolver.links(&document), resolver.images(&document))
+    }
+
+    #[test]
+    fn resolves_and_classifies_the_links_of_an_article() {
+        let (links, images) = page(include_str!("../../tests/fixtures/pages/news_article.html"), NEWS_URL);
+        let find = |url: &str| links.iter().find(|link| link.url == url).unwrap_or_else(|| panic!("{} not found", url));
+
+        assert_eq!(find("https://www.dailyledger.example/world").kind, LinkKind::Internal);
+        let shop = find("https://shop.dailyledger.example/");
+        assert_eq!((shop.kind, shop.nofollow, shop.text.as_str()), (LinkKind::External, true, "Shop"));
+        assert_eq!(find(&format!("{}#maincontent", NEWS_URL)).kind, LinkKind::Anchor);
+        assert_eq!(find(&format!("{}#comments", NEWS_URL)).kind, LinkKind::Anchor);
+        assert_eq!(find("mailto:corrections@dailyledger.example").kind, LinkKind::Mailto);
+        // An image link takes its text from the image's alt text
+        assert_eq!(find("https://www.dailyledger.example/").text, "The Daily Ledger");
+        assert!(links.iter().all(|link| !link.url.starts_with("javascript:")));
+        assert_eq!(links.iter().filter(|link| link.url.ends_with("/climate/heatwaves")).count(), 1);
+        assert_eq!(links.iter().filter(|link| link.url == NEWS_URL).count(), 1);
+
+        let urls: Vec<&str> = images.iter().map(|image| image.url.as_str()).collect();
+        assert_eq!(urls, vec![
+            "https://www.dailyledger.example/static/img/logo.svg",
+            "https://media.dailyledger.example/img/2026/07/grid-heatwave-800.jpg",
+            "https://media.dailyledger.example/img/2026/07/grid-heatwave-1200.jpg",
+        ]);
+        assert_eq!(images[1].alt.as_deref(), Some("Transmission lines near Lyon at dusk"));
+    }
+
+    #[test]
+    fn resolves_against_the_base_href_and_keeps_anchors_on_the_page() {
+        let (links, images) = page(
+            include_str!("../../tests/fixtures/pages/docs_page.html"),
+            "https://docs.ferrous.example/guide/v2/error-handling.html?from=search",
+        );
+        let find = |url: &str| links.iter().find(|link| link.url == url).unwrap_or_else(|| panic!("{} not found", url));
+
+        assert_eq!(find("https://docs.ferrous.example/guide/v2/ownership.html#borrowing").kind, LinkKind::Internal);
+        assert_eq!(find("https://docs.ferrous.example/guide/v2/error-handling.html?from=search#error-handling").kind, LinkKind::Anchor);
+        assert_eq!(find("https://docs.ferrous.example/guide/exercises/errors.zip").kind, LinkKind::Download);
+        assert_eq!(find("https://doc.rust-lang.org/std/ops/trait.Try.html").kind, LinkKind::External);
+        assert_eq!(find("https://docs.ferrous.example/guide/v2/traits.html").text, "Traits");
+
+        let urls: Vec<&str> = images.iter().map(|image| image.url.as_str()).collect();
+        assert_eq!(urls, vec!["https://docs.ferrous.example/guide/v2/images/error-flow.png", "https://docs.ferrous.example/guide/v2/images/lazy-diagram.png"]);
+    }
+}
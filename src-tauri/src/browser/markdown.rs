@@ -0,0 +1,329 @@
+use super::content_extractor::{collapse_whitespace, SKIPPED_ELEMENTS};
+use super::links::LinkResolver;
+use super::readability::MainContent;
+use ego_tree::NodeRef;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{ElementRef, Node};
+
+static CODE_LANGUAGE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b(?:language|lang)-([\w+#-]+)").unwrap());
+
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "body", "caption", "center", "dd", "details", "dialog", "div", "dl",
+    "dt", "fieldset", "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr",
+    "html", "li", "main", "nav", "ol", "p", "pre", "section", "summary", "table", "tbody", "td", "tfoot", "th",
+    "thead", "tr", "ul",
+];
+
+/// Cells holding any of these make a table a layout table, whose cells are
+/// rendered as ordinary blocks.
+const LAYOUT_CELL_CONTENT: &[&str] = &["p", "div", "ul", "ol", "table", "pre", "blockquote", "h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// Renders the article as CommonMark with GitHub tables: headings, lists,
+/// fenced code blocks with their language, quotes, tables, links, images
+/// and emphasis are kept; everything else becomes paragraphs. Links and
+/// images point at their resolved URLs.
+pub fn to_markdown(content: &MainContent<'_>, resolver: &LinkResolver) -> String {
+    let renderer = Renderer { content, resolver };
+    let mut blocks = Vec::new();
+    renderer.container(content.root, &mut blocks);
+    blocks.join("\n\n")
+}
+
+struct Renderer<'c, 'a> {
+    content: &'c MainContent<'a>,
+    resolver: &'c LinkResolver,
+}
+
+impl Renderer<'_, '_> {
+    fn included(&self, node: NodeRef<'_, Node>) -> bool {
+        if self.content.is_removed(node) {
+            return false;
+        }
+        node.value().as_element().is_none_or(|element| !SKIPPED_ELEMENTS.contains(&element.name()))
+    }
+
+    /// Renders the children of `node`, gathering runs of inline content
+    /// into paragraphs between its block children.
+    fn container(&self, node: NodeRef<'_, Node>, blocks: &mut Vec<String>) {
+        let mut paragraph = String::new();
+        for child in node.children().filter(|child| self.included(*child)) {
+            match ElementRef::wrap(child) {
+                Some(element) if BLOCK_ELEMENTS.contains(&element.value().name()) => {
+                    push_paragraph(&mut paragraph, blocks);
+                    self.block(element, blocks);
+                }
+                _ => self.inline(child, &mut paragraph),
+            }
+        }
+        push_paragraph(&mut paragraph, blocks);
+    }
+
+    fn block(&self, element: ElementRef<'_>, blocks: &mut Vec<String>) {
+        let name = element.value().name();
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let text = clean_inline(&self.inline_of(*element)).replace('\n', " ");
+                if !text.is_empty() {
+                    let level = name[1..].parse().unwrap_or(1);
+                    blocks.push(format!("{} {}", "#".repeat(level), text));
+                }
+            }
+            "pre" => blocks.push(code_block(element)),
+            "ul" | "ol" => {
+                let list = self.list(element, name == "ol");
+                if !list.is_empty() {
+                    blocks.push(list);
+                }
+            }
+            "blockquote" => {
+                let mut quoted = Vec::new();
+                self.container(*element, &mut quoted);
+                if !quoted.is_empty() {
+                    blocks.push(prefix_lines(&quoted.join("\n\n"), "> ", ">"));
+                }
+            }
+            "table" if !is_layout_table(element) => {
+                if let Some(table) = self.table(element) {
+                    blocks.push(table);
+                }
+            }
+            "hr" => blocks.push("---".to_string()),
+            _ => self.container(*element, blocks),
+        }
+    }
+
+    fn list(&self, element: ElementRef<'_>, ordered: bool) -> String {
+        let mut number: usize = element.value().attr("start").and_then(|start| start.trim().parse().ok()).unwrap_or(1);
+        let mut items = Vec::new();
+        for item in element.child_elements().filter(|item| item.value().name() == "li" && self.included(**item)) {
+            let mut blocks = Vec::new();
+            self.container(*item, &mut blocks);
+            if blocks.is_empty() {
+                continue;
+            }
+            let marker = if ordered { format!("{}. ", number) } else { "- ".to_string() };
+            let indent = " ".repeat(marker.len());
+            let body = prefix_lines(&blocks.join("\n"), &indent, "");
+            items.push(format!("{}{}", marker, &body[indent.len()..]));
+            number += 1;
+        }
+        items.join("\n")
+    }
+
+    /// A GitHub table, with the first row as its header.
+    fn table(&self, element: ElementRef<'_>) -> Option<String> {
+        let rows: Vec<Vec<String>> = element
+            .descendent_elements()
+            .filter(|row| row.value().name() == "tr" && nearest_table(*row) == Some(element.id()))
+            .map(|row| {
+                row.child_elements()
+                    .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+                    .map(|cell| clean_inline(&self.inline_of(*cell)).replace('\n', " ").replace('|', "\\|"))
+                    .collect()
+            })
+            .filter(|cells: &Vec<String>| !cells.is_empty())
+            .collect();
+        let columns = rows.iter().map(Vec::len).max()?;
+
+        let line = |cells: &[String]| {
+            let padded = (0..columns).map(|index| cells.get(index).map_or("", String::as_str));
+            format!("| {} |", padded.collect::<Vec<_>>().join(" | "))
+        };
+        let mut lines = vec![line(&rows[0]), line(&vec!["---".to_string(); columns])];
+        lines.extend(rows[1..].iter().map(|row| line(row)));
+        Some(lines.join("\n"))
+    }
+
+    fn inline_of(&self, node: NodeRef<'_, Node>) -> String {
+        let mut text = String::new();
+        for child in node.children() {
+            self.inline(child, &mut text);
+        }
+        text
+    }
+
+    fn inline(&self, node: NodeRef<'_, Node>, out: &mut String) {
+        if !self.included(node) {
+            return;
+        }
+        let element = match node.value() {
+            Node::Text(text) => {
+                out.push_str(&escape(&text.split_whitespace().collect::<Vec<_>>().join(" "), text));
+                return;
+            }
+            Node::Element(element) => element,
+            _ => return,
+        };
+
+        match element.name() {
+            "br" => out.push('\n'),
+            "a" => {
+                let text = self.inline_of(node);
+                // Anchors within the page are not worth keeping as links
+                let href = element.attr("href").filter(|href| !href.trim().starts_with('#'));
+                match href.and_then(|href| self.resolver.resolve(href)) {
+                    Some(href) if !text.trim().is_empty() => {
+                        push_wrapped(out, &text, "[", &format!("]({})", link_destination(&href)));
+                    }
+                    _ => out.push_str(&text),
+                }
+            }
+            "img" => {
+                let source = [element.attr("src"), element.attr("data-src")]
+                    .into_iter()
+                    .flatten()
+                    .map(str::trim)
+                    .filter(|source| !source.starts_with("data:"))
+                    .find_map(|source| self.resolver.resolve(source));
+                if let Some(source) = source {
+                    let alt = escape(&collapse_whitespace(element.attr("alt").unwrap_or_default()), "");
+                    out.push_str(&format!("![{}]({})", alt, link_destination(&source)));
+                }
+            }
+            "strong" | "b" => push_wrapped(out, &self.inline_of(node), "**", "**"),
+            "em" | "i" => push_wrapped(out, &self.inline_of(node), "*", "*"),
+            "del" | "s" | "strike" => push_wrapped(out, &self.inline_of(node), "~~", "~~"),
+            "code" | "kbd" | "samp" => {
+                let text = collapse_whitespace(&ElementRef::wrap(node).map(|code| code.text().collect::<String>()).unwrap_or_default());
+                if !text.is_empty() {
+                    let fence = if text.contains('`') { "``" } else { "`" };
+                    let padding = if text.starts_with('`') || text.ends_with('`') { " " } else { "" };
+                    out.push_str(&format!("{fence}{padding}{text}{padding}{fence}"));
+                }
+            }
+            _ => {
+                for child in node.children() {
+                    self.inline(child, out);
+                }
+            }
+        }
+    }
+}
+
+fn push_paragraph(paragraph: &mut String, blocks: &mut Vec<String>) {
+    let text = clean_inline(paragraph);
+    if !text.is_empty() {
+        blocks.push(text);
+    }
+    paragraph.clear();
+}
+
+/// Collapses the spaces of each line and drops blank lines.
+fn clean_inline(text: &str) -> String {
+    text.lines().map(collapse_whitespace).filter(|line| !line.is_empty()).collect::<Vec<_>>().join("\n")
+}
+
+/// Wraps inline text in markers, keeping its surrounding spaces outside
+/// them so `a<b> bold </b>b` does not become `a** bold **b`.
+fn push_wrapped(out: &mut String, text: &str, open: &str, close: &str) {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        out.push_str(text);
+        return;
+    }
+    if text.starts_with(char::is_whitespace) {
+        out.push(' ');
+    }
+    out.push_str(&format!("{}{}{}", open, trimmed, close));
+    if text.ends_with(char::is_whitespace) {
+        out.push(' ');
+    }
+}
+
+/// Escapes the characters Markdown would read as syntax. `original` keeps
+/// the whitespace at the ends of the text node, which collapsing loses.
+fn escape(text: &str, original: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    if original.starts_with(char::is_whitespace) && !text.is_empty() {
+        escaped.push(' ');
+    }
+    for character in text.chars() {
+        if matches!(character, '\\' | '`' | '*' | '_' | '[' | ']' | '<') {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    if original.ends_with(char::is_whitespace) {
+        escaped.push(' ');
+    }
+    escaped
+}
+
+fn link_destination(url: &str) -> String {
+    url.replace(' ', "%20").replace('(', "%28").replace(')', "%29")
+}
+
+fn code_block(pre: ElementRef<'_>) -> String {
+    let code = pre.text().collect::<String>();
+    let code = code.trim_matches('\n').trim_end();
+    let language = std::iter::once(pre)
+        .chain(pre.child_elements().filter(|child| child.value().name() == "code"))
+        .filter_map(|element| element.value().attr("class"))
+        .find_map(|class| CODE_LANGUAGE.captures(class).map(|captures| captures[1].to_string()))
+        .unwrap_or_default();
+
+    let longest_run = code.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!("{fence}{language}\n{code}\n{fence}")
+}
+
+fn is_layout_table(table: ElementRef<'_>) -> bool {
+    table.descendent_elements().skip(1).any(|element| LAYOUT_CELL_CONTENT.contains(&element.value().name()))
+}
+
+fn nearest_table(element: ElementRef<'_>) -> Option<ego_tree::NodeId> {
+    element.ancestors().filter_map(ElementRef::wrap).find(|ancestor| ancestor.value().name() == "table").map(|table| table.id())
+}
+
+fn prefix_lines(text: &str, prefix: &str, blank_prefix: &str) -> String {
+    text.lines()
+        .map(|line| if line.is_empty() { blank_prefix.to_string() } else { format!("{}{}", prefix, line) })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scraper::Html;
+
+    fn markdown(html: &str, url: &str) -> String {
+        let document = Html::parse_document(html);
+        to_markdown(&MainContent::find(&document), &LinkResolver::new(&document, url))
+    }
+
+    #[test]
+    fn keeps_the_structure_of_a_documentation_page() {
+        let page = markdown(include_str!("../../tests/fixtures/pages/docs_page.html"), "https://docs.ferrous.example/guide/v2/error-handling.html");
+
+        let expected = [
+            "# Error handling\n\nRust groups errors into two major categories: *recoverable* and *unrecoverable* errors. Recoverable errors are represented with the `Result<T, E>` type.",
+            "## The `?` operator",
+            "```rust\nuse std::fs::File;\nuse std::io::{self, Read};\n\nfn read_username() -> Result<String, io::Error> {",
+            "    Ok(username)\n}\n```\n\nThings to remember:",
+            "- It only works in functions that return `Result` or `Option`.\n- It converts errors with `From`.\n  - Implement `From` for your own error type.\n- See [the `Try` trait](https://doc.rust-lang.org/std/ops/trait.Try.html) for details.",
+            "### Choosing an approach\n\n| Situation | Use |\n| --- | --- |\n| Prototype or test | `unwrap` |\n| Library code | A custom error `enum` |\n| Application code | `Box<dyn Error>` \\| anyhow |",
+            "1. Read the [borrowing chapter](https://docs.ferrous.example/guide/v2/ownership.html#borrowing) first.\n2. Then try the [exercises](https://docs.ferrous.example/guide/exercises/errors.zip).",
+            "![How an error travels up the call stack](https://docs.ferrous.example/guide/v2/images/error-flow.png) ![Lazy-loaded diagram](https://docs.ferrous.example/guide/v2/images/lazy-diagram.png)",
+        ];
+        for part in expected {
+            assert!(page.contains(part), "{:?} is missing from:\n{}", part, page);
+        }
+        assert!(!page.contains("Introduction"));
+    }
+
+    #[test]
+    fn renders_quotes_emphasis_and_breaks() {
+        let page = markdown(
+            "<main><blockquote><p>First</p><p>Second <b>bold </b>text</p></blockquote>\
+             <p>Line one<br>Line two with a_b and [brackets]</p><hr><p>Anchors such as <a href=\"#top\">the top</a> stay plain text</p></main>",
+            "https://example.com/notes/",
+        );
+
+        assert_eq!(
+            page,
+            "> First\n>\n> Second **bold** text\n\nLine one\nLine two with a\\_b and \\[brackets\\]\n\n---\n\nAnchors such as the top stay plain text"
+        );
+    }
+}
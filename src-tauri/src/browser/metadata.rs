@@ -0,0 +1,325 @@
+use super::content_extractor::collapse_whitespace;
+use super::links::LinkResolver;
+use super::readability::parse_date;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+static META: Lazy<Selector> = Lazy::new(|| Selector::parse("meta[content]").unwrap());
+static LINK: Lazy<Selector> = Lazy::new(|| Selector::parse("link[rel][href]").unwrap());
+static JSON_LD: Lazy<Selector> = Lazy::new(|| Selector::parse("script[type]").unwrap());
+
+/// Meta properties whose content is a URL.
+const URL_PROPERTIES: &[&str] = &["og:url", "og:image", "og:image:url", "twitter:image", "twitter:image:src"];
+
+/// What a page says about itself in its `<head>`: meta tags, OpenGraph and
+/// Twitter card properties, `<link>` relations and schema.org JSON-LD.
+/// URLs are resolved against the page the same way its links are.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PageMetadata {
+    pub description: Option<String>,
+    pub keywords: Option<String>,
+    pub canonical_url: Option<String>,
+    pub open_graph: OpenGraph,
+    pub twitter_card: TwitterCard,
+    /// Translations of the page, from `<link rel=alternate hreflang=...>`.
+    pub alternates: Vec<AlternateLink>,
+    pub icons: Vec<IconLink>,
+    pub feeds: Vec<FeedLink>,
+    pub json_ld: Vec<JsonLdItem>,
+}
+
+/// `og:` properties.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpenGraph {
+    pub title: Option<String>,
+    /// `og:type`, such as `article` or `website`.
+    pub kind: Option<String>,
+    pub url: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    pub locale: Option<String>,
+    pub images: Vec<String>,
+}
+
+/// `twitter:` properties.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TwitterCard {
+    /// `summary`, `summary_large_image`, `player` or `app`.
+    pub card: Option<String>,
+    pub site: Option<String>,
+    pub creator: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlternateLink {
+    pub hreflang: String,
+    pub href: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IconLink {
+    pub href: String,
+    pub rel: String,
+    pub sizes: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedKind {
+    Rss,
+    Atom,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeedLink {
+    pub href: String,
+    pub title: Option<String>,
+    pub kind: FeedKind,
+}
+
+/// One schema.org item from a JSON-LD block, with the properties bookmarks
+/// and readers care about pulled out. Items of an `@graph` are listed one
+/// by one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonLdItem {
+    /// `@type`, such as `NewsArticle` or `Product`.
+    pub types: Vec<String>,
+    /// `headline`, else `name`.
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    pub images: Vec<String>,
+    pub authors: Vec<String>,
+    pub publisher: Option<String>,
+    pub date_published: Option<String>,
+    pub date_modified: Option<String>,
+    /// The item as written, for properties not pulled out above.
+    pub data: Value,
+}
+
+impl PageMetadata {
+    pub fn from_document(document: &Html, resolver: &LinkResolver) -> Self {
+        let mut metadata = Self::default();
+
+        for (key, content) in meta_tags(document) {
+            metadata.add_meta(&key, content, resolver);
+        }
+        for link in document.select(&LINK) {
+            metadata.add_link(link, resolver);
+        }
+        for script in document.select(&JSON_LD) {
+            if script.value().attr("type").is_some_and(|kind| kind.trim().eq_ignore_ascii_case("application/ld+json")) {
+                metadata.json_ld.extend(parse_json_ld(&script.text().collect::<String>(), resolver));
+            }
+        }
+        metadata
+    }
+
+    /// The first author named in the page's JSON-LD.
+    pub fn author(&self) -> Option<String> {
+        self.json_ld.iter().find_map(|item| item.authors.first().cloned())
+    }
+
+    /// When the page's JSON-LD says it was published.
+    pub fn published_at(&self) -> Option<DateTime<Utc>> {
+        self.json_ld.iter().find_map(|item| item.date_published.as_deref().and_then(parse_date))
+    }
+
+    fn add_meta(&mut self, key: &str, content: String, resolver: &LinkResolver) {
+        let content = if URL_PROPERTIES.contains(&key) {
+            let Some(url) = resolver.resolve(&content) else { return };
+            url
+        } else {
+            content
+        };
+        let slot = match key {
+            "description" => &mut self.description,
+            "keywords" => &mut self.keywords,
+            "og:title" => &mut self.open_graph.title,
+            "og:type" => &mut self.open_graph.kind,
+            "og:url" => &mut self.open_graph.url,
+            "og:description" => &mut self.open_graph.description,
+            "og:site_name" => &mut self.open_graph.site_name,
+            "og:locale" => &mut self.open_graph.locale,
+            "og:image" | "og:image:url" => {
+                if !self.open_graph.images.contains(&content) {
+                    self.open_graph.images.push(content);
+                }
+                return;
+            }
+            "twitter:card" => &mut self.twitter_card.card,
+            "twitter:site" => &mut self.twitter_card.site,
+            "twitter:creator" => &mut self.twitter_card.creator,
+            "twitter:title" => &mut self.twitter_card.title,
+            "twitter:description" => &mut self.twitter_card.description,
+            "twitter:image" | "twitter:image:src" => &mut self.twitter_card.image,
+            _ => return,
+        };
+        slot.get_or_insert(content);
+    }
+
+    fn add_link(&mut self, link: ElementRef<'_>, resolver: &LinkResolver) {
+        let value = link.value();
+        let Some(href) = value.attr("href").and_then(|href| resolver.resolve(href)) else { return };
+        let rel = value.attr("rel").unwrap_or_default().to_ascii_lowercase();
+        let relations: Vec<&str> = rel.split_whitespace().collect();
+        let attribute = |name: &str| value.attr(name).map(collapse_whitespace).filter(|value| !value.is_empty());
+
+        if relations.contains(&"canonical") {
+            self.canonical_url.get_or_insert(href.clone());
+        }
+        if relations.iter().any(|relation| matches!(*relation, "icon" | "apple-touch-icon" | "apple-touch-icon-precomposed" | "mask-icon")) {
+            self.icons.push(IconLink {
+                href: href.clone(),
+                rel: relations.join(" "),
+                sizes: attribute("sizes"),
+                mime_type: attribute("type"),
+            });
+        }
+        if relations.contains(&"alternate") {
+            if let Some(hreflang) = attribute("hreflang") {
+                self.alternates.push(AlternateLink { hreflang, href: href.clone() });
+            }
+            let kind = match attribute("type").map(|kind| kind.to_ascii_lowercase()).as_deref() {
+                Some("application/rss+xml") => Some(FeedKind::Rss),
+                Some("application/atom+xml") => Some(FeedKind::Atom),
+                Some("application/feed+json") | Some("application/json") => Some(FeedKind::Json),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                self.feeds.push(FeedLink { href, title: attribute("title"), kind });
+            }
+        }
+    }
+}
+
+/// The meta tags of a page as `(key, content)` pairs, keyed by the tag's
+/// `property`, `name` or `itemprop` trimmed and in lower case. Tags with no
+/// content are left out.
+pub(super) fn meta_tags(document: &Html) -> impl Iterator<Item = (String, String)> + '_ {
+    document.select(&META).filter_map(|meta| {
+        let value = meta.value();
+        let key = ["property", "name", "itemprop"].iter().find_map(|attribute| value.attr(attribute))?;
+        let content = collapse_whitespace(value.attr("content").unwrap_or_default());
+        (!content.is_empty()).then(|| (key.trim().to_ascii_lowercase(), content))
+    })
+}
+
+/// The items of a JSON-LD block. Blocks that are not valid JSON are skipped,
+/// as browsers do.
+fn parse_json_ld(text: &str, resolver: &LinkResolver) -> Vec<JsonLdItem> {
+    let Ok(value) = serde_json::from_str::<Value>(text.trim()) else { return Vec::new() };
+
+    let mut items = Vec::new();
+    let mut pending = vec![value];
+    while let Some(value) = pending.pop() {
+        match value {
+            Value::Array(values) => pending.extend(values.into_iter().rev()),
+            Value::Object(mut object) => match object.remove("@graph") {
+                Some(graph) => pending.push(graph),
+                None => items.push(json_ld_item(Value::Object(object), resolver)),
+            },
+            _ => {}
+        }
+    }
+    items
+}
+
+fn json_ld_item(data: Value, resolver: &LinkResolver) -> JsonLdItem {
+    let first = |keys: &[&str]| keys.iter().find_map(|key| names(&data[*key]).into_iter().next());
+
+    JsonLdItem {
+        types: names(&data["@type"]),
+        name: first(&["headline", "name"]),
+        description: first(&["description"]),
+        url: first(&["url", "@id"]).and_then(|url| resolver.resolve(&url)),
+        images: names(&data["image"]).iter().filter_map(|image| resolver.resolve(image)).collect(),
+        authors: names(&data["author"]),
+        publisher: first(&["publisher"]),
+        date_published: first(&["datePublished"]),
+        date_modified: first(&["dateModified"]),
+        data,
+    }
+}
+
+/// The strings a property holds. Properties may be a string, an object such
+/// as a `Person` or `ImageObject`, or a list of either; objects count by
+/// their name or URL.
+fn names(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(text) => Some(collapse_whitespace(text)).into_iter().filter(|text| !text.is_empty()).collect(),
+        Value::Array(values) => values.iter().flat_map(names).collect(),
+        Value::Object(object) => ["name", "url", "@id"].iter().find_map(|key| object.get(*key).map(names).filter(|found| !found.is_empty())).unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(html: &str, page_url: &str) -> PageMetadata {
+        let document = Html::parse_document(html);
+        PageMetadata::from_document(&document, &LinkResolver::new(&document, page_url))
+    }
+
+    #[test]
+    fn reads_the_head_of_a_news_article() {
+        let page = metadata(
+            include_str!("../../tests/fixtures/pages/news_article.html"),
+            "https://www.dailyledger.example/world/2026/jul/14/heatwave-europe-grid",
+        );
+
+        assert_eq!(page.canonical_url.as_deref(), Some("https://www.dailyledger.example/world/2026/jul/14/heatwave-europe-grid"));
+        assert_eq!(page.open_graph.title.as_deref(), Some("Heatwave pushes Europe’s grid to the limit"));
+        assert_eq!(page.open_graph.kind.as_deref(), Some("article"));
+        assert_eq!(page.open_graph.site_name.as_deref(), Some("The Daily Ledger"));
+        assert_eq!(page.open_graph.images, vec!["https://media.dailyledger.example/img/2026/07/grid-heatwave-1200.jpg"]);
+        assert_eq!(page.twitter_card.card.as_deref(), Some("summary_large_image"));
+        assert_eq!(page.twitter_card.creator.as_deref(), Some("@mlindqvist"));
+        assert_eq!(page.alternates.iter().map(|link| link.hreflang.as_str()).collect::<Vec<_>>(), vec!["en", "de"]);
+        assert_eq!(page.feeds, vec![FeedLink { href: "https://www.dailyledger.example/world/rss".to_string(), title: Some("The Daily Ledger » World".to_string()), kind: FeedKind::Rss }]);
+        assert_eq!(page.icons.iter().map(|icon| icon.href.as_str()).collect::<Vec<_>>(), vec!["https://www.dailyledger.example/favicon.ico", "https://www.dailyledger.example/icons/apple-touch-icon-180.png"]);
+        assert_eq!(page.icons[0].sizes.as_deref(), Some("any"));
+
+        let article = &page.json_ld[0];
+        assert_eq!(article.types, vec!["NewsArticle"]);
+        assert_eq!(article.authors, vec!["Marta Lindqvist"]);
+        assert_eq!(article.publisher.as_deref(), Some("The Daily Ledger"));
+        assert_eq!(article.date_modified.as_deref(), Some("2026-07-14T09:12:00+01:00"));
+        assert_eq!(page.published_at(), Some("2026-07-14T05:30:00Z".parse().unwrap()));
+    }
+
+    #[test]
+    fn accepts_twitter_properties_graphs_and_broken_json_ld() {
+        let page = metadata(
+            r#"<head>
+                <meta property="twitter:title" content="Card title"><meta property="og:image:url" content="/a.png">
+                <link rel="Shortcut Icon" href="/favicon.ico" type="image/x-icon">
+                <script type="application/ld+json">{"@context": "https://schema.org", "@graph": [
+                    {"@type": ["WebPage", "ItemPage"], "name": "Page"},
+                    {"@type": "Recipe", "name": "Bread", "author": ["Ann", {"@type": "Person", "name": "Ben"}], "image": {"url": "/bread.jpg"}}
+                ]}</script>
+                <script type="application/ld+json">{ not json }</script>
+            </head>"#,
+            "https://recipes.example/bread/",
+        );
+
+        assert_eq!(page.twitter_card.title.as_deref(), Some("Card title"));
+        assert_eq!(page.open_graph.images, vec!["https://recipes.example/a.png"]);
+        assert_eq!(page.icons[0].rel, "shortcut icon");
+        assert_eq!(page.json_ld.len(), 2);
+        assert_eq!(page.json_ld[0].types, vec!["WebPage", "ItemPage"]);
+        assert_eq!(page.json_ld[1].authors, vec!["Ann", "Ben"]);
+        assert_eq!(page.json_ld[1].images, vec!["https://recipes.example/bread.jpg"]);
+        assert_eq!(page.author().as_deref(), Some("Ann"));
+    }
+}
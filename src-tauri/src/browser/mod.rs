@@ -1,5 +1,9 @@
 pub mod tab_manager;
 pub mod content_extractor;
+pub mod links;
+pub mod markdown;
+pub mod metadata;
+pub mod readability;
 pub mod search_engine;
 
 pub use tab_manager::TabManager;
@@ -0,0 +1,357 @@
+use super::content_extractor::{collapse_whitespace, visible_text, SKIPPED_ELEMENTS};
+use super::metadata::meta_tags;
+use chrono::{DateTime, NaiveDate, Utc};
+use ego_tree::iter::Edge;
+use ego_tree::{NodeId, NodeRef};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{ElementRef, Html, Node, Selector};
+use std::collections::{HashMap, HashSet};
+
+/// Tried in order; the first that holds the article wins over scoring.
+static SEMANTIC_ROOTS: Lazy<Vec<Selector>> =
+    Lazy::new(|| ["article", "[role=main]", "main"].iter().map(|selector| Selector::parse(selector).unwrap()).collect());
+static PARAGRAPHS: Lazy<Selector> = Lazy::new(|| Selector::parse("p, pre, td, blockquote").unwrap());
+static BODY: Lazy<Selector> = Lazy::new(|| Selector::parse("body").unwrap());
+static AUTHOR_LINK: Lazy<Selector> = Lazy::new(|| Selector::parse("a[rel~=author]").unwrap());
+static BYLINE: Lazy<Selector> = Lazy::new(|| Selector::parse(".byline, .author, [itemprop=author]").unwrap());
+static TIME: Lazy<Selector> = Lazy::new(|| Selector::parse("time[datetime]").unwrap());
+static IMAGE_LINK: Lazy<Selector> = Lazy::new(|| Selector::parse("link[rel=image_src][href]").unwrap());
+static IMAGE: Lazy<Selector> = Lazy::new(|| Selector::parse("img[src]").unwrap());
+
+/// Class and id words of page furniture: banners, share bars, comments...
+static UNLIKELY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\b(ads?|advert\w*|author|banner|breadcrumbs?|byline|comments?|cookies?|disqus|footer|masthead|menu|modal|most-read|newsletter|popup|promo\w*|related|share|sharing|sidebar|skip-link|social|sponsor\w*|subscribe|widget)\b").unwrap()
+});
+/// ...unless they also name the content, as in `article-body`.
+static MAYBE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)article|body|content|entry|main|post|story|text").unwrap());
+static POSITIVE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)article|body|content|entry|main|page|post|story|text|blog").unwrap());
+static NEGATIVE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)comment|footer|footnote|masthead|meta|nav|menu|promo|related|share|sidebar|sponsor|widget").unwrap());
+static BY_PREFIX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)^by\s+").unwrap());
+
+/// Never part of an article, wherever they appear. Forms only count when
+/// they are small, since some sites wrap the whole page in one.
+const BOILERPLATE_ELEMENTS: &[&str] = &["nav", "aside", "footer", "button", "dialog", "select", "input", "textarea"];
+const BOILERPLATE_ROLES: &[&str] = &["navigation", "complementary", "contentinfo", "dialog", "alertdialog", "banner", "search", "menu", "menubar"];
+
+/// Containers dropped when most of their text is link text.
+const LINK_LIST_ELEMENTS: &[&str] = &["div", "section", "ul", "ol", "dl", "p", "table"];
+const MAX_LINK_DENSITY: f32 = 0.5;
+
+/// Characters an `<article>` or `<main>` needs before it is trusted to hold
+/// the page's content.
+const MIN_SEMANTIC_TEXT: usize = 200;
+/// Shorter paragraphs don't count towards their container's score.
+const MIN_PARAGRAPH_TEXT: usize = 25;
+const MAX_BYLINE_LENGTH: usize = 100;
+
+/// The part of a page that holds its article, and the boilerplate inside it
+/// to leave out. The root is the `<article>` or `<main>` element when the
+/// page has a substantial one; otherwise it is the element whose paragraphs
+/// score best on text length and commas, penalized by link density.
+pub struct MainContent<'a> {
+    pub root: NodeRef<'a, Node>,
+    removed: HashSet<NodeId>,
+}
+
+impl<'a> MainContent<'a> {
+    pub fn find(document: &'a Html) -> Self {
+        let body = document.select(&BODY).next().map_or_else(|| document.tree.root(), |body| *body);
+        let stats = TextStats::new(document);
+        let root = semantic_root(document, &stats).or_else(|| best_scored(document, &stats)).unwrap_or(body);
+
+        let content = Self::without_boilerplate(root, &stats);
+        if !content.text().is_empty() {
+            return content;
+        }
+        // Better the whole page than nothing when the guess was wrong
+        let content = if root == body { content } else { Self::without_boilerplate(body, &stats) };
+        if content.text().is_empty() {
+            Self { root: body, removed: HashSet::new() }
+        } else {
+            content
+        }
+    }
+
+    fn without_boilerplate(root: NodeRef<'a, Node>, stats: &TextStats) -> Self {
+        let mut removed = HashSet::new();
+        for child in root.children() {
+            mark_boilerplate(child, stats, &mut removed);
+        }
+        Self { root, removed }
+    }
+
+    /// Whether `node` was dropped as boilerplate; its descendants are not
+    /// marked separately.
+    pub fn is_removed(&self, node: NodeRef<'_, Node>) -> bool {
+        self.removed.contains(&node.id())
+    }
+
+    pub fn text(&self) -> String {
+        visible_text(self.root, &self.removed)
+    }
+
+    /// The first image of the article, skipping inline placeholders.
+    fn first_image(&self) -> Option<String> {
+        let root = ElementRef::wrap(self.root)?;
+        root.select(&IMAGE)
+            .filter(|image| !image.ancestors().any(|ancestor| self.removed.contains(&ancestor.id())))
+            .filter_map(|image| image.value().attr("src"))
+            .map(str::trim)
+            .find(|src| !src.is_empty() && !src.starts_with("data:"))
+            .map(str::to_string)
+    }
+}
+
+/// Who wrote the page: its author meta tag, else the author link or byline
+/// element, without a leading "By".
+pub fn byline(document: &Html) -> Option<String> {
+    meta_content(document, &["author", "article:author", "dc.creator"])
+        .filter(|author| !author.starts_with("http"))
+        .or_else(|| document.select(&AUTHOR_LINK).chain(document.select(&BYLINE)).map(text_of).find(|text| !text.is_empty()))
+        .map(|byline| BY_PREFIX.replace(&byline, "").to_string())
+        .filter(|byline| !byline.is_empty() && byline.chars().count() <= MAX_BYLINE_LENGTH)
+}
+
+/// When the page was published, from its meta tags or `<time>` elements.
+/// Dates without a time are taken as midnight UTC.
+pub fn published_at(document: &Html) -> Option<DateTime<Utc>> {
+    let keys = ["article:published_time", "datepublished", "date", "pubdate", "publishdate", "dc.date", "dc.date.issued", "dcterms.created"];
+    meta_content(document, &keys)
+        .and_then(|date| parse_date(&date))
+        .or_else(|| {
+            let mut times: Vec<ElementRef> = document.select(&TIME).collect();
+            // A `pubdate` attribute marks the publication time among others
+            times.sort_by_key(|time| time.value().attr("pubdate").is_none());
+            times.into_iter().find_map(|time| time.value().attr("datetime").and_then(parse_date))
+        })
+}
+
+/// The image representing the page: its OpenGraph or Twitter image, else
+/// the first image of the article.
+pub fn lead_image(document: &Html, content: &MainContent<'_>) -> Option<String> {
+    meta_content(document, &["og:image", "og:image:url", "og:image:secure_url", "twitter:image", "twitter:image:src"])
+        .or_else(|| document.select(&IMAGE_LINK).find_map(|link| link.value().attr("href")).map(|href| href.trim().to_string()))
+        .filter(|image| !image.is_empty())
+        .or_else(|| content.first_image())
+}
+
+fn semantic_root<'a>(document: &'a Html, stats: &TextStats) -> Option<NodeRef<'a, Node>> {
+    SEMANTIC_ROOTS.iter().find_map(|selector| {
+        document
+            .select(selector)
+            .map(|element| (stats.get(*element).length(), *element))
+            .filter(|(length, _)| *length >= MIN_SEMANTIC_TEXT)
+            .max_by_key(|(length, _)| *length)
+            .map(|(_, element)| element)
+    })
+}
+
+/// Scores every paragraph-like element by its length and commas, credits
+/// its parent in full and its grandparent by half, and returns the
+/// container with the best score once link density is taken into account.
+fn best_scored<'a>(document: &'a Html, stats: &TextStats) -> Option<NodeRef<'a, Node>> {
+    let mut scores: HashMap<NodeId, (NodeRef<'a, Node>, f32)> = HashMap::new();
+
+    for paragraph in document.select(&PARAGRAPHS) {
+        if paragraph.ancestors().filter_map(ElementRef::wrap).any(|ancestor| is_boilerplate(ancestor, stats)) {
+            continue;
+        }
+        let count = stats.get(*paragraph);
+        let length = count.length();
+        if length < MIN_PARAGRAPH_TEXT {
+            continue;
+        }
+        let score = 1.0 + count.commas as f32 + (length as f32 / 100.0).min(3.0);
+
+        let ancestors = paragraph.ancestors().filter_map(ElementRef::wrap).take(2);
+        for (level, ancestor) in ancestors.enumerate() {
+            let entry = scores.entry(ancestor.id()).or_insert_with(|| (*ancestor, initial_score(ancestor)));
+            entry.1 += score / (level + 1) as f32;
+        }
+    }
+
+    scores
+        .into_values()
+        .map(|(node, score)| (node, score * (1.0 - stats.get(node).link_density())))
+        .filter(|(_, score)| *score > 0.0)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(node, _)| node)
+}
+
+fn initial_score(element: ElementRef<'_>) -> f32 {
+    let tag = match element.value().name() {
+        "div" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "ol" | "ul" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    let names = class_and_id(element);
+    let weight = if names.is_empty() {
+        0.0
+    } else {
+        (if POSITIVE.is_match(&names) { 25.0 } else { 0.0 }) - (if NEGATIVE.is_match(&names) { 25.0 } else { 0.0 })
+    };
+    tag + weight
+}
+
+fn mark_boilerplate(node: NodeRef<'_, Node>, stats: &TextStats, removed: &mut HashSet<NodeId>) {
+    let Some(element) = ElementRef::wrap(node) else { return };
+
+    let link_list = LINK_LIST_ELEMENTS.contains(&element.value().name()) && stats.get(node).link_density() > MAX_LINK_DENSITY;
+    if is_boilerplate(element, stats) || link_list {
+        removed.insert(node.id());
+        return;
+    }
+    for child in node.children() {
+        mark_boilerplate(child, stats, removed);
+    }
+}
+
+fn is_boilerplate(element: ElementRef<'_>, stats: &TextStats) -> bool {
+    let value = element.value();
+    if BOILERPLATE_ELEMENTS.contains(&value.name()) || value.attr("hidden").is_some() || value.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    if value.name() == "form" && stats.get(*element).length() < MIN_SEMANTIC_TEXT {
+        return true;
+    }
+    if value.attr("role").is_some_and(|role| BOILERPLATE_ROLES.contains(&role.trim().to_ascii_lowercase().as_str())) {
+        return true;
+    }
+    let names = class_and_id(element);
+    !matches!(value.name(), "a" | "body" | "html") && UNLIKELY.is_match(&names) && !MAYBE.is_match(&names)
+}
+
+fn class_and_id(element: ElementRef<'_>) -> String {
+    let value = element.value();
+    format!("{} {}", value.attr("class").unwrap_or_default(), value.attr("id").unwrap_or_default()).trim().to_string()
+}
+
+fn text_of(element: ElementRef<'_>) -> String {
+    visible_text(*element, &HashSet::new())
+}
+
+/// How much visible text every node of a page holds, counted once from the
+/// leaves up so that scoring never walks the same subtree twice.
+struct TextStats(HashMap<NodeId, TextCount>);
+
+#[derive(Debug, Default, Clone, Copy)]
+struct TextCount {
+    /// Characters other than whitespace.
+    chars: usize,
+    words: usize,
+    commas: usize,
+    /// Length of the text inside links.
+    link_length: usize,
+}
+
+impl TextStats {
+    fn new(document: &Html) -> Self {
+        let mut counts: HashMap<NodeId, TextCount> = HashMap::new();
+        for edge in document.tree.root().traverse() {
+            let Edge::Close(node) = edge else { continue };
+            let count = match node.value() {
+                Node::Text(text) => TextCount {
+                    chars: text.chars().filter(|c| !c.is_whitespace()).count(),
+                    words: text.split_whitespace().count(),
+                    commas: text.matches(',').count(),
+                    link_length: 0,
+                },
+                Node::Element(element) if SKIPPED_ELEMENTS.contains(&element.name()) => TextCount::default(),
+                Node::Element(_) | Node::Document | Node::Fragment => {
+                    // Children close before their parent, so theirs are all counted
+                    let mut count = node.children().filter_map(|child| counts.get(&child.id())).fold(TextCount::default(), |total, child| TextCount {
+                        chars: total.chars + child.chars,
+                        words: total.words + child.words,
+                        commas: total.commas + child.commas,
+                        link_length: total.link_length + child.link_length,
+                    });
+                    if node.value().as_element().is_some_and(|element| element.name() == "a") {
+                        count.link_length = count.length();
+                    }
+                    count
+                }
+                _ => TextCount::default(),
+            };
+            counts.insert(node.id(), count);
+        }
+        Self(counts)
+    }
+
+    fn get(&self, node: NodeRef<'_, Node>) -> TextCount {
+        self.0.get(&node.id()).copied().unwrap_or_default()
+    }
+}
+
+impl TextCount {
+    /// Characters of the text with its whitespace collapsed to single spaces.
+    fn length(&self) -> usize {
+        self.chars + self.words.saturating_sub(1)
+    }
+
+    /// The share of the text that sits inside links.
+    fn link_density(&self) -> f32 {
+        match self.length() {
+            0 => 0.0,
+            length => self.link_length as f32 / length as f32,
+        }
+    }
+}
+
+/// The content of the first meta tag named by any of `keys`, in the order
+/// given, matching `name`, `property` and `itemprop` case-insensitively.
+fn meta_content(document: &Html, keys: &[&str]) -> Option<String> {
+    let tags: Vec<(String, String)> = meta_tags(document).collect();
+    keys.iter().find_map(|key| tags.iter().find(|(name, _)| name == key).map(|(_, content)| content.clone()))
+}
+
+pub(super) fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::parse_from_rfc2822(value))
+        .map(|date| date.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            let date = NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok()?;
+            Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_a_substantial_article_and_drops_link_lists() {
+        let paragraph = "A paragraph long enough to count, with a comma or two, and then some more words. ".repeat(3);
+        let html = format!(
+            r#"<body><article class="teaser"><p>Short teaser.</p></article>
+               <div id="story"><article><h1>Title</h1><ul><li><a href="/a">Share on X</a></li><li><a href="/b">Share by email</a></li></ul>
+               <p>{paragraph}</p><div hidden>Hidden note</div></article></div></body>"#
+        );
+        let document = Html::parse_document(&html);
+
+        let content = MainContent::find(&document);
+
+        assert_eq!(content.text(), format!("Title {}", paragraph.trim()));
+    }
+
+    #[test]
+    fn falls_back_to_the_whole_body_when_only_boilerplate_is_found() {
+        let document = Html::parse_document("<body><aside><p>Opening hours: nine to five, Monday to Friday.</p></aside></body>");
+
+        assert_eq!(MainContent::find(&document).text(), "Opening hours: nine to five, Monday to Friday.");
+    }
+
+    #[test]
+    fn reads_the_byline_from_the_page_when_there_is_no_meta_tag() {
+        let document = Html::parse_document(
+            r#"<article><p class="byline">By Jane Doe</p><time datetime="2024-02-03">3 February</time><p>Text</p></article>"#,
+        );
+
+        assert_eq!(byline(&document).as_deref(), Some("Jane Doe"));
+        assert_eq!(published_at(&document), Some("2024-02-03T00:00:00Z".parse().unwrap()));
+    }
+}
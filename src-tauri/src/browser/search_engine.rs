@@ -1,3 +1,5 @@
+use crate::error::ThetaError;
+use crate::search::hybrid::rank_score;
 use reqwest::Client;
 use serde_json::Value;
 use urlencoding::encode;
@@ -21,7 +23,7 @@ impl SearchEngine {
         }
     }
 
-    pub async fn search_duckduckgo(&self, query: &str) -> Result<Vec<SearchResult>, String> {
+    pub async fn search_duckduckgo(&self, query: &str) -> Result<Vec<SearchResult>, ThetaError> {
         let url = format!("https://api.duckduckgo.com/?q={}&format=json&no_html=1", encode(query));
         
         let response = self.client
@@ -29,9 +31,9 @@ impl SearchEngine {
             .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
             .send()
             .await
-            .map_err(|e| format!("Search request failed: {}", e))?;
+            .map_err(|e| ThetaError::search_request("Search request failed", e))?;
 
-        let json: Value = response.json().await.map_err(|e| format!("Failed to parse response: {}", e))?;
+        let json: Value = response.json().await.map_err(|e| ThetaError::search_request("Failed to parse response", e))?;
         
         let mut results = Vec::new();
         
@@ -43,7 +45,7 @@ impl SearchEngine {
                         title: text.split(" - ").next().unwrap_or(text).to_string(),
                         url: url.to_string(),
                         snippet: text.to_string(),
-                        relevance_score: 0.8,
+                        relevance_score: 0.0,
                     });
                 }
             }
@@ -56,17 +58,21 @@ impl SearchEngine {
                     title: json["Heading"].as_str().unwrap_or("DuckDuckGo Result").to_string(),
                     url: abstract_url.to_string(),
                     snippet: abstract_text.to_string(),
-                    relevance_score: 0.9,
+                    relevance_score: 0.0,
                 });
             }
         }
 
+        for (index, result) in results.iter_mut().enumerate() {
+            result.relevance_score = rank_score(index + 1);
+        }
+
         Ok(results)
     }
 
-    pub async fn search_google(&self, query: &str) -> Result<Vec<SearchResult>, String> {
-        let api_key = std::env::var("GOOGLE_API_KEY").map_err(|_| "Google API key not set")?;
-        let cx = std::env::var("GOOGLE_CX").map_err(|_| "Google Custom Search Engine ID not set")?;
+    pub async fn search_google(&self, query: &str) -> Result<Vec<SearchResult>, ThetaError> {
+        let api_key = std::env::var("GOOGLE_API_KEY").map_err(|_| ThetaError::NotConfigured("Google API key not set".to_string()))?;
+        let cx = std::env::var("GOOGLE_CX").map_err(|_| ThetaError::NotConfigured("Google Custom Search Engine ID not set".to_string()))?;
         
         let url = format!(
             "https://www.googleapis.com/customsearch/v1?key={}&cx={}&q={}",
@@ -77,9 +83,9 @@ impl SearchEngine {
             .get(&url)
             .send()
             .await
-            .map_err(|e| format!("Search request failed: {}", e))?;
+            .map_err(|e| ThetaError::search_request("Search request failed", e))?;
 
-        let json: Value = response.json().await.map_err(|e| format!("Failed to parse response: {}", e))?;
+        let json: Value = response.json().await.map_err(|e| ThetaError::search_request("Failed to parse response", e))?;
         
         let mut results = Vec::new();
         
@@ -94,7 +100,7 @@ impl SearchEngine {
                         title: title.to_string(),
                         url: link.to_string(),
                         snippet: snippet.to_string(),
-                        relevance_score: 0.9,
+                        relevance_score: rank_score(results.len() + 1),
                     });
                 }
             }
@@ -103,8 +109,8 @@ impl SearchEngine {
         Ok(results)
     }
 
-    pub async fn search_bing(&self, query: &str) -> Result<Vec<SearchResult>, String> {
-        let api_key = std::env::var("BING_API_KEY").map_err(|_| "Bing API key not set")?;
+    pub async fn search_bing(&self, query: &str) -> Result<Vec<SearchResult>, ThetaError> {
+        let api_key = std::env::var("BING_API_KEY").map_err(|_| ThetaError::NotConfigured("Bing API key not set".to_string()))?;
         
         let url = format!("https://api.bing.microsoft.com/v7.0/search?q={}", encode(query));
         
@@ -113,9 +119,9 @@ impl SearchEngine {
             .header("Ocp-Apim-Subscription-Key", api_key)
             .send()
             .await
-            .map_err(|e| format!("Search request failed: {}", e))?;
+            .map_err(|e| ThetaError::search_request("Search request failed", e))?;
 
-        let json: Value = response.json().await.map_err(|e| format!("Failed to parse response: {}", e))?;
+        let json: Value = response.json().await.map_err(|e| ThetaError::search_request("Failed to parse response", e))?;
         
         let mut results = Vec::new();
         
@@ -130,7 +136,7 @@ impl SearchEngine {
                         title: name.to_string(),
                         url: url.to_string(),
                         snippet: snippet.to_string(),
-                        relevance_score: 0.85,
+                        relevance_score: rank_score(results.len() + 1),
                     });
                 }
             }
@@ -139,11 +145,11 @@ impl SearchEngine {
         Ok(results)
     }
 
-    pub async fn search(&self, query: &str, engine: &str) -> Result<Vec<SearchResult>, String> {
+    pub async fn search(&self, query: &str, engine: &str) -> Result<Vec<SearchResult>, ThetaError> {
         match engine {
             "google" => self.search_google(query).await,
             "bing" => self.search_bing(query).await,
-            "duckduckgo" | _ => self.search_duckduckgo(query).await,
+            _ => self.search_duckduckgo(query).await,
         }
     }
 } 
\ No newline at end of file
@@ -0,0 +1,115 @@
+use crate::ai::browser_tools::browser_tools;
+use crate::ai::prompts;
+use crate::ai::tools::{run_with_tools, ToolConfirmation, DEFAULT_MAX_STEPS};
+use crate::ai::ToolDefinition;
+use crate::commands::ai::require_text;
+use crate::error::ThetaError;
+use crate::integrations::N8nIntegration;
+use crate::models::{AIFeature, AgentRun, AgentStepEvent, ToolCall, ToolConfirmationRequest, ToolStep};
+use crate::state::AppState;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::sync::{oneshot, RwLock};
+use uuid::Uuid;
+
+const AGENT_STEP_EVENT: &str = "agent-step";
+const TOOL_CONFIRMATION_EVENT: &str = "tool-confirmation";
+
+/// How long a tool call waits for the user before counting as declined.
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Carries out a request with browser tools: opening tabs, searching the
+/// web, bookmarking pages, creating tasks and running n8n workflows. Each
+/// tool call is reported as an `agent-step` event. Tools with side effects
+/// first send a `tool-confirmation` event and only run once
+/// `confirm_tool_call` approves them.
+#[tauri::command]
+pub async fn run_browser_agent(
+    request_id: String,
+    request: String,
+    provider: Option<String>,
+    max_steps: Option<u32>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<AgentRun, ThetaError> {
+    require_text(&request, "Request")?;
+
+    // Tools lock the state themselves, so the run must not hold it
+    let (services, completion, n8n) = {
+        let app_state = state.read().await;
+        let completion = app_state.prompts
+            .get(prompts::BROWSER_AGENT)?
+            .request(&[("request", &request)], AIFeature::Agent)?;
+        let n8n = app_state.integrations
+            .get("n8n")
+            .map(|integration| N8nIntegration::new(integration.endpoint.clone(), integration.auth_token.clone()));
+        (app_state.ai_services.in_run(&Uuid::new_v4().to_string()), completion, n8n)
+    };
+
+    let tools = browser_tools(state.inner().clone(), n8n);
+    let confirmation = EventConfirmation {
+        request_id: request_id.clone(),
+        app_handle: app_handle.clone(),
+        state: state.inner().clone(),
+    };
+    let on_step = |step: &ToolStep| {
+        let _ = app_handle.emit(AGENT_STEP_EVENT, AgentStepEvent {
+            request_id: request_id.clone(),
+            step: step.clone(),
+        });
+    };
+
+    run_with_tools(
+        &services,
+        provider.as_deref(),
+        completion,
+        &tools,
+        &confirmation,
+        max_steps.map_or(DEFAULT_MAX_STEPS, |steps| steps as usize),
+        &on_step,
+    )
+    .await
+}
+
+/// Answers a `tool-confirmation` event. Returns false when the call is no
+/// longer waiting, e.g. because it timed out.
+#[tauri::command]
+pub async fn confirm_tool_call(
+    confirmation_id: String,
+    approved: bool,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<bool, ThetaError> {
+    let sender = state.write().await.tool_confirmations.remove(&confirmation_id);
+
+    Ok(sender.is_some_and(|sender| sender.send(approved).is_ok()))
+}
+
+/// Asks the frontend to confirm a tool call and waits for the answer.
+struct EventConfirmation {
+    request_id: String,
+    app_handle: tauri::AppHandle,
+    state: Arc<RwLock<AppState>>,
+}
+
+#[async_trait]
+impl ToolConfirmation for EventConfirmation {
+    async fn confirm(&self, call: &ToolCall, tool: &ToolDefinition) -> bool {
+        let confirmation_id = Uuid::new_v4().to_string();
+        let (sender, receiver) = oneshot::channel();
+        self.state.write().await.tool_confirmations.insert(confirmation_id.clone(), sender);
+
+        let _ = self.app_handle.emit(TOOL_CONFIRMATION_EVENT, ToolConfirmationRequest {
+            request_id: self.request_id.clone(),
+            confirmation_id: confirmation_id.clone(),
+            tool: tool.name.clone(),
+            description: tool.description.clone(),
+            arguments: call.arguments.clone(),
+        });
+
+        let approved = matches!(tokio::time::timeout(CONFIRMATION_TIMEOUT, receiver).await, Ok(Ok(true)));
+        self.state.write().await.tool_confirmations.remove(&confirmation_id);
+        approved
+    }
+}
@@ -1,62 +1,143 @@
-use crate::models::{AIRequest, AIResponse, ContentAnalysis, ChatMessage, MessageRole};
-use crate::state::AppState;
+use crate::ai::cache::{content_hash, ResponseCache};
+use crate::ai::{analysis, prompts, summarize, usage, AIServiceManager, CompletionRequest, LlmProvider, StreamChunk};
+use crate::commands::analysis::save_analysis;
+use crate::error::ThetaError;
+use crate::models::{
+    AICacheStats, AIFeature, AIProviderInfo, AIUsageSummary, AIRequest, AIStreamEvent, ContentAnalysis,
+    SummaryProgressEvent, SummaryStage,
+};
+use crate::state::{AppState, AI_PROVIDER_PRIORITY, PRIVACY_MODE};
+use futures::StreamExt;
 use std::sync::Arc;
+use tauri::Emitter;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+const AI_STREAM_EVENT: &str = "ai-stream";
+const SUMMARY_PROGRESS_EVENT: &str = "summary-progress";
+
+/// Rejects blank input before it reaches a provider, which would otherwise
+/// answer it with an empty or made-up reply.
+pub(crate) fn require_text(value: &str, what: &str) -> Result<(), ThetaError> {
+    if value.trim().is_empty() {
+        return Err(ThetaError::InvalidInput(format!("{} is empty", what)));
+    }
+    Ok(())
+}
+
+/// Identifies the model a cached response would have come from.
+fn cache_model(service: &dyn LlmProvider) -> String {
+    format!("{}/{}", service.name(), service.default_model())
+}
+
+/// Like `cache_model`, but for the provider that actually answered, which
+/// differs from the default after a failover.
+fn answered_model(services: &AIServiceManager, provider: &str) -> Option<String> {
+    services.get_service(provider).ok().map(|service| cache_model(service.as_ref()))
+}
+
+/// Analyzes page content. When `url` is given the result is also saved to
+/// the page's analysis history.
 #[tauri::command]
 pub async fn analyze_content(
     content: String,
+    url: Option<String>,
+    title: Option<String>,
     state: tauri::State<'_, Arc<RwLock<AppState>>>,
-) -> Result<ContentAnalysis, String> {
-    let app_state = state.read().await;
+) -> Result<ContentAnalysis, ThetaError> {
+    require_text(&content, "Content")?;
+    // Retries and backoff can take a while, so don't hold the state lock meanwhile
+    let (services, template, cache, database) = {
+        let app_state = state.read().await;
+        let template = app_state.prompts.get(prompts::CONTENT_ANALYSIS)?.clone();
+        (app_state.ai_services.clone(), template, app_state.ai_cache.clone(), app_state.database.clone())
+    };
     
-    if let Some(service) = app_state.ai_services.get("openai") {
-        let request = AIRequest {
-            content: format!("Analyze this content and provide a summary, keywords, sentiment (-1 to 1), topics, reading time (minutes), and complexity score (0-1): {}", content),
-            context: Some("Content analysis".to_string()),
-            model: Some(service.model.clone()),
-            temperature: Some(0.3),
-            max_tokens: Some(1000),
-        };
-        
-        match call_ai_service(&service, &request, &app_state.http_client).await {
-            Ok(response) => {
-                let analysis = parse_content_analysis(&response.content);
-                Ok(analysis)
+    let result = match services.default_service() {
+        Ok(service) => {
+            let key = |model: &str| ResponseCache::key(AIFeature::Analysis, &content_hash(&content), model, &template.cache_version());
+            match cache.get::<ContentAnalysis>(&key(&cache_model(service.as_ref()))).await {
+                Some(cached) => cached,
+                None => {
+                    let result = analysis::analyze(&services, &template, &content).await?;
+                    let model = result.provider.as_deref().and_then(|provider| answered_model(&services, provider));
+                    if let Some(model) = model {
+                        cache.put(&key(&model), &result).await;
+                    }
+                    result
+                }
             }
-            Err(e) => Err(format!("AI service error: {}", e)),
         }
-    } else {
-        Err("No AI service available".to_string())
+        Err(e) => {
+            log::warn!("{}; falling back to heuristic content analysis", e);
+            analysis::heuristic_analysis(&content)
+        }
+    };
+    
+    if let (Some(url), Some(pool)) = (&url, &database) {
+        // The analysis is still useful to the caller if it could not be saved
+        if let Err(e) = save_analysis(url, title.as_deref(), &result, pool).await {
+            log::warn!("Failed to save content analysis for {}: {}", url, e);
+        }
     }
+    
+    Ok(result)
 }
 
+/// Summarizes content of any length. Long content is summarized in chunks
+/// and, when `request_id` is given, progress is reported as
+/// `summary-progress` events tagged with it.
 #[tauri::command]
 pub async fn generate_summary(
     content: String,
     max_length: Option<u32>,
+    request_id: Option<String>,
+    app_handle: tauri::AppHandle,
     state: tauri::State<'_, Arc<RwLock<AppState>>>,
-) -> Result<String, String> {
-    let app_state = state.read().await;
+) -> Result<String, ThetaError> {
+    require_text(&content, "Content")?;
+    let max_len = max_length.unwrap_or(150);
+    if max_len == 0 {
+        return Err(ThetaError::InvalidInput("Summary length must be at least one word".to_string()));
+    }
     
-    if let Some(service) = app_state.ai_services.get("openai") {
-        let max_len = max_length.unwrap_or(150);
-        let request = AIRequest {
-            content: format!("Summarize this content in {} words or less: {}", max_len, content),
-            context: Some("Summary generation".to_string()),
-            model: Some(service.model.clone()),
-            temperature: Some(0.3),
-            max_tokens: Some(max_len * 2),
-        };
-        
-        match call_ai_service(&service, &request, &app_state.http_client).await {
-            Ok(response) => Ok(response.content),
-            Err(e) => Err(format!("AI service error: {}", e)),
+    // A long page takes many requests, so don't hold the state lock meanwhile
+    let (services, prompts, cache) = {
+        let app_state = state.read().await;
+        (app_state.ai_services.in_run(&Uuid::new_v4().to_string()), app_state.prompts.clone(), app_state.ai_cache.clone())
+    };
+    let service = services.default_service()?;
+    
+    let prompt_version = [prompts::SUMMARY, prompts::SUMMARY_CHUNK, prompts::SUMMARY_REDUCE]
+        .iter()
+        .map(|name| prompts.get(name).map(|template| template.cache_version()))
+        .collect::<Result<Vec<_>, _>>()?
+        .join("+");
+    let key = |model: &str| ResponseCache::key(AIFeature::Summary, &content_hash(&content), model, &format!("{}:{}", prompt_version, max_len));
+    if let Some(cached) = cache.get::<String>(&key(&cache_model(service.as_ref()))).await {
+        return Ok(cached);
+    }
+    
+    let progress = |stage: SummaryStage, completed: usize, total: usize| {
+        if let Some(request_id) = &request_id {
+            let _ = app_handle.emit(SUMMARY_PROGRESS_EVENT, SummaryProgressEvent {
+                request_id: request_id.clone(),
+                stage,
+                completed,
+                total,
+            });
+        }
+    };
+    
+    let summary = summarize::summarize(&services, &prompts, &content, max_len, &progress).await?;
+    // A summary pieced together from several providers' answers is not any one model's
+    let mut providers = summary.providers.iter();
+    if let (Some(provider), None) = (providers.next(), providers.next()) {
+        if let Some(model) = answered_model(&services, provider) {
+            cache.put(&key(&model), &summary.text).await;
         }
-    } else {
-        Err("No AI service available".to_string())
     }
+    Ok(summary.text)
 }
 
 #[tauri::command]
@@ -65,194 +146,223 @@ pub async fn chat_with_ai(
     context: Option<String>,
     model: Option<String>,
     state: tauri::State<'_, Arc<RwLock<AppState>>>,
-) -> Result<String, String> {
-    let app_state = state.read().await;
+) -> Result<String, ThetaError> {
+    require_text(&message, "Message")?;
+    let services = state.read().await.ai_services.clone();
     
-    let service_name = model.as_deref().unwrap_or("openai");
+    let request = AIRequest {
+        content: message,
+        context,
+        model: None,
+        temperature: Some(0.7),
+        max_tokens: Some(2000),
+    };
     
-    if let Some(service) = app_state.ai_services.get(service_name) {
-        let request = AIRequest {
-            content: message,
-            context,
-            model: Some(service.model.clone()),
-            temperature: Some(0.7),
-            max_tokens: Some(2000),
-        };
-        
-        match call_ai_service(&service, &request, &app_state.http_client).await {
-            Ok(response) => Ok(response.content),
-            Err(e) => Err(format!("AI service error: {}", e)),
-        }
-    } else {
-        Err(format!("AI service '{}' not available", service_name))
-    }
+    let response = services.complete_with_failover(model.as_deref(), &CompletionRequest::from(&request)).await?;
+    Ok(response.content)
 }
 
-async fn call_ai_service(
-    service: &crate::state::AIService,
-    request: &AIRequest,
-    client: &reqwest::Client,
-) -> Result<AIResponse, Box<dyn std::error::Error>> {
-    let start_time = std::time::Instant::now();
-    
-    match service.service_type.as_str() {
-        "openai" => call_openai_service(service, request, client).await,
-        "anthropic" => call_anthropic_service(service, request, client).await,
-        _ => Err("Unsupported AI service".into()),
-    }
-}
-
-async fn call_openai_service(
-    service: &crate::state::AIService,
-    request: &AIRequest,
-    client: &reqwest::Client,
-) -> Result<AIResponse, Box<dyn std::error::Error>> {
-    let start_time = std::time::Instant::now();
-    
-    let payload = serde_json::json!({
-        "model": request.model.as_ref().unwrap_or(&service.model),
-        "messages": [
-            {
-                "role": "user",
-                "content": request.content
-            }
-        ],
-        "temperature": request.temperature.unwrap_or(0.7),
-        "max_tokens": request.max_tokens.unwrap_or(1000)
-    });
-    
-    let response = client
-        .post(&format!("{}/chat/completions", service.endpoint))
-        .header("Authorization", format!("Bearer {}", service.api_key.as_ref().unwrap()))
-        .header("Content-Type", "application/json")
-        .json(&payload)
-        .send()
-        .await?;
-    
-    let response_json: serde_json::Value = response.json().await?;
-    
-    let content = response_json["choices"][0]["message"]["content"]
-        .as_str()
-        .unwrap_or("")
-        .to_string();
+/// Streams a chat completion to the frontend as `ai-stream` events tagged with
+/// `request_id`. Returns as soon as the stream is started.
+#[tauri::command]
+pub async fn stream_chat_with_ai(
+    request_id: String,
+    message: String,
+    context: Option<String>,
+    model: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<(), ThetaError> {
+    require_text(&message, "Message")?;
     
-    let tokens_used = response_json["usage"]["total_tokens"]
-        .as_u64()
-        .unwrap_or(0) as u32;
+    // Providers are shared, so the task gets its own copy of the routing
+    // table and cancelling never waits on the state lock during retries
+    let services = state.read().await.ai_services.clone();
+    services.resolve(model.as_deref())?;
     
-    let processing_time = start_time.elapsed().as_millis() as u64;
+    let request = AIRequest {
+        content: message,
+        context,
+        model: None,
+        temperature: Some(0.7),
+        max_tokens: Some(2000),
+    };
+    let completion = CompletionRequest::from(&request);
     
-    Ok(AIResponse {
-        content,
-        model: service.model.clone(),
-        tokens_used,
-        processing_time,
-        confidence: None,
-    })
-}
-
-async fn call_anthropic_service(
-    service: &crate::state::AIService,
-    request: &AIRequest,
-    client: &reqwest::Client,
-) -> Result<AIResponse, Box<dyn std::error::Error>> {
-    let start_time = std::time::Instant::now();
+    // Hold the write lock until the handle is registered so a fast stream
+    // cannot finish and deregister itself before it was inserted
+    let mut app_state = state.write().await;
+    if app_state.ai_streams.contains_key(&request_id) {
+        return Err(ThetaError::InvalidInput(format!("Stream '{}' is already running", request_id)));
+    }
     
-    let payload = serde_json::json!({
-        "model": request.model.as_ref().unwrap_or(&service.model),
-        "max_tokens": request.max_tokens.unwrap_or(1000),
-        "messages": [
-            {
-                "role": "user",
-                "content": request.content
+    let shared_state = state.inner().clone();
+    let task_request_id = request_id.clone();
+    let task = tokio::spawn(async move {
+        let request_id = task_request_id;
+        let event = match services.stream_with_failover(model.as_deref(), &completion).await {
+            Ok(mut stream) => {
+                let mut last_event = None;
+                while let Some(chunk) = stream.next().await {
+                    match chunk {
+                        Ok(StreamChunk::Delta(delta)) => {
+                            let _ = app_handle.emit(AI_STREAM_EVENT, AIStreamEvent::Chunk {
+                                request_id: request_id.clone(),
+                                delta,
+                            });
+                        }
+                        Ok(StreamChunk::Done(response)) => {
+                            last_event = Some(AIStreamEvent::Done { request_id: request_id.clone(), response });
+                            break;
+                        }
+                        Err(e) => {
+                            last_event = Some(AIStreamEvent::Error {
+                                request_id: request_id.clone(),
+                                error: ThetaError::from(e).into(),
+                            });
+                            break;
+                        }
+                    }
+                }
+                last_event.unwrap_or_else(|| AIStreamEvent::Error {
+                    request_id: request_id.clone(),
+                    error: ThetaError::Ai {
+                        message: "Stream ended without a response".to_string(),
+                        retryable: true,
+                    }
+                    .into(),
+                })
             }
-        ]
+            Err(e) => AIStreamEvent::Error { request_id: request_id.clone(), error: e.into() },
+        };
+        
+        // Deregister before emitting so a cancel that raced the end of the
+        // stream reports either `Cancelled` or this event, never both
+        if shared_state.write().await.ai_streams.remove(&request_id).is_some() {
+            let _ = app_handle.emit(AI_STREAM_EVENT, event);
+        }
     });
     
-    let response = client
-        .post(&format!("{}/messages", service.endpoint))
-        .header("x-api-key", service.api_key.as_ref().unwrap())
-        .header("anthropic-version", "2023-06-01")
-        .header("Content-Type", "application/json")
-        .json(&payload)
-        .send()
-        .await?;
-    
-    let response_json: serde_json::Value = response.json().await?;
-    
-    let content = response_json["content"][0]["text"]
-        .as_str()
-        .unwrap_or("")
-        .to_string();
-    
-    let tokens_used = response_json["usage"]["output_tokens"]
-        .as_u64()
-        .unwrap_or(0) as u32;
-    
-    let processing_time = start_time.elapsed().as_millis() as u64;
-    
-    Ok(AIResponse {
-        content,
-        model: service.model.clone(),
-        tokens_used,
-        processing_time,
-        confidence: None,
-    })
+    app_state.ai_streams.insert(request_id, task.abort_handle());
+    Ok(())
 }
 
-fn parse_content_analysis(content: &str) -> ContentAnalysis {
-    ContentAnalysis {
-        summary: extract_field(content, "summary").unwrap_or_else(|| content.chars().take(200).collect()),
-        keywords: extract_keywords(content),
-        sentiment: extract_sentiment(content),
-        topics: extract_topics(content),
-        reading_time: estimate_reading_time(content),
-        complexity_score: calculate_complexity_score(content),
+#[tauri::command]
+pub async fn cancel_ai_stream(
+    request_id: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<bool, ThetaError> {
+    let mut app_state = state.write().await;
+    
+    if let Some(handle) = app_state.ai_streams.remove(&request_id) {
+        handle.abort();
+        let _ = app_handle.emit(AI_STREAM_EVENT, AIStreamEvent::Cancelled { request_id });
+        Ok(true)
+    } else {
+        Ok(false)
     }
 }
 
-fn extract_field(content: &str, field: &str) -> Option<String> {
-    content.lines()
-        .find(|line| line.to_lowercase().contains(field))
-        .map(|line| line.split(':').nth(1).unwrap_or("").trim().to_string())
+#[tauri::command]
+pub async fn list_ai_providers(
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<AIProviderInfo>, ThetaError> {
+    let app_state = state.read().await;
+    let providers = &app_state.ai_services.providers;
+    
+    Ok(providers
+        .list_providers()
+        .into_iter()
+        .filter_map(|name| providers.get_provider(name))
+        .map(|provider| AIProviderInfo {
+            name: provider.name().to_string(),
+            models: provider.config().models.clone(),
+            is_local: provider.is_local(),
+        })
+        .collect())
 }
 
-fn extract_keywords(content: &str) -> Vec<String> {
-    content.split_whitespace()
-        .filter(|word| word.len() > 4)
-        .take(10)
-        .map(|word| word.to_lowercase())
-        .collect()
+/// Asks the provider itself which models it serves, e.g. what is installed in Ollama.
+#[tauri::command]
+pub async fn list_ai_models(
+    provider: String,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<String>, ThetaError> {
+    let service = state.read().await.ai_services.get_service(&provider)?;
+    
+    Ok(service.list_models().await?)
 }
 
-fn extract_sentiment(content: &str) -> f32 {
-    let positive_words = ["good", "great", "excellent", "amazing", "wonderful"];
-    let negative_words = ["bad", "terrible", "awful", "horrible", "disappointing"];
+#[tauri::command]
+pub async fn set_privacy_mode(
+    enabled: bool,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<bool, ThetaError> {
+    let mut app_state = state.write().await;
     
-    let words: Vec<&str> = content.split_whitespace().collect();
-    let positive_count = words.iter().filter(|word| positive_words.contains(&word.to_lowercase().as_str())).count();
-    let negative_count = words.iter().filter(|word| negative_words.contains(&word.to_lowercase().as_str())).count();
+    app_state.set_preference(PRIVACY_MODE, enabled.to_string()).await?;
+    app_state.ai_services.privacy_mode = enabled;
     
-    if positive_count + negative_count == 0 {
-        0.0
-    } else {
-        (positive_count as f32 - negative_count as f32) / (positive_count + negative_count) as f32
+    Ok(enabled)
+}
+
+/// Sets the order providers are tried in. Providers left out are tried after
+/// the listed ones.
+#[tauri::command]
+pub async fn set_ai_provider_priority(
+    providers: Vec<String>,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<String>, ThetaError> {
+    let mut app_state = state.write().await;
+    
+    if let Some(unknown) = providers.iter().find(|name| app_state.ai_services.providers.get_provider(name).is_none()) {
+        return Err(ThetaError::AiUnavailable(format!("AI service '{}' not available", unknown)));
     }
+    
+    app_state.set_preference(AI_PROVIDER_PRIORITY, providers.join(",")).await?;
+    app_state.ai_services.priority = providers;
+    
+    Ok(app_state.ai_services
+        .ordered_services()
+        .iter()
+        .map(|provider| provider.name().to_string())
+        .collect())
 }
 
-fn extract_topics(content: &str) -> Vec<String> {
-    vec!["Technology".to_string(), "AI".to_string(), "Browser".to_string()]
+/// Token and cost totals per day, provider and feature from the usage ledger.
+#[tauri::command]
+pub async fn get_ai_usage(
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    until: Option<chrono::DateTime<chrono::Utc>>,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<AIUsageSummary>, ThetaError> {
+    let app_state = state.read().await;
+    
+    if let Some(pool) = &app_state.database {
+        Ok(usage::usage_summary(pool, since, until).await?)
+    } else {
+        Err(ThetaError::DatabaseUnavailable)
+    }
 }
 
-fn estimate_reading_time(content: &str) -> u32 {
-    let words = content.split_whitespace().count();
-    ((words as f32 / 200.0).ceil() as u32).max(1)
+#[tauri::command]
+pub async fn get_ai_cache_stats(
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<AICacheStats, ThetaError> {
+    let cache = state.read().await.ai_cache.clone();
+    
+    Ok(cache.stats().await)
 }
 
-fn calculate_complexity_score(content: &str) -> f32 {
-    let avg_word_length = content.split_whitespace()
-        .map(|word| word.len())
-        .sum::<usize>() as f32 / content.split_whitespace().count() as f32;
+/// Drops cached analyses and summaries, only those for `feature` if given.
+/// Returns how many entries were removed.
+#[tauri::command]
+pub async fn purge_ai_cache(
+    feature: Option<AIFeature>,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<u64, ThetaError> {
+    let cache = state.read().await.ai_cache.clone();
     
-    (avg_word_length / 10.0).min(1.0)
-} 
\ No newline at end of file
+    Ok(cache.purge(feature).await?)
+}
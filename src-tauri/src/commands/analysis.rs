@@ -0,0 +1,152 @@
+use crate::ai::analysis;
+use crate::database::ContentAnalysis as AnalysisRow;
+use crate::error::ThetaError;
+use crate::models::{AnalysisDiff, ContentAnalysis, StoredAnalysis};
+use crate::state::AppState;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+const HISTORY_LIMIT: u32 = 20;
+
+#[tauri::command]
+pub async fn get_latest_analysis(
+    url: String,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Option<StoredAnalysis>, ThetaError> {
+    let app_state = state.read().await;
+
+    if let Some(pool) = &app_state.database {
+        Ok(fetch_history(&url, Some(1), pool).await?.into_iter().next())
+    } else {
+        Err(ThetaError::DatabaseUnavailable)
+    }
+}
+
+/// Saved analyses of a page, newest first.
+#[tauri::command]
+pub async fn get_analysis_history(
+    url: String,
+    limit: Option<u32>,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<StoredAnalysis>, ThetaError> {
+    let app_state = state.read().await;
+
+    if let Some(pool) = &app_state.database {
+        fetch_history(&url, Some(limit.unwrap_or(HISTORY_LIMIT)), pool).await
+    } else {
+        Err(ThetaError::DatabaseUnavailable)
+    }
+}
+
+/// Compares two saved analyses of a page. Without ids, compares the latest
+/// analysis with the one before it; with only `current_id`, compares that
+/// analysis with the one before it. Returns `None` when there is nothing
+/// to compare against.
+#[tauri::command]
+pub async fn diff_page_analyses(
+    url: String,
+    previous_id: Option<Uuid>,
+    current_id: Option<Uuid>,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Option<AnalysisDiff>, ThetaError> {
+    let app_state = state.read().await;
+    let pool = app_state.database.as_ref().ok_or(ThetaError::DatabaseUnavailable)?;
+
+    let mut history = fetch_history(&url, None, pool).await?;
+
+    let current_index = match current_id {
+        Some(id) => position(&history, id)?,
+        None => 0,
+    };
+    let previous_index = match previous_id {
+        Some(id) => position(&history, id)?,
+        None => current_index + 1,
+    };
+    if previous_index >= history.len() || current_index >= history.len() {
+        return Ok(None);
+    }
+
+    let previous = history[previous_index].clone();
+    let current = history.swap_remove(current_index);
+    Ok(Some(analysis::diff_analyses(previous, current)))
+}
+
+fn position(history: &[StoredAnalysis], id: Uuid) -> Result<usize, ThetaError> {
+    history
+        .iter()
+        .position(|stored| stored.id == id)
+        .ok_or_else(|| ThetaError::NotFound(format!("Analysis {} not found for this page", id)))
+}
+
+/// Saves an analysis for `url`. Analysing the page again while its content
+/// is unchanged replaces the latest row; any change, even back to earlier
+/// content, adds a row so the history keeps every version in order.
+pub(crate) async fn save_analysis(
+    url: &str,
+    title: Option<&str>,
+    analysis: &ContentAnalysis,
+    pool: &sqlx::PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "WITH latest AS (
+             SELECT id, content_hash FROM content_analysis
+             WHERE url = $1
+             ORDER BY created_at DESC
+             LIMIT 1
+         ), updated AS (
+             UPDATE content_analysis
+             SET title = $2, model = $4, analysis_data = $5
+             FROM latest
+             WHERE content_analysis.id = latest.id AND latest.content_hash IS NOT DISTINCT FROM $3
+             RETURNING content_analysis.id
+         )
+         INSERT INTO content_analysis (url, title, content_hash, model, analysis_data, created_at)
+         SELECT $1, $2, $3, $4, $5, NOW()
+         WHERE NOT EXISTS (SELECT 1 FROM updated)",
+    )
+    .bind(url)
+    .bind(title)
+    .bind(&analysis.content_hash)
+    .bind(&analysis.model)
+    .bind(serde_json::to_value(analysis).unwrap_or_default())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+fn stored_from_row(row: AnalysisRow) -> Result<StoredAnalysis, ThetaError> {
+    let analysis: ContentAnalysis = serde_json::from_value(row.analysis_data)
+        .map_err(|e| sqlx::Error::Decode(format!("Corrupt content analysis: {}", e).into()))?;
+
+    Ok(StoredAnalysis {
+        id: row.id,
+        url: row.url,
+        title: row.title,
+        content_hash: row.content_hash,
+        model: row.model,
+        analysis,
+        created_at: row.created_at,
+    })
+}
+
+async fn fetch_history(
+    url: &str,
+    limit: Option<u32>,
+    pool: &sqlx::PgPool,
+) -> Result<Vec<StoredAnalysis>, ThetaError> {
+    let rows = sqlx::query_as::<_, AnalysisRow>(
+        "SELECT id, url, title, content_hash, model, analysis_data, created_at
+         FROM content_analysis
+         WHERE url = $1
+         ORDER BY created_at DESC
+         LIMIT $2",
+    )
+    .bind(url)
+    .bind(limit.map(i64::from))
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter().map(stored_from_row).collect()
+}
@@ -1,4 +1,9 @@
-use crate::models::{SearchRequest, SearchResult, Bookmark, WebPageContent};
+use crate::browser::ContentExtractor;
+use crate::commands::search::{index_in_background, index_url_in_background};
+use crate::error::ThetaError;
+use crate::models::{SearchRequest, SearchResult, Bookmark, ContentFormat, DocumentSource, WebPageContent};
+use crate::search::hybrid::rank_score;
+use crate::search::index::IndexDocument;
 use crate::state::AppState;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -8,7 +13,7 @@ use uuid::Uuid;
 pub async fn navigate_to_url(
     url: String,
     state: tauri::State<'_, Arc<RwLock<AppState>>>,
-) -> Result<String, String> {
+) -> Result<String, ThetaError> {
     let mut app_state = state.write().await;
     
     let tab_id = app_state.create_tab(url.clone(), "Loading...".to_string());
@@ -21,7 +26,7 @@ pub async fn search_web(
     query: String,
     search_engine: Option<String>,
     state: tauri::State<'_, Arc<RwLock<AppState>>>,
-) -> Result<Vec<SearchResult>, String> {
+) -> Result<Vec<SearchResult>, ThetaError> {
     let app_state = state.read().await;
     
     let engine = search_engine.unwrap_or_else(|| "duckduckgo".to_string());
@@ -30,7 +35,7 @@ pub async fn search_web(
         "duckduckgo" => search_duckduckgo(&query, &app_state.http_client).await,
         "google" => search_google(&query, &app_state.http_client).await,
         "bing" => search_bing(&query, &app_state.http_client).await,
-        _ => Err("Unsupported search engine".to_string()),
+        _ => Err(ThetaError::NotConfigured(format!("Unsupported search engine '{}'", engine))),
     }
 }
 
@@ -38,49 +43,70 @@ pub async fn search_web(
 pub async fn bookmark_page(
     url: String,
     title: String,
+    description: Option<String>,
     tags: Vec<String>,
     folder: Option<String>,
     state: tauri::State<'_, Arc<RwLock<AppState>>>,
-) -> Result<Bookmark, String> {
+) -> Result<Bookmark, ThetaError> {
     let app_state = state.read().await;
     
     let bookmark = Bookmark {
         id: Uuid::new_v4(),
         title,
         url,
+        description,
         tags,
         created_at: chrono::Utc::now(),
         folder,
         ai_summary: None,
     };
     
+    add_bookmark(&app_state, bookmark).await
+}
+
+/// Saves a bookmark and indexes its page for semantic search.
+pub(crate) async fn add_bookmark(app_state: &AppState, bookmark: Bookmark) -> Result<Bookmark, ThetaError> {
     if let Some(pool) = &app_state.database {
-        match save_bookmark_to_db(&bookmark, pool).await {
-            Ok(_) => Ok(bookmark),
-            Err(e) => Err(format!("Database error: {}", e)),
-        }
+        save_bookmark_to_db(&bookmark, pool).await?;
+        index_url_in_background(app_state, bookmark.url.clone(), DocumentSource::Bookmark);
+        Ok(bookmark)
     } else {
-        Err("Database not initialized".to_string())
+        Err(ThetaError::DatabaseUnavailable)
     }
 }
 
+/// Fetches a page and extracts its article as plain text, and as Markdown
+/// too if `format` asks for it. Only the plain text is cached and indexed.
 #[tauri::command]
 pub async fn get_page_content(
     url: String,
+    format: Option<ContentFormat>,
     state: tauri::State<'_, Arc<RwLock<AppState>>>,
-) -> Result<WebPageContent, String> {
+) -> Result<WebPageContent, ThetaError> {
     let app_state = state.read().await;
     
-    match extract_page_content(&url, &app_state.http_client).await {
-        Ok(content) => Ok(content),
-        Err(e) => Err(format!("Failed to extract page content: {}", e)),
+    let page = extract_page_content(&url, &app_state.http_client, format.unwrap_or_default()).await?;
+    // Keep the text for full-text search of history; the page is still
+    // returned if saving fails
+    if let Some(pool) = &app_state.database {
+        if let Err(e) = save_page_content(&page, pool).await {
+            log::warn!("Failed to save the content of {}: {}", page.url, e);
+        }
     }
+    index_in_background(&app_state, IndexDocument {
+        url: page.url.clone(),
+        title: page.title.clone(),
+        source: DocumentSource::Page,
+        content: page.content.clone(),
+    });
+    
+    Ok(page)
 }
 
 async fn search_duckduckgo(
     query: &str,
     client: &reqwest::Client,
-) -> Result<Vec<SearchResult>, String> {
+) -> Result<Vec<SearchResult>, ThetaError> {
     let url = format!("https://api.duckduckgo.com/?q={}&format=json&no_html=1", 
                      urlencoding::encode(query));
     
@@ -101,7 +127,7 @@ async fn search_duckduckgo(
                                     title: title.to_string(),
                                     url: url.to_string(),
                                     snippet: text.to_string(),
-                                    relevance_score: 0.8,
+                                    relevance_score: rank_score(results.len() + 1),
                                     metadata: serde_json::Value::Object(serde_json::Map::new()),
                                 });
                             }
@@ -110,19 +136,19 @@ async fn search_duckduckgo(
                     
                     Ok(results)
                 }
-                Err(e) => Err(format!("Failed to parse search results: {}", e)),
+                Err(e) => Err(ThetaError::search_request("Failed to parse search results", e)),
             }
         }
-        Err(e) => Err(format!("Search request failed: {}", e)),
+        Err(e) => Err(ThetaError::search_request("Search request failed", e)),
     }
 }
 
 async fn search_google(
     query: &str,
     client: &reqwest::Client,
-) -> Result<Vec<SearchResult>, String> {
-    let api_key = std::env::var("GOOGLE_API_KEY").map_err(|_| "Google API key not set")?;
-    let cx = std::env::var("GOOGLE_CX").map_err(|_| "Google Custom Search Engine ID not set")?;
+) -> Result<Vec<SearchResult>, ThetaError> {
+    let api_key = std::env::var("GOOGLE_API_KEY").map_err(|_| ThetaError::NotConfigured("Google API key not set".to_string()))?;
+    let cx = std::env::var("GOOGLE_CX").map_err(|_| ThetaError::NotConfigured("Google Custom Search Engine ID not set".to_string()))?;
     
     let url = format!(
         "https://www.googleapis.com/customsearch/v1?key={}&cx={}&q={}",
@@ -146,7 +172,7 @@ async fn search_google(
                                     title: title.to_string(),
                                     url: link.to_string(),
                                     snippet: snippet.to_string(),
-                                    relevance_score: 0.9,
+                                    relevance_score: rank_score(results.len() + 1),
                                     metadata: item.clone(),
                                 });
                             }
@@ -155,18 +181,18 @@ async fn search_google(
                     
                     Ok(results)
                 }
-                Err(e) => Err(format!("Failed to parse search results: {}", e)),
+                Err(e) => Err(ThetaError::search_request("Failed to parse search results", e)),
             }
         }
-        Err(e) => Err(format!("Search request failed: {}", e)),
+        Err(e) => Err(ThetaError::search_request("Search request failed", e)),
     }
 }
 
 async fn search_bing(
     query: &str,
     client: &reqwest::Client,
-) -> Result<Vec<SearchResult>, String> {
-    let api_key = std::env::var("BING_API_KEY").map_err(|_| "Bing API key not set")?;
+) -> Result<Vec<SearchResult>, ThetaError> {
+    let api_key = std::env::var("BING_API_KEY").map_err(|_| ThetaError::NotConfigured("Bing API key not set".to_string()))?;
     
     let url = format!("https://api.bing.microsoft.com/v7.0/search?q={}", 
                      urlencoding::encode(query));
@@ -193,7 +219,7 @@ async fn search_bing(
                                     title: name.to_string(),
                                     url: url.to_string(),
                                     snippet: snippet.to_string(),
-                                    relevance_score: 0.85,
+                                    relevance_score: rank_score(results.len() + 1),
                                     metadata: page.clone(),
                                 });
                             }
@@ -202,95 +228,58 @@ async fn search_bing(
                     
                     Ok(results)
                 }
-                Err(e) => Err(format!("Failed to parse search results: {}", e)),
+                Err(e) => Err(ThetaError::search_request("Failed to parse search results", e)),
             }
         }
-        Err(e) => Err(format!("Search request failed: {}", e)),
+        Err(e) => Err(ThetaError::search_request("Search request failed", e)),
     }
 }
 
 async fn extract_page_content(
     url: &str,
     client: &reqwest::Client,
-) -> Result<WebPageContent, Box<dyn std::error::Error>> {
-    let response = client.get(url).send().await?;
-    let html = response.text().await?;
-    
-    let content = extract_main_content(&html);
-    let title = extract_title(&html);
-    let links = extract_links(&html);
-    let images = extract_images(&html);
+    format: ContentFormat,
+) -> Result<WebPageContent, ThetaError> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| ThetaError::extraction_request("Failed to fetch URL", e))?;
+    let page_url = response.url().to_string();
+    let html = response
+        .text()
+        .await
+        .map_err(|e| ThetaError::extraction_request("Failed to read response", e))?;
     
+    let extracted = ContentExtractor::with_client(client.clone()).with_format(format).extract_from_html(&html, &page_url);
+
     Ok(WebPageContent {
         url: url.to_string(),
-        title,
-        content,
+        title: extracted.title,
+        content: extracted.content,
+        markdown: extracted.markdown,
         html,
-        links,
-        images,
-        metadata: serde_json::Value::Object(serde_json::Map::new()),
+        links: extracted.links,
+        images: extracted.images,
+        metadata: serde_json::to_value(&extracted.metadata).unwrap_or_default(),
+        byline: extracted.byline,
+        published_at: extracted.published_at,
+        lead_image: extracted.lead_image,
         extracted_at: chrono::Utc::now(),
     })
 }
 
-fn extract_main_content(html: &str) -> String {
-    use regex::Regex;
-    
-    let re = Regex::new(r"<[^>]*>").unwrap();
-    let text = re.replace_all(html, " ");
-    
-    text.split_whitespace()
-        .collect::<Vec<_>>()
-        .join(" ")
-        .chars()
-        .take(10000)
-        .collect()
-}
-
-fn extract_title(html: &str) -> String {
-    use regex::Regex;
-    
-    let re = Regex::new(r"<title[^>]*>([^<]+)</title>").unwrap();
-    
-    if let Some(captures) = re.captures(html) {
-        captures.get(1).map_or("".to_string(), |m| m.as_str().to_string())
-    } else {
-        "Untitled".to_string()
-    }
-}
-
-fn extract_links(html: &str) -> Vec<String> {
-    use regex::Regex;
-    
-    let re = Regex::new(r#"<a[^>]*href=["']([^"']+)["'][^>]*>"#).unwrap();
-    
-    re.captures_iter(html)
-        .filter_map(|cap| cap.get(1))
-        .map(|m| m.as_str().to_string())
-        .collect()
-}
-
-fn extract_images(html: &str) -> Vec<String> {
-    use regex::Regex;
-    
-    let re = Regex::new(r#"<img[^>]*src=["']([^"']+)["'][^>]*>"#).unwrap();
-    
-    re.captures_iter(html)
-        .filter_map(|cap| cap.get(1))
-        .map(|m| m.as_str().to_string())
-        .collect()
-}
-
 async fn save_bookmark_to_db(
     bookmark: &Bookmark,
     pool: &sqlx::PgPool,
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
-        "INSERT INTO bookmarks (id, title, url, tags, created_at, folder, ai_summary) 
-         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        "INSERT INTO bookmarks (id, title, url, description, tags, created_at, folder, ai_summary) 
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
         bookmark.id,
         bookmark.title,
         bookmark.url,
+        bookmark.description,
         &bookmark.tags,
         bookmark.created_at,
         bookmark.folder,
@@ -300,4 +289,35 @@ async fn save_bookmark_to_db(
     .await?;
     
     Ok(())
-} 
\ No newline at end of file
+}
+
+async fn save_page_content(
+    page: &WebPageContent,
+    pool: &sqlx::PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO page_content_cache (url, title, content, html, links, images, metadata, extracted_at, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8 + INTERVAL '24 hours')
+         ON CONFLICT (url) DO UPDATE
+         SET title = EXCLUDED.title,
+             content = EXCLUDED.content,
+             html = EXCLUDED.html,
+             links = EXCLUDED.links,
+             images = EXCLUDED.images,
+             metadata = EXCLUDED.metadata,
+             extracted_at = EXCLUDED.extracted_at,
+             expires_at = EXCLUDED.expires_at",
+    )
+    .bind(&page.url)
+    .bind(&page.title)
+    .bind(&page.content)
+    .bind(&page.html)
+    .bind(page.links.iter().map(|link| link.url.as_str()).collect::<Vec<_>>())
+    .bind(page.images.iter().map(|image| image.url.as_str()).collect::<Vec<_>>())
+    .bind(&page.metadata)
+    .bind(page.extracted_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,344 @@
+use crate::ai::{context, CompletionRequest};
+use crate::database::AIConversation;
+use crate::error::ThetaError;
+use crate::models::{AIChat, AIFeature, ChatMessage, ContextStrategy, ConversationSummary, MessageRole};
+use crate::state::AppState;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+const TITLE_LENGTH: usize = 50;
+const REPLY_TOKENS: u32 = 2000;
+
+#[tauri::command]
+pub async fn create_conversation(
+    title: Option<String>,
+    system_prompt: Option<String>,
+    provider: Option<String>,
+    context_strategy: Option<ContextStrategy>,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<AIChat, ThetaError> {
+    let app_state = state.read().await;
+
+    let service = app_state.ai_services.resolve(provider.as_deref())?;
+
+    let mut messages = Vec::new();
+    if let Some(prompt) = system_prompt.filter(|prompt| !prompt.trim().is_empty()) {
+        messages.push(ChatMessage::new(MessageRole::System, prompt));
+    }
+
+    let now = chrono::Utc::now();
+    let conversation = AIChat {
+        id: Uuid::new_v4(),
+        title,
+        context: system_context(&messages),
+        messages,
+        model: service.default_model().to_string(),
+        provider: service.name().to_string(),
+        context_strategy: context_strategy.unwrap_or_default(),
+        rolling_summary: None,
+        created_at: now,
+        updated_at: now,
+    };
+
+    if let Some(pool) = &app_state.database {
+        insert_conversation(&conversation, pool).await?;
+        Ok(conversation)
+    } else {
+        Err(ThetaError::DatabaseUnavailable)
+    }
+}
+
+#[tauri::command]
+pub async fn list_conversations(
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<ConversationSummary>, ThetaError> {
+    let app_state = state.read().await;
+
+    if let Some(pool) = &app_state.database {
+        Ok(fetch_conversation_summaries(pool).await?)
+    } else {
+        Err(ThetaError::DatabaseUnavailable)
+    }
+}
+
+#[tauri::command]
+pub async fn get_conversation(
+    conversation_id: Uuid,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Option<AIChat>, ThetaError> {
+    let app_state = state.read().await;
+
+    if let Some(pool) = &app_state.database {
+        load_conversation(conversation_id, pool).await
+    } else {
+        Err(ThetaError::DatabaseUnavailable)
+    }
+}
+
+/// Appends a message without asking the provider for a reply, e.g. to add
+/// system instructions mid-conversation.
+#[tauri::command]
+pub async fn append_conversation_message(
+    conversation_id: Uuid,
+    role: MessageRole,
+    content: String,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<AIChat, ThetaError> {
+    let app_state = state.read().await;
+    let pool = app_state.database.as_ref().ok_or(ThetaError::DatabaseUnavailable)?;
+
+    let mut conversation = load_conversation(conversation_id, pool)
+        .await?
+        .ok_or_else(|| ThetaError::NotFound(format!("Conversation '{}' not found", conversation_id)))?;
+
+    let message = ChatMessage::new(role, content);
+    conversation.messages.push(message.clone());
+    conversation.context = system_context(&conversation.messages);
+    conversation.updated_at = chrono::Utc::now();
+
+    append_messages(&conversation, &[message], pool).await?;
+
+    Ok(conversation)
+}
+
+/// Sends a user turn with the full history to the conversation's provider and
+/// stores both the question and the reply.
+#[tauri::command]
+pub async fn send_conversation_message(
+    conversation_id: Uuid,
+    message: String,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<ChatMessage, ThetaError> {
+    super::ai::require_text(&message, "Message")?;
+    let app_state = state.read().await;
+    let pool = app_state.database.as_ref().ok_or(ThetaError::DatabaseUnavailable)?;
+
+    let mut conversation = load_conversation(conversation_id, pool)
+        .await?
+        .ok_or_else(|| ThetaError::NotFound(format!("Conversation '{}' not found", conversation_id)))?;
+
+    if conversation.title.is_none() {
+        conversation.title = Some(message.chars().take(TITLE_LENGTH).collect());
+    }
+    let question = ChatMessage::new(MessageRole::User, message);
+    conversation.messages.push(question.clone());
+
+    let provider = app_state.ai_services.get_service(&conversation.provider)?;
+    let messages = context::build_context(&app_state.ai_services, provider.as_ref(), &mut conversation, REPLY_TOKENS).await?;
+
+    let request = CompletionRequest {
+        messages,
+        model: Some(conversation.model.clone()).filter(|model| !model.is_empty()),
+        temperature: Some(0.7),
+        max_tokens: Some(REPLY_TOKENS),
+        response_schema: None,
+        feature: AIFeature::Conversation,
+        tools: Vec::new(),
+    };
+
+    // The history was fitted to this provider's context window, so a
+    // fallback with a smaller one would reject it
+    let response = app_state.ai_services.complete(&conversation.provider, &request).await?;
+
+    let mut reply = ChatMessage::new(MessageRole::Assistant, response.content.clone());
+    reply.metadata = Some(serde_json::json!({
+        "model": response.model,
+        "provider": response.provider,
+        "input_tokens": response.input_tokens,
+        "output_tokens": response.output_tokens,
+        "tokens_used": response.tokens_used,
+        "cost_usd": response.cost_usd,
+        "processing_time": response.processing_time,
+    }));
+    conversation.messages.push(reply.clone());
+    conversation.updated_at = chrono::Utc::now();
+
+    append_messages(&conversation, &[question, reply.clone()], pool).await?;
+
+    Ok(reply)
+}
+
+#[tauri::command]
+pub async fn set_conversation_context_strategy(
+    conversation_id: Uuid,
+    strategy: ContextStrategy,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<bool, ThetaError> {
+    let app_state = state.read().await;
+
+    if let Some(pool) = &app_state.database {
+        let result = sqlx::query("UPDATE ai_conversations SET context_strategy = $2, updated_at = NOW() WHERE id = $1")
+            .bind(conversation_id)
+            .bind(strategy.as_str())
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    } else {
+        Err(ThetaError::DatabaseUnavailable)
+    }
+}
+
+#[tauri::command]
+pub async fn rename_conversation(
+    conversation_id: Uuid,
+    title: String,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<bool, ThetaError> {
+    let app_state = state.read().await;
+
+    if let Some(pool) = &app_state.database {
+        let result = sqlx::query("UPDATE ai_conversations SET title = $2, updated_at = NOW() WHERE id = $1")
+            .bind(conversation_id)
+            .bind(title)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    } else {
+        Err(ThetaError::DatabaseUnavailable)
+    }
+}
+
+#[tauri::command]
+pub async fn delete_conversation(
+    conversation_id: Uuid,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<bool, ThetaError> {
+    let app_state = state.read().await;
+
+    if let Some(pool) = &app_state.database {
+        let result = sqlx::query("DELETE FROM ai_conversations WHERE id = $1")
+            .bind(conversation_id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    } else {
+        Err(ThetaError::DatabaseUnavailable)
+    }
+}
+
+fn system_context(messages: &[ChatMessage]) -> Option<String> {
+    let system: Vec<&str> = messages
+        .iter()
+        .filter(|message| matches!(message.role, MessageRole::System))
+        .map(|message| message.content.as_str())
+        .collect();
+
+    if system.is_empty() {
+        None
+    } else {
+        Some(system.join("\n\n"))
+    }
+}
+
+fn conversation_from_row(row: AIConversation) -> Result<AIChat, ThetaError> {
+    let messages: Vec<ChatMessage> = serde_json::from_value(row.messages)
+        .map_err(|e| sqlx::Error::Decode(format!("Corrupt conversation history: {}", e).into()))?;
+
+    Ok(AIChat {
+        id: row.id,
+        title: row.title,
+        context: system_context(&messages),
+        messages,
+        model: row.model.unwrap_or_default(),
+        provider: row.provider.unwrap_or_default(),
+        context_strategy: row.context_strategy
+            .as_deref()
+            .map(ContextStrategy::parse)
+            .unwrap_or_default(),
+        rolling_summary: row.rolling_summary.and_then(|summary| serde_json::from_value(summary).ok()),
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    })
+}
+
+async fn load_conversation(
+    conversation_id: Uuid,
+    pool: &sqlx::PgPool,
+) -> Result<Option<AIChat>, ThetaError> {
+    let row = sqlx::query_as::<_, AIConversation>(
+        "SELECT id, title, messages, model, provider, context_strategy, rolling_summary, created_at, updated_at
+         FROM ai_conversations
+         WHERE id = $1",
+    )
+    .bind(conversation_id)
+    .fetch_optional(pool)
+    .await?;
+
+    row.map(conversation_from_row).transpose()
+}
+
+type SummaryRow = (Uuid, Option<String>, Option<String>, Option<String>, i32, chrono::DateTime<chrono::Utc>);
+
+async fn fetch_conversation_summaries(
+    pool: &sqlx::PgPool,
+) -> Result<Vec<ConversationSummary>, sqlx::Error> {
+    let rows: Vec<SummaryRow> = sqlx::query_as(
+        "SELECT id, title, model, provider, jsonb_array_length(messages), updated_at
+         FROM ai_conversations
+         ORDER BY updated_at DESC",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, title, model, provider, message_count, updated_at)| ConversationSummary {
+            id,
+            title,
+            model: model.unwrap_or_default(),
+            provider: provider.unwrap_or_default(),
+            message_count,
+            updated_at,
+        })
+        .collect())
+}
+
+async fn insert_conversation(
+    conversation: &AIChat,
+    pool: &sqlx::PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO ai_conversations (id, title, messages, model, provider, context_strategy, created_at, updated_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(conversation.id)
+    .bind(&conversation.title)
+    .bind(serde_json::to_value(&conversation.messages).unwrap_or_default())
+    .bind(&conversation.model)
+    .bind(&conversation.provider)
+    .bind(conversation.context_strategy.as_str())
+    .bind(conversation.created_at)
+    .bind(conversation.updated_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Appends `messages` to the stored history rather than overwriting it, so
+/// turns saved concurrently by another send are kept. The title is only set
+/// if the conversation has none yet.
+async fn append_messages(
+    conversation: &AIChat,
+    messages: &[ChatMessage],
+    pool: &sqlx::PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE ai_conversations
+         SET title = COALESCE(title, $2), messages = messages || $3, rolling_summary = COALESCE($4, rolling_summary), updated_at = $5
+         WHERE id = $1",
+    )
+    .bind(conversation.id)
+    .bind(&conversation.title)
+    .bind(serde_json::to_value(messages).unwrap_or_default())
+    .bind(conversation.rolling_summary.as_ref().and_then(|summary| serde_json::to_value(summary).ok()))
+    .bind(conversation.updated_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use crate::error::ThetaError;
 use crate::models::UserData;
 use crate::state::AppState;
 use std::sync::Arc;
@@ -10,7 +11,7 @@ pub async fn save_user_data(
     value: serde_json::Value,
     category: Option<String>,
     state: tauri::State<'_, Arc<RwLock<AppState>>>,
-) -> Result<UserData, String> {
+) -> Result<UserData, ThetaError> {
     let app_state = state.read().await;
     
     let user_data = UserData {
@@ -23,12 +24,10 @@ pub async fn save_user_data(
     };
     
     if let Some(pool) = &app_state.database {
-        match save_data_to_db(&user_data, pool).await {
-            Ok(_) => Ok(user_data),
-            Err(e) => Err(format!("Database error: {}", e)),
-        }
+        save_data_to_db(&user_data, pool).await?;
+        Ok(user_data)
     } else {
-        Err("Database not initialized".to_string())
+        Err(ThetaError::DatabaseUnavailable)
     }
 }
 
@@ -37,16 +36,13 @@ pub async fn get_user_data(
     key: String,
     category: Option<String>,
     state: tauri::State<'_, Arc<RwLock<AppState>>>,
-) -> Result<Option<UserData>, String> {
+) -> Result<Option<UserData>, ThetaError> {
     let app_state = state.read().await;
     
     if let Some(pool) = &app_state.database {
-        match get_data_from_db(&key, category.as_deref(), pool).await {
-            Ok(data) => Ok(data),
-            Err(e) => Err(format!("Database error: {}", e)),
-        }
+        Ok(get_data_from_db(&key, category.as_deref(), pool).await?)
     } else {
-        Err("Database not initialized".to_string())
+        Err(ThetaError::DatabaseUnavailable)
     }
 }
 
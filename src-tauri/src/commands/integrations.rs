@@ -1,3 +1,4 @@
+use crate::error::ThetaError;
 use crate::models::{N8nWorkflow, IntegrationTrigger};
 use crate::state::AppState;
 use std::sync::Arc;
@@ -8,16 +9,13 @@ pub async fn setup_n8n_workflow(
     workflow_name: String,
     description: Option<String>,
     state: tauri::State<'_, Arc<RwLock<AppState>>>,
-) -> Result<N8nWorkflow, String> {
+) -> Result<N8nWorkflow, ThetaError> {
     let app_state = state.read().await;
     
     if let Some(integration) = app_state.integrations.get("n8n") {
-        match create_n8n_workflow(&workflow_name, description, integration, &app_state.http_client).await {
-            Ok(workflow) => Ok(workflow),
-            Err(e) => Err(format!("Failed to create n8n workflow: {}", e)),
-        }
+        create_n8n_workflow(&workflow_name, description, integration, &app_state.http_client).await
     } else {
-        Err("n8n integration not configured".to_string())
+        Err(ThetaError::NotConfigured("n8n integration not configured".to_string()))
     }
 }
 
@@ -25,16 +23,13 @@ pub async fn setup_n8n_workflow(
 pub async fn trigger_integration(
     trigger: IntegrationTrigger,
     state: tauri::State<'_, Arc<RwLock<AppState>>>,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, ThetaError> {
     let app_state = state.read().await;
     
     if let Some(integration) = app_state.integrations.get("n8n") {
-        match trigger_n8n_workflow(&trigger, integration, &app_state.http_client).await {
-            Ok(response) => Ok(response),
-            Err(e) => Err(format!("Failed to trigger integration: {}", e)),
-        }
+        trigger_n8n_workflow(&trigger, integration, &app_state.http_client).await
     } else {
-        Err("n8n integration not configured".to_string())
+        Err(ThetaError::NotConfigured("n8n integration not configured".to_string()))
     }
 }
 
@@ -43,7 +38,7 @@ async fn create_n8n_workflow(
     description: Option<String>,
     integration: &crate::state::Integration,
     client: &reqwest::Client,
-) -> Result<N8nWorkflow, Box<dyn std::error::Error>> {
+) -> Result<N8nWorkflow, ThetaError> {
     let workflow_data = serde_json::json!({
         "name": name,
         "active": true,
@@ -96,8 +91,14 @@ async fn create_n8n_workflow(
         request = request.header("Authorization", format!("Bearer {}", token));
     }
     
-    let response = request.send().await?;
-    let response_json: serde_json::Value = response.json().await?;
+    let response = request
+        .send()
+        .await
+        .map_err(|e| ThetaError::integration_request("Failed to create n8n workflow", e))?;
+    let response_json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
     
     let workflow = N8nWorkflow {
         id: response_json["id"].as_str().unwrap_or("").to_string(),
@@ -116,7 +117,7 @@ async fn trigger_n8n_workflow(
     trigger: &IntegrationTrigger,
     integration: &crate::state::Integration,
     client: &reqwest::Client,
-) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+) -> Result<serde_json::Value, ThetaError> {
     let url = format!("{}/workflows/{}/execute", integration.endpoint, trigger.workflow_id);
     let mut request = client.post(&url).json(&trigger.data);
     
@@ -124,8 +125,14 @@ async fn trigger_n8n_workflow(
         request = request.header("Authorization", format!("Bearer {}", token));
     }
     
-    let response = request.send().await?;
-    let response_json: serde_json::Value = response.json().await?;
+    let response = request
+        .send()
+        .await
+        .map_err(|e| ThetaError::integration_request("Failed to trigger integration", e))?;
+    let response_json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
     
     Ok(response_json)
 }
@@ -133,23 +140,20 @@ async fn trigger_n8n_workflow(
 #[tauri::command]
 pub async fn get_n8n_workflows(
     state: tauri::State<'_, Arc<RwLock<AppState>>>,
-) -> Result<Vec<N8nWorkflow>, String> {
+) -> Result<Vec<N8nWorkflow>, ThetaError> {
     let app_state = state.read().await;
     
     if let Some(integration) = app_state.integrations.get("n8n") {
-        match fetch_n8n_workflows(integration, &app_state.http_client).await {
-            Ok(workflows) => Ok(workflows),
-            Err(e) => Err(format!("Failed to fetch workflows: {}", e)),
-        }
+        fetch_n8n_workflows(integration, &app_state.http_client).await
     } else {
-        Err("n8n integration not configured".to_string())
+        Err(ThetaError::NotConfigured("n8n integration not configured".to_string()))
     }
 }
 
 async fn fetch_n8n_workflows(
     integration: &crate::state::Integration,
     client: &reqwest::Client,
-) -> Result<Vec<N8nWorkflow>, Box<dyn std::error::Error>> {
+) -> Result<Vec<N8nWorkflow>, ThetaError> {
     let url = format!("{}/workflows", integration.endpoint);
     let mut request = client.get(&url);
     
@@ -157,8 +161,14 @@ async fn fetch_n8n_workflows(
         request = request.header("Authorization", format!("Bearer {}", token));
     }
     
-    let response = request.send().await?;
-    let response_json: serde_json::Value = response.json().await?;
+    let response = request
+        .send()
+        .await
+        .map_err(|e| ThetaError::integration_request("Failed to fetch workflows", e))?;
+    let response_json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
     
     let mut workflows = Vec::new();
     
@@ -183,23 +193,20 @@ async fn fetch_n8n_workflows(
 #[tauri::command]
 pub async fn test_n8n_connection(
     state: tauri::State<'_, Arc<RwLock<AppState>>>,
-) -> Result<bool, String> {
+) -> Result<bool, ThetaError> {
     let app_state = state.read().await;
     
     if let Some(integration) = app_state.integrations.get("n8n") {
-        match test_n8n_api(integration, &app_state.http_client).await {
-            Ok(status) => Ok(status),
-            Err(e) => Err(format!("Connection test failed: {}", e)),
-        }
+        test_n8n_api(integration, &app_state.http_client).await
     } else {
-        Err("n8n integration not configured".to_string())
+        Err(ThetaError::NotConfigured("n8n integration not configured".to_string()))
     }
 }
 
 async fn test_n8n_api(
     integration: &crate::state::Integration,
     client: &reqwest::Client,
-) -> Result<bool, Box<dyn std::error::Error>> {
+) -> Result<bool, ThetaError> {
     let url = format!("{}/workflows", integration.endpoint);
     let mut request = client.get(&url);
     
@@ -207,6 +214,9 @@ async fn test_n8n_api(
         request = request.header("Authorization", format!("Bearer {}", token));
     }
     
-    let response = request.send().await?;
+    let response = request
+        .send()
+        .await
+        .map_err(|e| ThetaError::integration_request("Connection test failed", e))?;
     Ok(response.status().is_success())
 } 
\ No newline at end of file
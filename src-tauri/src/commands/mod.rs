@@ -1,4 +1,12 @@
 pub mod ai;
+pub mod analysis;
+pub mod prompts;
+pub mod tabs;
+pub mod workspaces;
+pub mod agent;
+pub mod runs;
+pub mod search;
+pub mod conversations;
 pub mod browser;
 pub mod database;
 pub mod integrations; 
\ No newline at end of file
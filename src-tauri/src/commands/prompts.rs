@@ -0,0 +1,129 @@
+use crate::ai::prompts::{PromptRegistry, PromptTemplate, TemplateSource, OVERRIDE_CATEGORY};
+use crate::error::ThetaError;
+use crate::state::AppState;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+#[tauri::command]
+pub async fn list_prompt_templates(
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<PromptTemplate>, ThetaError> {
+    Ok(state.read().await.prompts.list())
+}
+
+/// Saves changes to a template in `user_data`. `changes` may set any of
+/// `version`, `description`, `system`, `template`, `model`, `temperature`
+/// and `max_tokens`; it is merged into any earlier saved changes.
+#[tauri::command]
+pub async fn save_prompt_template(
+    name: String,
+    changes: serde_json::Value,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<PromptTemplate, ThetaError> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(ThetaError::InvalidInput("Prompt template name is empty".to_string()));
+    }
+    let changes = match changes {
+        serde_json::Value::Object(changes) => changes,
+        _ => return Err(ThetaError::InvalidInput("Prompt template changes must be an object".to_string())),
+    };
+
+    let mut app_state = state.write().await;
+    let pool = app_state.database.clone().ok_or(ThetaError::DatabaseUnavailable)?;
+
+    let mut saved = match fetch_override(&name, &pool).await? {
+        Some(serde_json::Value::Object(saved)) => saved,
+        _ => serde_json::Map::new(),
+    };
+    saved.extend(changes);
+    let saved = serde_json::Value::Object(saved);
+
+    // Validate against a copy so a rejected change leaves the registry as it was
+    let mut prompts = app_state.prompts.clone();
+    let template = prompts
+        .apply_override(&name, saved.clone(), TemplateSource::UserData)
+        .map_err(|e| ThetaError::InvalidInput(format!("Invalid prompt template '{}': {}", name, e)))?
+        .clone();
+
+    save_override(&name, &saved, &pool).await?;
+    app_state.prompts = prompts;
+
+    Ok(template)
+}
+
+/// Drops saved changes to a template. Returns the template as it is without
+/// them, or `None` if it only existed in `user_data`.
+#[tauri::command]
+pub async fn reset_prompt_template(
+    name: String,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Option<PromptTemplate>, ThetaError> {
+    let mut app_state = state.write().await;
+    let pool = app_state.database.clone().ok_or(ThetaError::DatabaseUnavailable)?;
+
+    delete_override(&name, &pool).await?;
+    let directory = app_state.prompts.directory().map(|directory| directory.to_path_buf());
+    app_state.prompts = PromptRegistry::load(directory, Some(&pool)).await;
+
+    Ok(app_state.prompts.get(&name).ok().cloned())
+}
+
+/// Re-reads the templates directory and `user_data`, e.g. after editing a
+/// template file.
+#[tauri::command]
+pub async fn reload_prompt_templates(
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<PromptTemplate>, ThetaError> {
+    let mut app_state = state.write().await;
+
+    let directory = app_state.prompts.directory().map(|directory| directory.to_path_buf());
+    app_state.prompts = PromptRegistry::load(directory, app_state.database.as_ref()).await;
+
+    Ok(app_state.prompts.list())
+}
+
+async fn fetch_override(
+    name: &str,
+    pool: &sqlx::PgPool,
+) -> Result<Option<serde_json::Value>, sqlx::Error> {
+    let row: Option<(serde_json::Value,)> = sqlx::query_as(
+        "SELECT value FROM user_data WHERE key = $1 AND category = $2",
+    )
+    .bind(name)
+    .bind(OVERRIDE_CATEGORY)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|(value,)| value))
+}
+
+async fn save_override(
+    name: &str,
+    value: &serde_json::Value,
+    pool: &sqlx::PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO user_data (key, value, category, created_at, updated_at)
+         VALUES ($1, $2, $3, NOW(), NOW())
+         ON CONFLICT (key, category)
+         DO UPDATE SET value = EXCLUDED.value, updated_at = EXCLUDED.updated_at",
+    )
+    .bind(name)
+    .bind(value)
+    .bind(OVERRIDE_CATEGORY)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn delete_override(name: &str, pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM user_data WHERE key = $1 AND category = $2")
+        .bind(name)
+        .bind(OVERRIDE_CATEGORY)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use crate::ai::recorder::{RecordedCall, RunRecorder};
+use crate::ai::replay;
+use crate::error::ThetaError;
+use crate::models::{AIRunReplay, AIRunSummary};
+use crate::state::AppState;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const DEFAULT_LIMIT: u32 = 50;
+
+/// The most recent runs in the AI run log, newest first. A run is the
+/// provider calls made for one request; responses carry its `run_id`.
+#[tauri::command]
+pub async fn list_ai_runs(
+    limit: Option<u32>,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<AIRunSummary>, ThetaError> {
+    let recorder = run_log(&*state.read().await)?;
+
+    recorder.list_runs(limit.unwrap_or(DEFAULT_LIMIT) as usize).await
+}
+
+/// Every provider call of a run: the request as sent, the response and raw
+/// body, tool calls, errors and latency.
+#[tauri::command]
+pub async fn get_ai_run(
+    run_id: String,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<RecordedCall>, ThetaError> {
+    let recorder = run_log(&*state.read().await)?;
+
+    recorder.load_run(&run_id).await
+}
+
+/// Re-executes a run against its recorded responses, without calling any
+/// provider or repeating any tool action, and reports where the requests
+/// now differ from the recording.
+#[tauri::command]
+pub async fn replay_ai_run(
+    run_id: String,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<AIRunReplay, ThetaError> {
+    let recorder = run_log(&*state.read().await)?;
+
+    let calls = recorder.load_run(&run_id).await?;
+    replay::replay_run(&run_id, &calls).await
+}
+
+fn run_log(app_state: &AppState) -> Result<RunRecorder, ThetaError> {
+    app_state.ai_services
+        .recorder
+        .clone()
+        .ok_or_else(|| ThetaError::NotConfigured("The AI run log is turned off".to_string()))
+}
@@ -0,0 +1,241 @@
+use crate::browser::ContentExtractor;
+use crate::commands::ai::require_text;
+use crate::error::ThetaError;
+use crate::models::{DocumentSource, SearchResult, SemanticMatch};
+use crate::search::hybrid::reciprocal_rank_fusion;
+use crate::search::index::IndexDocument;
+use crate::search::SemanticIndex;
+use crate::state::AppState;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const DEFAULT_LIMIT: u32 = 10;
+
+/// Candidates fetched from each search per requested result, so results
+/// found by only one of them can still be fused in.
+const CANDIDATES_PER_RESULT: usize = 3;
+
+/// A document found by the full-text search.
+#[derive(sqlx::FromRow)]
+struct FullTextHit {
+    source: String,
+    url: String,
+    title: String,
+    snippet: String,
+}
+
+/// Visited pages and bookmarks closest in meaning to `query`, optionally
+/// only those from one source.
+#[tauri::command]
+pub async fn semantic_search(
+    query: String,
+    limit: Option<u32>,
+    source: Option<DocumentSource>,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<SemanticMatch>, ThetaError> {
+    require_text(&query, "Search query")?;
+
+    let index = {
+        let app_state = state.read().await;
+        allowed_index(&app_state)?
+    };
+
+    index.search(&query, limit.unwrap_or(DEFAULT_LIMIT).max(1) as usize, source).await
+}
+
+/// Searches bookmarks, visited pages, saved analyses and notes both by
+/// keyword (Postgres full-text search) and by meaning (the semantic index),
+/// fusing the two rankings. `relevance_score` is the fused score: 1.0 for
+/// a result both searches rank first, lower the further down either one
+/// ranks it. Works with only one of the two when the other is unavailable.
+#[tauri::command]
+pub async fn hybrid_search(
+    query: String,
+    limit: Option<u32>,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<SearchResult>, ThetaError> {
+    require_text(&query, "Search query")?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).max(1) as usize;
+
+    let (pool, index) = {
+        let app_state = state.read().await;
+        (app_state.database.clone(), allowed_index(&app_state))
+    };
+
+    let full_text = async {
+        let pool = pool.as_ref().ok_or(ThetaError::DatabaseUnavailable)?;
+        Ok::<_, ThetaError>(full_text_search(&query, (limit * CANDIDATES_PER_RESULT) as i64, pool).await?)
+    };
+    let semantic = async {
+        match index {
+            Ok(index) => index.search(&query, limit * CANDIDATES_PER_RESULT, None).await,
+            Err(e) => Err(e),
+        }
+    };
+    let (full_text, semantic) = match tokio::join!(full_text, semantic) {
+        (Err(e), Err(_)) => return Err(e),
+        (full_text, semantic) => (
+            full_text.unwrap_or_else(|e| {
+                log::warn!("Full-text search unavailable, using semantic results only: {}", e);
+                Vec::new()
+            }),
+            semantic.unwrap_or_else(|e| {
+                log::warn!("Semantic search unavailable, using full-text results only: {}", e);
+                Vec::new()
+            }),
+        ),
+    };
+
+    Ok(fuse_results(full_text, semantic, limit))
+}
+
+/// Fetches a page and adds it to the semantic index now. Returns the number
+/// of chunks embedded, 0 when the page has not changed since it was indexed.
+#[tauri::command]
+pub async fn index_page(
+    url: String,
+    source: Option<DocumentSource>,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<usize, ThetaError> {
+    let (index, http_client) = {
+        let app_state = state.read().await;
+        (allowed_index(&app_state)?, app_state.http_client.clone())
+    };
+
+    index
+        .index_url(&ContentExtractor::with_client(http_client), &url, source.unwrap_or(DocumentSource::Page))
+        .await
+}
+
+/// Indexes a document without holding up the caller. Skipped in privacy
+/// mode when the index would send the text off this machine.
+pub(crate) fn index_in_background(app_state: &AppState, document: IndexDocument) {
+    let Ok(index) = allowed_index(app_state) else { return };
+
+    tokio::spawn(async move {
+        if let Err(e) = index.index_document(&document).await {
+            log::warn!("Failed to index {} for semantic search: {}", document.url, e);
+        }
+    });
+}
+
+/// Fetches and indexes a page without holding up the caller, e.g. a new
+/// bookmark. Skipped in privacy mode like `index_in_background`.
+pub(crate) fn index_url_in_background(app_state: &AppState, url: String, source: DocumentSource) {
+    let Ok(index) = allowed_index(app_state) else { return };
+    let extractor = ContentExtractor::with_client(app_state.http_client.clone());
+
+    tokio::spawn(async move {
+        if let Err(e) = index.index_url(&extractor, &url, source).await {
+            log::warn!("Failed to index {} for semantic search: {}", url, e);
+        }
+    });
+}
+
+fn fuse_results(full_text: Vec<FullTextHit>, semantic: Vec<SemanticMatch>, limit: usize) -> Vec<SearchResult> {
+    let mut results: HashMap<String, SearchResult> = HashMap::new();
+
+    let mut full_text_order: Vec<String> = Vec::new();
+    for hit in full_text {
+        // Several analyses of a page count as one hit, at the best rank
+        if full_text_order.contains(&hit.url) {
+            continue;
+        }
+        full_text_order.push(hit.url.clone());
+        results.insert(hit.url.clone(), SearchResult {
+            title: hit.title,
+            url: hit.url,
+            snippet: hit.snippet,
+            relevance_score: 0.0,
+            metadata: serde_json::json!({
+                "sources": [hit.source],
+                "full_text_rank": full_text_order.len(),
+            }),
+        });
+    }
+
+    let semantic_order: Vec<String> = semantic.iter().map(|found| found.url.clone()).collect();
+    for (index, found) in semantic.into_iter().enumerate() {
+        let result = results.entry(found.url.clone()).or_insert_with(|| SearchResult {
+            title: found.title,
+            url: found.url,
+            snippet: found.snippet,
+            relevance_score: 0.0,
+            metadata: serde_json::json!({ "sources": [] }),
+        });
+        let source = found.source.as_str();
+        if let Some(sources) = result.metadata["sources"].as_array_mut() {
+            if !sources.iter().any(|known| known == source) {
+                sources.push(source.into());
+            }
+        }
+        result.metadata["semantic_rank"] = (index + 1).into();
+        result.metadata["similarity"] = found.score.into();
+    }
+
+    reciprocal_rank_fusion(&[full_text_order, semantic_order])
+        .into_iter()
+        .take(limit)
+        .filter_map(|(url, score)| {
+            let mut result = results.remove(&url)?;
+            result.relevance_score = score;
+            Some(result)
+        })
+        .collect()
+}
+
+fn allowed_index(app_state: &AppState) -> Result<SemanticIndex, ThetaError> {
+    let index = &app_state.semantic_index;
+    if app_state.ai_services.privacy_mode && !index.is_local() {
+        return Err(ThetaError::AiUnavailable(format!(
+            "Semantic search uses {} embeddings and the {} store, which privacy mode does not allow",
+            index.embedder(),
+            index.store()
+        )));
+    }
+    Ok(index.clone())
+}
+
+/// Bookmarks, visited pages, analyses and notes matching `query`, best
+/// first. Notes are `user_data` rows in the `note` category.
+async fn full_text_search(
+    query: &str,
+    limit: i64,
+    pool: &sqlx::PgPool,
+) -> Result<Vec<FullTextHit>, sqlx::Error> {
+    sqlx::query_as::<_, FullTextHit>(
+        r#"SELECT source, url, title,
+                  ts_headline('english', body, query, 'MaxWords=35, MinWords=15, StartSel="", StopSel=""') AS snippet
+           FROM (
+               SELECT 'bookmark' AS source, url, title,
+                      concat_ws(' ', description, ai_summary) AS body,
+                      ts_rank_cd(search_vector, query) AS rank
+               FROM bookmarks, websearch_to_tsquery('english', $1) query
+               WHERE search_vector @@ query
+               UNION ALL
+               SELECT 'page', url, coalesce(title, url), coalesce(content, ''),
+                      ts_rank_cd(search_vector, query)
+               FROM page_content_cache, websearch_to_tsquery('english', $1) query
+               WHERE search_vector @@ query
+               UNION ALL
+               SELECT 'page', url, coalesce(title, url), coalesce(analysis_data->>'summary', ''),
+                      ts_rank_cd(search_vector, query)
+               FROM content_analysis, websearch_to_tsquery('english', $1) query
+               WHERE search_vector @@ query
+               UNION ALL
+               SELECT 'note', 'note:' || key, coalesce(value->>'title', key),
+                      coalesce(value->>'content', value::text),
+                      ts_rank_cd(to_tsvector('english', value), query)
+               FROM user_data, websearch_to_tsquery('english', $1) query
+               WHERE category = 'note' AND to_tsvector('english', value) @@ query
+               ORDER BY rank DESC
+               LIMIT $2
+           ) hits, websearch_to_tsquery('english', $1) query
+           ORDER BY rank DESC"#,
+    )
+    .bind(query)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
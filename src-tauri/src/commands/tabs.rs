@@ -0,0 +1,91 @@
+use crate::ai::tab_chat::{self, SourceDocument};
+use crate::browser::ContentExtractor;
+use crate::commands::ai::require_text;
+use crate::error::ThetaError;
+use crate::models::TabAnswer;
+use crate::state::AppState;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Answers a question about an open tab from the page's own text, citing
+/// the passages used. The page is fetched and extracted first if the tab
+/// has no content yet, and the tab's `ai_context` keeps a running summary
+/// of the conversation for follow-up questions.
+#[tauri::command]
+pub async fn chat_with_tab(
+    tab_id: Uuid,
+    question: String,
+    provider: Option<String>,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<TabAnswer, ThetaError> {
+    require_text(&question, "Question")?;
+
+    // Fetching the page and asking the model take a while; work on copies
+    let (tab, services, prompts, http_client) = {
+        let app_state = state.read().await;
+        let tab = app_state
+            .get_tab(&tab_id)
+            .cloned()
+            .ok_or_else(|| ThetaError::NotFound(format!("Tab {} not found", tab_id)))?;
+        (tab, app_state.ai_services.in_run(&Uuid::new_v4().to_string()), app_state.prompts.clone(), app_state.http_client.clone())
+    };
+
+    let content = match tab.content.clone().filter(|content| !content.trim().is_empty()) {
+        Some(content) => content,
+        None => {
+            let extracted = ContentExtractor::with_client(http_client).extract_from_url(&tab.url).await?;
+            if extracted.content.trim().is_empty() {
+                return Err(ThetaError::extraction(format!("{} has no readable text", tab.url)));
+            }
+            state.write().await.update_tab_content(&tab_id, extracted.content.clone());
+            extracted.content
+        }
+    };
+
+    let document = SourceDocument {
+        tab_id,
+        url: tab.url.clone(),
+        title: tab.title.clone(),
+        content,
+    };
+    let (response, citations) = tab_chat::answer_tab(
+        &services,
+        &prompts,
+        provider.as_deref(),
+        &document,
+        tab.ai_context.as_deref(),
+        &question,
+    )
+    .await?;
+
+    // The answer stands even if the running summary could not be updated
+    let ai_context = match tab_chat::update_context(
+        &services,
+        &prompts,
+        &response.provider,
+        tab.ai_context.as_deref(),
+        &question,
+        &response.content,
+    )
+    .await
+    {
+        Ok(summary) => {
+            state.write().await.update_tab_context(&tab_id, summary.clone());
+            Some(summary)
+        }
+        Err(e) => {
+            log::warn!("Failed to update the conversation summary for tab {}: {}", tab_id, e);
+            tab.ai_context
+        }
+    };
+
+    Ok(TabAnswer {
+        tab_id,
+        answer: response.content,
+        citations,
+        ai_context,
+        provider: response.provider,
+        model: response.model,
+    })
+}
@@ -0,0 +1,175 @@
+use crate::ai::tab_chat::{self, SourceDocument};
+use crate::browser::ContentExtractor;
+use crate::commands::ai::require_text;
+use crate::error::ThetaError;
+use crate::models::{SkippedTab, WorkspaceAnswer};
+use crate::state::AppState;
+use futures::{stream, StreamExt};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Pages fetched at once when tabs have no content yet.
+const MAX_PARALLEL_EXTRACTIONS: usize = 4;
+
+/// Answers a question from the text of every tab in a workspace, citing
+/// which tab each passage came from. Tabs whose page cannot be read are
+/// left out and reported, and the workspace's `ai_context` keeps a running
+/// summary of the conversation for follow-up questions.
+#[tauri::command]
+pub async fn ask_workspace(
+    workspace_id: Uuid,
+    question: String,
+    provider: Option<String>,
+    state: tauri::State<'_, Arc<RwLock<AppState>>>,
+) -> Result<WorkspaceAnswer, ThetaError> {
+    require_text(&question, "Question")?;
+
+    // Fetching pages and asking the model take a while; work on copies
+    let (pool, services, prompts, http_client) = {
+        let app_state = state.read().await;
+        let pool = app_state.database.clone().ok_or(ThetaError::DatabaseUnavailable)?;
+        (pool, app_state.ai_services.in_run(&Uuid::new_v4().to_string()), app_state.prompts.clone(), app_state.http_client.clone())
+    };
+
+    let (name, ai_context) = fetch_workspace(workspace_id, &pool)
+        .await?
+        .ok_or_else(|| ThetaError::NotFound(format!("Workspace {} not found", workspace_id)))?;
+
+    let tabs = fetch_workspace_tabs(workspace_id, &pool).await?;
+    if tabs.is_empty() {
+        return Err(ThetaError::InvalidInput(format!("Workspace '{}' has no tabs", name)));
+    }
+
+    // Open tabs may already hold their page text
+    let known: Vec<Option<String>> = {
+        let app_state = state.read().await;
+        tabs.iter()
+            .map(|(id, url, _)| {
+                app_state
+                    .get_tab(id)
+                    .or_else(|| app_state.active_tabs.values().find(|tab| &tab.url == url))
+                    .and_then(|tab| tab.content.clone())
+                    .filter(|content| !content.trim().is_empty())
+            })
+            .collect()
+    };
+
+    let extractor = ContentExtractor::with_client(http_client);
+    let results: Vec<Result<SourceDocument, SkippedTab>> = stream::iter(tabs.into_iter().zip(known))
+        .map(|((tab_id, url, title), content)| {
+            let extractor = &extractor;
+            async move {
+                let (title, content) = match content {
+                    Some(content) => (title, content),
+                    None => match extractor.extract_from_url(&url).await {
+                        Ok(extracted) if !extracted.content.trim().is_empty() => {
+                            (title.or(Some(extracted.title)), extracted.content)
+                        }
+                        Ok(_) => {
+                            let error = "No readable text".to_string();
+                            return Err(SkippedTab { tab_id, url, error });
+                        }
+                        Err(e) => return Err(SkippedTab { tab_id, url, error: e.to_string() }),
+                    },
+                };
+                Ok(SourceDocument { tab_id, title: title.unwrap_or_else(|| url.clone()), url, content })
+            }
+        })
+        .buffered(MAX_PARALLEL_EXTRACTIONS)
+        .collect()
+        .await;
+
+    let (documents, skipped_tabs): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
+    let documents: Vec<SourceDocument> = documents.into_iter().filter_map(Result::ok).collect();
+    let skipped_tabs: Vec<SkippedTab> = skipped_tabs.into_iter().filter_map(Result::err).collect();
+    for skipped in &skipped_tabs {
+        log::warn!("Leaving out {} from workspace {}: {}", skipped.url, workspace_id, skipped.error);
+    }
+    if documents.is_empty() {
+        return Err(ThetaError::InvalidInput(format!("None of the tabs in workspace '{}' could be read", name)));
+    }
+
+    let (response, citations) = tab_chat::answer_workspace(
+        &services,
+        &prompts,
+        provider.as_deref(),
+        &name,
+        &documents,
+        ai_context.as_deref(),
+        &question,
+    )
+    .await?;
+
+    // The answer stands even if the running summary could not be updated
+    let summary = tab_chat::update_context(
+        &services,
+        &prompts,
+        &response.provider,
+        ai_context.as_deref(),
+        &question,
+        &response.content,
+    )
+    .await;
+    let ai_context = match summary {
+        Ok(summary) => match save_workspace_context(workspace_id, &summary, &pool).await {
+            Ok(()) => Some(summary),
+            Err(e) => {
+                log::warn!("Failed to save the conversation summary for workspace {}: {}", workspace_id, e);
+                ai_context
+            }
+        },
+        Err(e) => {
+            log::warn!("Failed to update the conversation summary for workspace {}: {}", workspace_id, e);
+            ai_context
+        }
+    };
+
+    Ok(WorkspaceAnswer {
+        workspace_id,
+        answer: response.content,
+        citations,
+        skipped_tabs,
+        ai_context,
+        provider: response.provider,
+        model: response.model,
+    })
+}
+
+async fn fetch_workspace(
+    workspace_id: Uuid,
+    pool: &sqlx::PgPool,
+) -> Result<Option<(String, Option<String>)>, sqlx::Error> {
+    sqlx::query_as("SELECT name, ai_context FROM workspaces WHERE id = $1")
+        .bind(workspace_id)
+        .fetch_optional(pool)
+        .await
+}
+
+async fn fetch_workspace_tabs(
+    workspace_id: Uuid,
+    pool: &sqlx::PgPool,
+) -> Result<Vec<(Uuid, String, Option<String>)>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT id, url, title FROM browser_tabs
+         WHERE workspace_id = $1
+         ORDER BY created_at",
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+}
+
+async fn save_workspace_context(
+    workspace_id: Uuid,
+    ai_context: &str,
+    pool: &sqlx::PgPool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE workspaces SET ai_context = $2 WHERE id = $1")
+        .bind(workspace_id)
+        .bind(ai_context)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -83,7 +83,7 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     sqlx::query(r#"
         CREATE TABLE IF NOT EXISTS ai_conversations (
             id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
-            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            user_id UUID REFERENCES users(id) ON DELETE CASCADE,
             title VARCHAR(255),
             messages JSONB NOT NULL DEFAULT '[]',
             model VARCHAR(100),
@@ -95,6 +95,19 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // Conversations are owned by the local profile unless a user is attached
+    sqlx::query("ALTER TABLE ai_conversations ALTER COLUMN user_id DROP NOT NULL")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE ai_conversations ADD COLUMN IF NOT EXISTS context_strategy VARCHAR(32) NOT NULL DEFAULT 'drop_oldest'")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE ai_conversations ADD COLUMN IF NOT EXISTS rolling_summary JSONB")
+        .execute(pool)
+        .await?;
+
     // Create content_analysis table
     sqlx::query(r#"
         CREATE TABLE IF NOT EXISTS content_analysis (
@@ -110,6 +123,77 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // Analyses are saved for the local profile unless a user is attached
+    sqlx::query("ALTER TABLE content_analysis ALTER COLUMN user_id DROP NOT NULL")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE content_analysis ADD COLUMN IF NOT EXISTS model VARCHAR(100)")
+        .execute(pool)
+        .await?;
+
+    // Extracted text of visited pages, refetched once expired
+    sqlx::query(r#"
+        CREATE TABLE IF NOT EXISTS page_content_cache (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            url TEXT NOT NULL UNIQUE,
+            title VARCHAR(500),
+            content TEXT,
+            html TEXT,
+            links TEXT[],
+            images TEXT[],
+            metadata JSONB,
+            extracted_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            expires_at TIMESTAMP WITH TIME ZONE DEFAULT (NOW() + INTERVAL '24 hours')
+        )
+    "#)
+    .execute(pool)
+    .await?;
+
+    // Weighted text for full-text search; titles rank above body text
+    sqlx::query(r#"
+        ALTER TABLE bookmarks ADD COLUMN IF NOT EXISTS search_vector TSVECTOR GENERATED ALWAYS AS (
+            setweight(to_tsvector('english', coalesce(title, '')), 'A') ||
+            setweight(to_tsvector('english', coalesce(description, '') || ' ' || coalesce(ai_summary, '')), 'B')
+        ) STORED
+    "#)
+    .execute(pool)
+    .await?;
+
+    sqlx::query(r#"
+        ALTER TABLE content_analysis ADD COLUMN IF NOT EXISTS search_vector TSVECTOR GENERATED ALWAYS AS (
+            setweight(to_tsvector('english', coalesce(title, '')), 'A') ||
+            setweight(to_tsvector('english', coalesce(analysis_data->>'summary', '')), 'B')
+        ) STORED
+    "#)
+    .execute(pool)
+    .await?;
+
+    sqlx::query(r#"
+        ALTER TABLE page_content_cache ADD COLUMN IF NOT EXISTS search_vector TSVECTOR GENERATED ALWAYS AS (
+            setweight(to_tsvector('english', coalesce(title, '')), 'A') ||
+            setweight(to_tsvector('english', coalesce(content, '')), 'C')
+        ) STORED
+    "#)
+    .execute(pool)
+    .await?;
+
+    // One row per completed AI request, for cost reporting
+    sqlx::query(r#"
+        CREATE TABLE IF NOT EXISTS ai_usage (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            provider VARCHAR(100) NOT NULL,
+            model VARCHAR(100) NOT NULL,
+            feature VARCHAR(32) NOT NULL,
+            input_tokens INTEGER NOT NULL DEFAULT 0,
+            output_tokens INTEGER NOT NULL DEFAULT 0,
+            cost_usd DOUBLE PRECISION,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )
+    "#)
+    .execute(pool)
+    .await?;
+
     // Create user_data table
     sqlx::query(r#"
         CREATE TABLE IF NOT EXISTS user_data (
@@ -143,9 +227,42 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
     
+    // A page may return to earlier content, so a hash can recur in its history
+    sqlx::query("DROP INDEX IF EXISTS idx_content_analysis_url_hash")
+        .execute(pool)
+        .await?;
+    
+    sqlx::query("ALTER TABLE content_analysis DROP CONSTRAINT IF EXISTS content_analysis_url_content_hash_key")
+        .execute(pool)
+        .await?;
+    
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_ai_conversations_updated_at ON ai_conversations(updated_at DESC)")
+        .execute(pool)
+        .await?;
+    
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_ai_usage_created_at ON ai_usage(created_at DESC)")
+        .execute(pool)
+        .await?;
+    
     sqlx::query("CREATE INDEX IF NOT EXISTS idx_user_data_key ON user_data(key)")
         .execute(pool)
         .await?;
+    
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_bookmarks_search ON bookmarks USING GIN(search_vector)")
+        .execute(pool)
+        .await?;
+    
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_content_analysis_search ON content_analysis USING GIN(search_vector)")
+        .execute(pool)
+        .await?;
+    
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_page_content_cache_search ON page_content_cache USING GIN(search_vector)")
+        .execute(pool)
+        .await?;
+    
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_user_data_notes_search ON user_data USING GIN(to_tsvector('english', value)) WHERE category = 'note'")
+        .execute(pool)
+        .await?;
 
     Ok(())
 }
@@ -154,6 +271,8 @@ pub async fn drop_all_tables(pool: &PgPool) -> Result<(), sqlx::Error> {
     // Drop tables in reverse order due to foreign key constraints
     let tables = vec![
         "user_data",
+        "page_content_cache",
+        "ai_usage",
         "content_analysis", 
         "ai_conversations",
         "browsing_sessions",
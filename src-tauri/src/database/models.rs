@@ -64,11 +64,16 @@ pub struct BrowsingSession {
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct AIConversation {
     pub id: Uuid,
-    pub user_id: Uuid,
+    #[sqlx(default)]
+    pub user_id: Option<Uuid>,
     pub title: Option<String>,
     pub messages: serde_json::Value,
     pub model: Option<String>,
     pub provider: Option<String>,
+    #[sqlx(default)]
+    pub context_strategy: Option<String>,
+    #[sqlx(default)]
+    pub rolling_summary: Option<serde_json::Value>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -76,10 +81,12 @@ pub struct AIConversation {
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct ContentAnalysis {
     pub id: Uuid,
-    pub user_id: Uuid,
+    #[sqlx(default)]
+    pub user_id: Option<Uuid>,
     pub url: String,
     pub title: Option<String>,
     pub content_hash: Option<String>,
+    pub model: Option<String>,
     pub analysis_data: serde_json::Value,
     pub created_at: DateTime<Utc>,
 }
@@ -0,0 +1,149 @@
+use crate::ai::{ProviderError, ProviderErrorKind};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// Error type shared by every command. The frontend receives it as
+/// `{ kind, message, retryable }` (see `ErrorInfo`).
+#[derive(Debug, thiserror::Error)]
+pub enum ThetaError {
+    #[error("AI service error: {message}")]
+    Ai { message: String, retryable: bool },
+
+    /// No provider is configured, or none is allowed in privacy mode.
+    #[error("{0}")]
+    AiUnavailable(String),
+
+    #[error("Database not initialized")]
+    DatabaseUnavailable,
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Cache error: {0}")]
+    Cache(#[from] redis::RedisError),
+
+    #[error("{message}")]
+    Search { message: String, retryable: bool },
+
+    #[error("{message}")]
+    Integration { message: String, retryable: bool },
+
+    #[error("Failed to extract page content: {message}")]
+    Extraction { message: String, retryable: bool },
+
+    /// A missing API key, endpoint or unsupported provider name.
+    #[error("{0}")]
+    NotConfigured(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    InvalidInput(String),
+}
+
+impl ThetaError {
+    pub fn ai(message: impl Into<String>) -> Self {
+        Self::Ai { message: message.into(), retryable: false }
+    }
+
+    pub fn search(message: impl Into<String>) -> Self {
+        Self::Search { message: message.into(), retryable: false }
+    }
+
+    pub fn integration(message: impl Into<String>) -> Self {
+        Self::Integration { message: message.into(), retryable: false }
+    }
+
+    pub fn extraction(message: impl Into<String>) -> Self {
+        Self::Extraction { message: message.into(), retryable: false }
+    }
+
+    pub fn search_request(context: &str, error: reqwest::Error) -> Self {
+        Self::Search { retryable: is_transient(&error), message: format!("{}: {}", context, error) }
+    }
+
+    pub fn integration_request(context: &str, error: reqwest::Error) -> Self {
+        Self::Integration { retryable: is_transient(&error), message: format!("{}: {}", context, error) }
+    }
+
+    pub fn extraction_request(context: &str, error: reqwest::Error) -> Self {
+        Self::Extraction { retryable: is_transient(&error), message: format!("{}: {}", context, error) }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Ai { .. } => "ai",
+            Self::AiUnavailable(_) => "ai_unavailable",
+            Self::DatabaseUnavailable => "database_unavailable",
+            Self::Database(_) => "database",
+            Self::Cache(_) => "cache",
+            Self::Search { .. } => "search",
+            Self::Integration { .. } => "integration",
+            Self::Extraction { .. } => "extraction",
+            Self::NotConfigured(_) => "not_configured",
+            Self::NotFound(_) => "not_found",
+            Self::InvalidInput(_) => "invalid_input",
+        }
+    }
+
+    /// Whether trying the same action again later may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Ai { retryable, .. }
+            | Self::Search { retryable, .. }
+            | Self::Integration { retryable, .. }
+            | Self::Extraction { retryable, .. } => *retryable,
+            Self::Database(error) => matches!(
+                error,
+                sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed
+            ),
+            Self::Cache(error) => error.is_io_error() || error.is_timeout() || error.is_connection_dropped(),
+            _ => false,
+        }
+    }
+}
+
+fn is_transient(error: &reqwest::Error) -> bool {
+    error.is_timeout()
+        || error.is_connect()
+        || error.status().is_some_and(|status| status.is_server_error() || status.as_u16() == 429)
+}
+
+impl From<ProviderError> for ThetaError {
+    fn from(error: ProviderError) -> Self {
+        match error.kind {
+            ProviderErrorKind::NotConfigured => Self::NotConfigured(error.to_string()),
+            _ => Self::Ai { retryable: error.is_retryable(), message: error.to_string() },
+        }
+    }
+}
+
+/// The serialized form of `ThetaError`, also used inside events.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorInfo {
+    pub kind: String,
+    pub message: String,
+    pub retryable: bool,
+}
+
+impl From<&ThetaError> for ErrorInfo {
+    fn from(error: &ThetaError) -> Self {
+        Self {
+            kind: error.kind().to_string(),
+            message: error.to_string(),
+            retryable: error.is_retryable(),
+        }
+    }
+}
+
+impl From<ThetaError> for ErrorInfo {
+    fn from(error: ThetaError) -> Self {
+        Self::from(&error)
+    }
+}
+
+impl Serialize for ThetaError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ErrorInfo::from(self).serialize(serializer)
+    }
+}
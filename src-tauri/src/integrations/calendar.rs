@@ -1,3 +1,4 @@
+use crate::error::ThetaError;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -43,24 +44,24 @@ impl CalendarIntegration {
         }
     }
 
-    pub async fn get_events(&self, start_date: DateTime<Utc>, end_date: DateTime<Utc>) -> Result<Vec<CalendarEvent>, String> {
+    pub async fn get_events(&self, start_date: DateTime<Utc>, end_date: DateTime<Utc>) -> Result<Vec<CalendarEvent>, ThetaError> {
         match self.provider.as_str() {
             "google" => self.get_google_events(start_date, end_date).await,
             "outlook" => self.get_outlook_events(start_date, end_date).await,
-            _ => Err("Unsupported calendar provider".to_string()),
+            _ => Err(ThetaError::NotConfigured("Unsupported calendar provider".to_string())),
         }
     }
 
-    pub async fn create_event(&self, event: CreateCalendarEvent) -> Result<CalendarEvent, String> {
+    pub async fn create_event(&self, event: CreateCalendarEvent) -> Result<CalendarEvent, ThetaError> {
         match self.provider.as_str() {
             "google" => self.create_google_event(event).await,
             "outlook" => self.create_outlook_event(event).await,
-            _ => Err("Unsupported calendar provider".to_string()),
+            _ => Err(ThetaError::NotConfigured("Unsupported calendar provider".to_string())),
         }
     }
 
-    async fn get_google_events(&self, start_date: DateTime<Utc>, end_date: DateTime<Utc>) -> Result<Vec<CalendarEvent>, String> {
-        let api_key = self.api_key.as_ref().ok_or("Google API key not set")?;
+    async fn get_google_events(&self, start_date: DateTime<Utc>, end_date: DateTime<Utc>) -> Result<Vec<CalendarEvent>, ThetaError> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| ThetaError::NotConfigured("Google API key not set".to_string()))?;
         
         let url = format!(
             "https://www.googleapis.com/calendar/v3/calendars/primary/events?timeMin={}&timeMax={}&key={}",
@@ -73,10 +74,10 @@ impl CalendarIntegration {
             .get(&url)
             .send()
             .await
-            .map_err(|e| format!("Failed to fetch Google events: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to fetch Google events", e))?;
         
         let json: Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
         
         let mut events = Vec::new();
         
@@ -87,9 +88,9 @@ impl CalendarIntegration {
                     title: item["summary"].as_str().unwrap_or("").to_string(),
                     description: item["description"].as_str().map(|s| s.to_string()),
                     start_time: DateTime::parse_from_rfc3339(item["start"]["dateTime"].as_str().unwrap_or(""))
-                        .map_err(|e| format!("Invalid start time: {}", e))?.with_timezone(&Utc),
+                        .map_err(|e| ThetaError::integration(format!("Invalid start time: {}", e)))?.with_timezone(&Utc),
                     end_time: DateTime::parse_from_rfc3339(item["end"]["dateTime"].as_str().unwrap_or(""))
-                        .map_err(|e| format!("Invalid end time: {}", e))?.with_timezone(&Utc),
+                        .map_err(|e| ThetaError::integration(format!("Invalid end time: {}", e)))?.with_timezone(&Utc),
                     location: item["location"].as_str().map(|s| s.to_string()),
                     attendees: vec![],
                     is_all_day: item["start"]["date"].is_string(),
@@ -101,8 +102,8 @@ impl CalendarIntegration {
         Ok(events)
     }
 
-    async fn get_outlook_events(&self, start_date: DateTime<Utc>, end_date: DateTime<Utc>) -> Result<Vec<CalendarEvent>, String> {
-        let api_key = self.api_key.as_ref().ok_or("Outlook API key not set")?;
+    async fn get_outlook_events(&self, start_date: DateTime<Utc>, end_date: DateTime<Utc>) -> Result<Vec<CalendarEvent>, ThetaError> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| ThetaError::NotConfigured("Outlook API key not set".to_string()))?;
         
         let url = format!(
             "https://graph.microsoft.com/v1.0/me/events?$filter=start/dateTime ge '{}' and end/dateTime le '{}'",
@@ -115,10 +116,10 @@ impl CalendarIntegration {
             .header("Authorization", format!("Bearer {}", api_key))
             .send()
             .await
-            .map_err(|e| format!("Failed to fetch Outlook events: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to fetch Outlook events", e))?;
         
         let json: Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
         
         let mut events = Vec::new();
         
@@ -129,9 +130,9 @@ impl CalendarIntegration {
                     title: item["subject"].as_str().unwrap_or("").to_string(),
                     description: item["body"]["content"].as_str().map(|s| s.to_string()),
                     start_time: DateTime::parse_from_rfc3339(item["start"]["dateTime"].as_str().unwrap_or(""))
-                        .map_err(|e| format!("Invalid start time: {}", e))?.with_timezone(&Utc),
+                        .map_err(|e| ThetaError::integration(format!("Invalid start time: {}", e)))?.with_timezone(&Utc),
                     end_time: DateTime::parse_from_rfc3339(item["end"]["dateTime"].as_str().unwrap_or(""))
-                        .map_err(|e| format!("Invalid end time: {}", e))?.with_timezone(&Utc),
+                        .map_err(|e| ThetaError::integration(format!("Invalid end time: {}", e)))?.with_timezone(&Utc),
                     location: item["location"]["displayName"].as_str().map(|s| s.to_string()),
                     attendees: vec![],
                     is_all_day: item["isAllDay"].as_bool().unwrap_or(false),
@@ -143,8 +144,8 @@ impl CalendarIntegration {
         Ok(events)
     }
 
-    async fn create_google_event(&self, event: CreateCalendarEvent) -> Result<CalendarEvent, String> {
-        let api_key = self.api_key.as_ref().ok_or("Google API key not set")?;
+    async fn create_google_event(&self, event: CreateCalendarEvent) -> Result<CalendarEvent, ThetaError> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| ThetaError::NotConfigured("Google API key not set".to_string()))?;
         
         let url = format!("https://www.googleapis.com/calendar/v3/calendars/primary/events?key={}", api_key);
         
@@ -167,19 +168,19 @@ impl CalendarIntegration {
             .json(&event_data)
             .send()
             .await
-            .map_err(|e| format!("Failed to create Google event: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to create Google event", e))?;
         
         let json: Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
         
         let created_event = CalendarEvent {
             id: json["id"].as_str().unwrap_or("").to_string(),
             title: json["summary"].as_str().unwrap_or("").to_string(),
             description: json["description"].as_str().map(|s| s.to_string()),
             start_time: DateTime::parse_from_rfc3339(json["start"]["dateTime"].as_str().unwrap_or(""))
-                .map_err(|e| format!("Invalid start time: {}", e))?.with_timezone(&Utc),
+                .map_err(|e| ThetaError::integration(format!("Invalid start time: {}", e)))?.with_timezone(&Utc),
             end_time: DateTime::parse_from_rfc3339(json["end"]["dateTime"].as_str().unwrap_or(""))
-                .map_err(|e| format!("Invalid end time: {}", e))?.with_timezone(&Utc),
+                .map_err(|e| ThetaError::integration(format!("Invalid end time: {}", e)))?.with_timezone(&Utc),
             location: json["location"].as_str().map(|s| s.to_string()),
             attendees: vec![],
             is_all_day: false,
@@ -188,8 +189,8 @@ impl CalendarIntegration {
         Ok(created_event)
     }
 
-    async fn create_outlook_event(&self, event: CreateCalendarEvent) -> Result<CalendarEvent, String> {
-        let api_key = self.api_key.as_ref().ok_or("Outlook API key not set")?;
+    async fn create_outlook_event(&self, event: CreateCalendarEvent) -> Result<CalendarEvent, ThetaError> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| ThetaError::NotConfigured("Outlook API key not set".to_string()))?;
         
         let url = "https://graph.microsoft.com/v1.0/me/events";
         
@@ -218,19 +219,19 @@ impl CalendarIntegration {
             .json(&event_data)
             .send()
             .await
-            .map_err(|e| format!("Failed to create Outlook event: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to create Outlook event", e))?;
         
         let json: Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
         
         let created_event = CalendarEvent {
             id: json["id"].as_str().unwrap_or("").to_string(),
             title: json["subject"].as_str().unwrap_or("").to_string(),
             description: json["body"]["content"].as_str().map(|s| s.to_string()),
             start_time: DateTime::parse_from_rfc3339(json["start"]["dateTime"].as_str().unwrap_or(""))
-                .map_err(|e| format!("Invalid start time: {}", e))?.with_timezone(&Utc),
+                .map_err(|e| ThetaError::integration(format!("Invalid start time: {}", e)))?.with_timezone(&Utc),
             end_time: DateTime::parse_from_rfc3339(json["end"]["dateTime"].as_str().unwrap_or(""))
-                .map_err(|e| format!("Invalid end time: {}", e))?.with_timezone(&Utc),
+                .map_err(|e| ThetaError::integration(format!("Invalid end time: {}", e)))?.with_timezone(&Utc),
             location: json["location"]["displayName"].as_str().map(|s| s.to_string()),
             attendees: vec![],
             is_all_day: json["isAllDay"].as_bool().unwrap_or(false),
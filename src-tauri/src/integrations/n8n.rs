@@ -1,3 +1,4 @@
+use crate::error::ThetaError;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -44,7 +45,7 @@ impl N8nIntegration {
         }
     }
 
-    pub async fn test_connection(&self) -> Result<bool, String> {
+    pub async fn test_connection(&self) -> Result<bool, ThetaError> {
         let url = format!("{}/workflows", self.endpoint);
         
         let mut request = self.client.get(&url);
@@ -55,11 +56,11 @@ impl N8nIntegration {
         
         match request.send().await {
             Ok(response) => Ok(response.status().is_success()),
-            Err(e) => Err(format!("Connection test failed: {}", e)),
+            Err(e) => Err(ThetaError::integration_request("Connection test failed", e)),
         }
     }
 
-    pub async fn get_workflows(&self) -> Result<Vec<N8nWorkflow>, String> {
+    pub async fn get_workflows(&self) -> Result<Vec<N8nWorkflow>, ThetaError> {
         let url = format!("{}/workflows", self.endpoint);
         
         let mut request = self.client.get(&url);
@@ -69,10 +70,10 @@ impl N8nIntegration {
         }
         
         let response = request.send().await
-            .map_err(|e| format!("Failed to fetch workflows: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to fetch workflows", e))?;
         
         let json: Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
         
         let mut workflows = Vec::new();
         
@@ -94,7 +95,7 @@ impl N8nIntegration {
         Ok(workflows)
     }
 
-    pub async fn create_workflow(&self, name: &str, description: Option<&str>) -> Result<N8nWorkflow, String> {
+    pub async fn create_workflow(&self, name: &str, description: Option<&str>) -> Result<N8nWorkflow, ThetaError> {
         let url = format!("{}/workflows", self.endpoint);
         
         let workflow_data = serde_json::json!({
@@ -143,10 +144,10 @@ impl N8nIntegration {
         }
         
         let response = request.send().await
-            .map_err(|e| format!("Failed to create workflow: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to create workflow", e))?;
         
         let json: Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
         
         let workflow = N8nWorkflow {
             id: json["id"].as_str().unwrap_or("").to_string(),
@@ -161,7 +162,7 @@ impl N8nIntegration {
         Ok(workflow)
     }
 
-    pub async fn execute_workflow(&self, workflow_id: &str, data: Value) -> Result<N8nExecution, String> {
+    pub async fn execute_workflow(&self, workflow_id: &str, data: Value) -> Result<N8nExecution, ThetaError> {
         let url = format!("{}/workflows/{}/execute", self.endpoint, workflow_id);
         
         let mut request = self.client.post(&url).json(&data);
@@ -171,10 +172,10 @@ impl N8nIntegration {
         }
         
         let response = request.send().await
-            .map_err(|e| format!("Failed to execute workflow: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to execute workflow", e))?;
         
         let json: Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
         
         let execution = N8nExecution {
             id: json["id"].as_str().unwrap_or("").to_string(),
@@ -188,16 +189,16 @@ impl N8nIntegration {
         Ok(execution)
     }
 
-    pub async fn trigger_webhook(&self, webhook_url: &str, data: Value) -> Result<Value, String> {
+    pub async fn trigger_webhook(&self, webhook_url: &str, data: Value) -> Result<Value, ThetaError> {
         let response = self.client
             .post(webhook_url)
             .json(&data)
             .send()
             .await
-            .map_err(|e| format!("Failed to trigger webhook: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to trigger webhook", e))?;
         
         let json: Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
         
         Ok(json)
     }
@@ -1,3 +1,4 @@
+use crate::error::ThetaError;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -58,45 +59,45 @@ impl ProductivityIntegration {
     }
 
     // Task Management
-    pub async fn get_tasks(&self) -> Result<Vec<Task>, String> {
+    pub async fn get_tasks(&self) -> Result<Vec<Task>, ThetaError> {
         match self.provider.as_str() {
             "notion" => self.get_notion_tasks().await,
             "todoist" => self.get_todoist_tasks().await,
             "trello" => self.get_trello_tasks().await,
-            _ => Err("Unsupported productivity provider".to_string()),
+            _ => Err(ThetaError::NotConfigured("Unsupported productivity provider".to_string())),
         }
     }
 
-    pub async fn create_task(&self, task: CreateTask) -> Result<Task, String> {
+    pub async fn create_task(&self, task: CreateTask) -> Result<Task, ThetaError> {
         match self.provider.as_str() {
             "notion" => self.create_notion_task(task).await,
             "todoist" => self.create_todoist_task(task).await,
             "trello" => self.create_trello_task(task).await,
-            _ => Err("Unsupported productivity provider".to_string()),
+            _ => Err(ThetaError::NotConfigured("Unsupported productivity provider".to_string())),
         }
     }
 
     // Note Management
-    pub async fn get_notes(&self) -> Result<Vec<Note>, String> {
+    pub async fn get_notes(&self) -> Result<Vec<Note>, ThetaError> {
         match self.provider.as_str() {
             "notion" => self.get_notion_notes().await,
             "obsidian" => self.get_obsidian_notes().await,
-            _ => Err("Unsupported note provider".to_string()),
+            _ => Err(ThetaError::NotConfigured("Unsupported note provider".to_string())),
         }
     }
 
-    pub async fn create_note(&self, note: CreateNote) -> Result<Note, String> {
+    pub async fn create_note(&self, note: CreateNote) -> Result<Note, ThetaError> {
         match self.provider.as_str() {
             "notion" => self.create_notion_note(note).await,
             "obsidian" => self.create_obsidian_note(note).await,
-            _ => Err("Unsupported note provider".to_string()),
+            _ => Err(ThetaError::NotConfigured("Unsupported note provider".to_string())),
         }
     }
 
     // Notion Integration
-    async fn get_notion_tasks(&self) -> Result<Vec<Task>, String> {
-        let api_key = self.api_key.as_ref().ok_or("Notion API key not set")?;
-        let database_id = std::env::var("NOTION_TASKS_DATABASE_ID").map_err(|_| "Notion tasks database ID not set")?;
+    async fn get_notion_tasks(&self) -> Result<Vec<Task>, ThetaError> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| ThetaError::NotConfigured("Notion API key not set".to_string()))?;
+        let database_id = std::env::var("NOTION_TASKS_DATABASE_ID").map_err(|_| ThetaError::NotConfigured("Notion tasks database ID not set".to_string()))?;
         
         let url = format!("https://api.notion.com/v1/databases/{}/query", database_id);
         
@@ -107,10 +108,10 @@ impl ProductivityIntegration {
             .json(&serde_json::json!({}))
             .send()
             .await
-            .map_err(|e| format!("Failed to fetch Notion tasks: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to fetch Notion tasks", e))?;
         
         let json: Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
         
         let mut tasks = Vec::new();
         
@@ -134,9 +135,9 @@ impl ProductivityIntegration {
         Ok(tasks)
     }
 
-    async fn create_notion_task(&self, task: CreateTask) -> Result<Task, String> {
-        let api_key = self.api_key.as_ref().ok_or("Notion API key not set")?;
-        let database_id = std::env::var("NOTION_TASKS_DATABASE_ID").map_err(|_| "Notion tasks database ID not set")?;
+    async fn create_notion_task(&self, task: CreateTask) -> Result<Task, ThetaError> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| ThetaError::NotConfigured("Notion API key not set".to_string()))?;
+        let database_id = std::env::var("NOTION_TASKS_DATABASE_ID").map_err(|_| ThetaError::NotConfigured("Notion tasks database ID not set".to_string()))?;
         
         let url = "https://api.notion.com/v1/pages";
         
@@ -183,10 +184,10 @@ impl ProductivityIntegration {
             .json(&task_data)
             .send()
             .await
-            .map_err(|e| format!("Failed to create Notion task: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to create Notion task", e))?;
         
         let json: Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
         
         let created_task = Task {
             id: json["id"].as_str().unwrap_or("").to_string(),
@@ -201,9 +202,9 @@ impl ProductivityIntegration {
         Ok(created_task)
     }
 
-    async fn get_notion_notes(&self) -> Result<Vec<Note>, String> {
-        let api_key = self.api_key.as_ref().ok_or("Notion API key not set")?;
-        let database_id = std::env::var("NOTION_NOTES_DATABASE_ID").map_err(|_| "Notion notes database ID not set")?;
+    async fn get_notion_notes(&self) -> Result<Vec<Note>, ThetaError> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| ThetaError::NotConfigured("Notion API key not set".to_string()))?;
+        let database_id = std::env::var("NOTION_NOTES_DATABASE_ID").map_err(|_| ThetaError::NotConfigured("Notion notes database ID not set".to_string()))?;
         
         let url = format!("https://api.notion.com/v1/databases/{}/query", database_id);
         
@@ -214,10 +215,10 @@ impl ProductivityIntegration {
             .json(&serde_json::json!({}))
             .send()
             .await
-            .map_err(|e| format!("Failed to fetch Notion notes: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to fetch Notion notes", e))?;
         
         let json: Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
         
         let mut notes = Vec::new();
         
@@ -228,9 +229,9 @@ impl ProductivityIntegration {
                     title: result["properties"]["Title"]["title"][0]["text"]["content"].as_str().unwrap_or("").to_string(),
                     content: result["properties"]["Content"]["rich_text"][0]["text"]["content"].as_str().unwrap_or("").to_string(),
                     created_at: DateTime::parse_from_rfc3339(result["created_time"].as_str().unwrap_or(""))
-                        .map_err(|e| format!("Invalid created time: {}", e))?.with_timezone(&Utc),
+                        .map_err(|e| ThetaError::integration(format!("Invalid created time: {}", e)))?.with_timezone(&Utc),
                     updated_at: DateTime::parse_from_rfc3339(result["last_edited_time"].as_str().unwrap_or(""))
-                        .map_err(|e| format!("Invalid updated time: {}", e))?.with_timezone(&Utc),
+                        .map_err(|e| ThetaError::integration(format!("Invalid updated time: {}", e)))?.with_timezone(&Utc),
                     tags: vec![],
                 };
                 notes.push(note);
@@ -240,9 +241,9 @@ impl ProductivityIntegration {
         Ok(notes)
     }
 
-    async fn create_notion_note(&self, note: CreateNote) -> Result<Note, String> {
-        let api_key = self.api_key.as_ref().ok_or("Notion API key not set")?;
-        let database_id = std::env::var("NOTION_NOTES_DATABASE_ID").map_err(|_| "Notion notes database ID not set")?;
+    async fn create_notion_note(&self, note: CreateNote) -> Result<Note, ThetaError> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| ThetaError::NotConfigured("Notion API key not set".to_string()))?;
+        let database_id = std::env::var("NOTION_NOTES_DATABASE_ID").map_err(|_| ThetaError::NotConfigured("Notion notes database ID not set".to_string()))?;
         
         let url = "https://api.notion.com/v1/pages";
         
@@ -279,10 +280,10 @@ impl ProductivityIntegration {
             .json(&note_data)
             .send()
             .await
-            .map_err(|e| format!("Failed to create Notion note: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to create Notion note", e))?;
         
         let json: Value = response.json().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| ThetaError::integration_request("Failed to parse response", e))?;
         
         let created_note = Note {
             id: json["id"].as_str().unwrap_or("").to_string(),
@@ -297,34 +298,34 @@ impl ProductivityIntegration {
     }
 
     // Placeholder implementations for other providers
-    async fn get_todoist_tasks(&self) -> Result<Vec<Task>, String> {
+    async fn get_todoist_tasks(&self) -> Result<Vec<Task>, ThetaError> {
         // Implement Todoist API integration
         Ok(vec![])
     }
 
-    async fn create_todoist_task(&self, _task: CreateTask) -> Result<Task, String> {
+    async fn create_todoist_task(&self, _task: CreateTask) -> Result<Task, ThetaError> {
         // Implement Todoist API integration
-        Err("Todoist integration not implemented".to_string())
+        Err(ThetaError::integration("Todoist integration not implemented"))
     }
 
-    async fn get_trello_tasks(&self) -> Result<Vec<Task>, String> {
+    async fn get_trello_tasks(&self) -> Result<Vec<Task>, ThetaError> {
         // Implement Trello API integration
         Ok(vec![])
     }
 
-    async fn create_trello_task(&self, _task: CreateTask) -> Result<Task, String> {
+    async fn create_trello_task(&self, _task: CreateTask) -> Result<Task, ThetaError> {
         // Implement Trello API integration
-        Err("Trello integration not implemented".to_string())
+        Err(ThetaError::integration("Trello integration not implemented"))
     }
 
-    async fn get_obsidian_notes(&self) -> Result<Vec<Note>, String> {
+    async fn get_obsidian_notes(&self) -> Result<Vec<Note>, ThetaError> {
         // Implement Obsidian local file integration
         Ok(vec![])
     }
 
-    async fn create_obsidian_note(&self, _note: CreateNote) -> Result<Note, String> {
+    async fn create_obsidian_note(&self, _note: CreateNote) -> Result<Note, ThetaError> {
         // Implement Obsidian local file integration
-        Err("Obsidian integration not implemented".to_string())
+        Err(ThetaError::integration("Obsidian integration not implemented"))
     }
 }
 
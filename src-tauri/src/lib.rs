@@ -8,7 +8,9 @@ mod database;
 mod integrations;
 mod models;
 mod commands;
+mod error;
 mod state;
+mod search;
 
 use state::AppState;
 
@@ -39,6 +41,40 @@ pub fn run() {
             commands::ai::analyze_content,
             commands::ai::generate_summary,
             commands::ai::chat_with_ai,
+            commands::ai::stream_chat_with_ai,
+            commands::ai::cancel_ai_stream,
+            commands::ai::list_ai_providers,
+            commands::ai::list_ai_models,
+            commands::ai::set_privacy_mode,
+            commands::ai::set_ai_provider_priority,
+            commands::ai::get_ai_usage,
+            commands::ai::get_ai_cache_stats,
+            commands::ai::purge_ai_cache,
+            commands::analysis::get_latest_analysis,
+            commands::analysis::get_analysis_history,
+            commands::analysis::diff_page_analyses,
+            commands::prompts::list_prompt_templates,
+            commands::prompts::save_prompt_template,
+            commands::prompts::reset_prompt_template,
+            commands::prompts::reload_prompt_templates,
+            commands::conversations::create_conversation,
+            commands::conversations::list_conversations,
+            commands::conversations::get_conversation,
+            commands::conversations::append_conversation_message,
+            commands::conversations::send_conversation_message,
+            commands::conversations::set_conversation_context_strategy,
+            commands::conversations::rename_conversation,
+            commands::conversations::delete_conversation,
+            commands::tabs::chat_with_tab,
+            commands::workspaces::ask_workspace,
+            commands::agent::run_browser_agent,
+            commands::agent::confirm_tool_call,
+            commands::runs::list_ai_runs,
+            commands::runs::get_ai_run,
+            commands::runs::replay_ai_run,
+            commands::search::semantic_search,
+            commands::search::hybrid_search,
+            commands::search::index_page,
             commands::browser::navigate_to_url,
             commands::browser::search_web,
             commands::browser::bookmark_page,
@@ -63,14 +99,26 @@ pub fn run() {
         .expect("error while running tauri application");
 }
 
-async fn initialize_app_state(app_handle: tauri::AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+async fn initialize_app_state(app_handle: tauri::AppHandle) -> Result<(), error::ThetaError> {
     log::info!("Initializing Theta Browser...");
     
     let state = app_handle.state::<Arc<RwLock<AppState>>>();
     let mut app_state = state.write().await;
     
     app_state.initialize_database().await?;
+    if let Err(e) = app_state.initialize_preferences().await {
+        log::warn!("Saved preferences were not loaded: {}", e);
+    }
     app_state.initialize_ai_services().await?;
+    let prompts_dir = app_handle.path().app_config_dir().ok().map(|dir| dir.join("prompts"));
+    app_state.initialize_prompts(prompts_dir).await?;
+    let run_log_dir = app_handle.path().app_data_dir().ok().map(|dir| dir.join("ai-runs"));
+    if let Err(e) = app_state.initialize_run_log(run_log_dir).await {
+        log::warn!("AI calls will not be recorded: {}", e);
+    }
+    if let Err(e) = app_state.initialize_semantic_index().await {
+        log::warn!("Keeping the semantic index in memory with local embeddings: {}", e);
+    }
     app_state.initialize_integrations().await?;
     
     log::info!("Theta Browser initialized successfully");
@@ -1,3 +1,4 @@
+use crate::error::ErrorInfo;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -14,9 +15,238 @@ pub struct AIRequest {
 pub struct AIResponse {
     pub content: String,
     pub model: String,
+    /// Which provider produced the response, which may differ from the one
+    /// requested after a failover.
+    #[serde(default)]
+    pub provider: String,
+    /// Prompt tokens billed by the provider.
+    #[serde(default)]
+    pub input_tokens: u32,
+    /// Completion tokens billed by the provider.
+    #[serde(default)]
+    pub output_tokens: u32,
+    /// Input plus output tokens.
     pub tokens_used: u32,
+    /// Estimated price in USD; `None` when the model is not in the price table.
+    #[serde(default)]
+    pub cost_usd: Option<f64>,
     pub processing_time: u64,
     pub confidence: Option<f32>,
+    /// Tools the model asked to run; the content may then be empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// The run log entry this response was recorded under.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    /// The provider's response body as received, kept for the run log.
+    #[serde(skip)]
+    pub raw: Option<serde_json::Value>,
+}
+
+/// A model's request to run a tool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Identifies the call when its result is sent back.
+    pub id: String,
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+/// What a completion was requested for, so usage can be broken down by feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AIFeature {
+    #[default]
+    Chat,
+    Conversation,
+    ConversationSummary,
+    Summary,
+    Analysis,
+    TabChat,
+    WorkspaceChat,
+    Agent,
+}
+
+/// Usage for one day, provider and feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AIUsageSummary {
+    pub day: chrono::NaiveDate,
+    pub provider: String,
+    pub feature: AIFeature,
+    pub requests: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub total_tokens: i64,
+    pub cost_usd: f64,
+    /// Requests whose model had no known price and so are not in `cost_usd`.
+    pub unpriced_requests: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AICacheStats {
+    /// `redis`, or `memory` when Redis is not reachable.
+    pub backend: String,
+    pub entries: u64,
+    pub ttl_seconds: u64,
+    /// Lookups since startup or the last purge.
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// An answer about an open tab, grounded in the page's text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabAnswer {
+    pub tab_id: Uuid,
+    pub answer: String,
+    pub citations: Vec<TabCitation>,
+    /// The tab's running conversation summary after this answer.
+    pub ai_context: Option<String>,
+    pub provider: String,
+    pub model: String,
+}
+
+/// An answer drawn from the text of every tab in a workspace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceAnswer {
+    pub workspace_id: Uuid,
+    pub answer: String,
+    pub citations: Vec<TabCitation>,
+    /// Tabs left out because their page could not be read.
+    pub skipped_tabs: Vec<SkippedTab>,
+    /// The workspace's running conversation summary after this answer.
+    pub ai_context: Option<String>,
+    pub provider: String,
+    pub model: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedTab {
+    pub tab_id: Uuid,
+    pub url: String,
+    pub error: String,
+}
+
+/// A passage of a page cited in an answer as `[passage]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabCitation {
+    pub passage: usize,
+    /// The tab the passage came from.
+    pub tab_id: Uuid,
+    pub title: String,
+    pub text: String,
+    /// The page URL with a text fragment pointing at the passage.
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AIProviderInfo {
+    pub name: String,
+    pub models: Vec<String>,
+    pub is_local: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AIStreamEvent {
+    Chunk { request_id: String, delta: String },
+    Done { request_id: String, response: AIResponse },
+    Error { request_id: String, error: ErrorInfo },
+    Cancelled { request_id: String },
+}
+
+/// What became of a tool call the model asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallStatus {
+    Completed,
+    /// The user did not confirm a tool with side effects.
+    Declined,
+    /// The tool is unknown, its arguments were invalid or it failed.
+    Failed,
+}
+
+/// One tool call of an agent run and what it returned to the model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolStep {
+    pub call: ToolCall,
+    pub status: ToolCallStatus,
+    pub output: serde_json::Value,
+}
+
+/// The outcome of a request the AI carried out with browser tools.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentRun {
+    pub answer: String,
+    pub steps: Vec<ToolStep>,
+    /// Whether the run stopped at the step limit while the model still
+    /// wanted to call tools.
+    pub hit_step_limit: bool,
+    pub provider: String,
+    pub model: String,
+    /// The run log entry holding every provider call of the run.
+    pub run_id: Option<String>,
+}
+
+/// Sent as an `agent-step` event after each tool call of a run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentStepEvent {
+    pub request_id: String,
+    pub step: ToolStep,
+}
+
+/// Sent as a `tool-confirmation` event before a tool with side effects
+/// runs; answered with `confirm_tool_call`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolConfirmationRequest {
+    pub request_id: String,
+    pub confirmation_id: String,
+    pub tool: String,
+    pub description: String,
+    pub arguments: serde_json::Value,
+}
+
+/// A run in the AI run log: the provider calls made for one request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AIRunSummary {
+    pub run_id: String,
+    pub feature: AIFeature,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub calls: usize,
+    pub failed_calls: usize,
+    /// Providers called, in the order first used.
+    pub providers: Vec<String>,
+    pub total_tokens: u32,
+    pub latency_ms: u64,
+}
+
+/// The outcome of re-executing a recorded run against its recorded responses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AIRunReplay {
+    pub run_id: String,
+    /// Successful calls in the recording.
+    pub recorded_calls: usize,
+    /// Calls the replay made.
+    pub replayed_calls: usize,
+    /// Where the replay sent something other than what was recorded. Empty
+    /// when the run reproduced exactly.
+    pub divergences: Vec<String>,
+    pub answer: Option<String>,
+}
+
+/// Stage of a long-page summary: summarizing chunks, then combining them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryStage {
+    Map,
+    Reduce,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummaryProgressEvent {
+    pub request_id: String,
+    pub stage: SummaryStage,
+    pub completed: usize,
+    pub total: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,8 +255,59 @@ pub struct ContentAnalysis {
     pub keywords: Vec<String>,
     pub sentiment: f32,
     pub topics: Vec<String>,
+    #[serde(default)]
     pub reading_time: u32,
     pub complexity_score: f32,
+    #[serde(default)]
+    pub source: AnalysisSource,
+    /// Hash of the normalized content that was analyzed.
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    /// Model that produced the analysis; unset for heuristic analyses.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Provider that produced the analysis; unset for heuristic analyses.
+    #[serde(default)]
+    pub provider: Option<String>,
+}
+
+/// An analysis saved for a page, one per distinct version of its content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredAnalysis {
+    pub id: Uuid,
+    pub url: String,
+    pub title: Option<String>,
+    pub content_hash: Option<String>,
+    pub model: Option<String>,
+    pub analysis: ContentAnalysis,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// How a page's analysis changed between two saved versions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisDiff {
+    pub previous: StoredAnalysis,
+    pub current: StoredAnalysis,
+    pub content_changed: bool,
+    pub summary_changed: bool,
+    pub keywords_added: Vec<String>,
+    pub keywords_removed: Vec<String>,
+    pub topics_added: Vec<String>,
+    pub topics_removed: Vec<String>,
+    /// Current minus previous.
+    pub sentiment_change: f32,
+    pub complexity_change: f32,
+    pub reading_time_change: i64,
+}
+
+/// Whether an analysis came from a model or from the local word-count heuristics
+/// used when no provider is available.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalysisSource {
+    #[default]
+    Model,
+    Heuristic,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,11 +327,33 @@ pub struct SearchResult {
     pub metadata: serde_json::Value,
 }
 
+/// Where an indexed document came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentSource {
+    Page,
+    Bookmark,
+}
+
+/// A page or bookmark whose text is close in meaning to a search query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticMatch {
+    pub url: String,
+    pub title: String,
+    pub source: DocumentSource,
+    /// The best matching chunk of the document.
+    pub snippet: String,
+    /// Cosine similarity of that chunk to the query.
+    pub score: f32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Bookmark {
     pub id: Uuid,
     pub title: String,
     pub url: String,
+    #[serde(default)]
+    pub description: Option<String>,
     pub tags: Vec<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub folder: Option<String>,
@@ -71,14 +374,67 @@ pub struct UserData {
 pub struct WebPageContent {
     pub url: String,
     pub title: String,
+    /// The article as plain text; this is what gets cached and indexed.
     pub content: String,
+    /// The article as Markdown, when the caller asked for it.
+    pub markdown: Option<String>,
     pub html: String,
-    pub links: Vec<String>,
-    pub images: Vec<String>,
+    /// Objects rather than the bare URL strings `get_page_content` used to
+    /// return; read `url` for the old value.
+    pub links: Vec<PageLink>,
+    pub images: Vec<PageImage>,
+    /// A `browser::metadata::PageMetadata`: OpenGraph, Twitter card,
+    /// canonical URL, alternates, icons, feeds and JSON-LD.
     pub metadata: serde_json::Value,
+    pub byline: Option<String>,
+    pub published_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub lead_image: Option<String>,
     pub extracted_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// How extracted page content is written out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentFormat {
+    /// Whitespace-collapsed plain text.
+    #[default]
+    Text,
+    /// Markdown keeping headings, lists, code blocks, tables and links.
+    Markdown,
+}
+
+/// Where a link on a page leads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+    /// Another page on the same site.
+    Internal,
+    External,
+    /// A fragment of the page itself.
+    Anchor,
+    /// A file, marked `download` or by its extension.
+    Download,
+    Mailto,
+    /// Any other scheme, such as `tel:` or `ftp:`.
+    Other,
+}
+
+/// A link on a page, resolved to an absolute URL.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageLink {
+    pub url: String,
+    pub text: String,
+    pub kind: LinkKind,
+    pub nofollow: bool,
+}
+
+/// An image on a page, resolved to an absolute URL.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageImage {
+    pub url: String,
+    pub alt: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct N8nWorkflow {
     pub id: String,
@@ -125,13 +481,33 @@ pub struct Workspace {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AIChat {
     pub id: Uuid,
+    pub title: Option<String>,
     pub messages: Vec<ChatMessage>,
     pub context: Option<String>,
     pub model: String,
+    pub provider: String,
+    pub context_strategy: ContextStrategy,
+    pub rolling_summary: Option<RollingSummary>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// How a conversation is trimmed once its history outgrows the model's context window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextStrategy {
+    #[default]
+    DropOldest,
+    RollingSummary,
+}
+
+/// Summary standing in for the first `message_count` non-system messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollingSummary {
+    pub content: String,
+    pub message_count: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub id: Uuid,
@@ -148,6 +524,91 @@ pub enum MessageRole {
     System,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub id: Uuid,
+    pub title: Option<String>,
+    pub model: String,
+    pub provider: String,
+    pub message_count: i32,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl ChatMessage {
+    pub fn new(role: MessageRole, content: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            role,
+            content,
+            timestamp: chrono::Utc::now(),
+            metadata: None,
+        }
+    }
+}
+
+impl ContextStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContextStrategy::DropOldest => "drop_oldest",
+            ContextStrategy::RollingSummary => "rolling_summary",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "rolling_summary" => ContextStrategy::RollingSummary,
+            _ => ContextStrategy::DropOldest,
+        }
+    }
+}
+
+impl AIFeature {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AIFeature::Chat => "chat",
+            AIFeature::Conversation => "conversation",
+            AIFeature::ConversationSummary => "conversation_summary",
+            AIFeature::Summary => "summary",
+            AIFeature::Analysis => "analysis",
+            AIFeature::TabChat => "tab_chat",
+            AIFeature::WorkspaceChat => "workspace_chat",
+            AIFeature::Agent => "agent",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "conversation" => AIFeature::Conversation,
+            "conversation_summary" => AIFeature::ConversationSummary,
+            "summary" => AIFeature::Summary,
+            "analysis" => AIFeature::Analysis,
+            "tab_chat" => AIFeature::TabChat,
+            "workspace_chat" => AIFeature::WorkspaceChat,
+            "agent" => AIFeature::Agent,
+            _ => AIFeature::Chat,
+        }
+    }
+}
+
+impl DocumentSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentSource::Page => "page",
+            DocumentSource::Bookmark => "bookmark",
+        }
+    }
+}
+
+impl MessageRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageRole::User => "user",
+            MessageRole::Assistant => "assistant",
+            MessageRole::System => "system",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserPreferences {
     pub theme: String,
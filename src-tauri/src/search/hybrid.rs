@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+/// Damps the difference between top ranks so one list cannot dominate; 60
+/// is the value from the original reciprocal rank fusion paper.
+pub const RRF_K: f32 = 60.0;
+
+/// Relevance of the item at 1-based `rank` in a single ranked list: the
+/// reciprocal rank `1 / (k + rank)` scaled by `k + 1`, so the first item
+/// scores 1.0.
+pub fn rank_score(rank: usize) -> f32 {
+    (RRF_K + 1.0) / (RRF_K + rank as f32)
+}
+
+/// Fuses ranked lists of keys with reciprocal rank fusion. Scores are
+/// normalized so that an item ranked first in every list scores 1.0 and
+/// one missing from a list gets nothing from it. Best first; ties keep the
+/// order in which items were first seen.
+pub fn reciprocal_rank_fusion(lists: &[Vec<String>]) -> Vec<(String, f32)> {
+    if lists.is_empty() {
+        return Vec::new();
+    }
+
+    let mut order: Vec<String> = Vec::new();
+    let mut scores: HashMap<String, f32> = HashMap::new();
+    for list in lists {
+        for (index, key) in list.iter().enumerate() {
+            // Only the best rank of a key within one list counts
+            if list[..index].contains(key) {
+                continue;
+            }
+            let score = scores.entry(key.clone()).or_insert_with(|| {
+                order.push(key.clone());
+                0.0
+            });
+            *score += rank_score(index + 1) / lists.len() as f32;
+        }
+    }
+
+    let mut fused: Vec<(String, f32)> = order
+        .into_iter()
+        .map(|key| {
+            let score = scores[&key];
+            (key, score)
+        })
+        .collect();
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1));
+    fused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn single_list_scores_follow_rank() {
+        assert_eq!(rank_score(1), 1.0);
+        assert!(rank_score(2) < rank_score(1));
+        assert!(rank_score(10) > 0.8);
+
+        let fused = reciprocal_rank_fusion(&[keys(&["a", "b", "c"])]);
+        assert_eq!(fused.iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        assert_eq!(fused[0].1, 1.0);
+    }
+
+    #[test]
+    fn items_found_by_both_lists_rise() {
+        let full_text = keys(&["a", "b", "c"]);
+        let semantic = keys(&["d", "c", "b"]);
+
+        let fused = reciprocal_rank_fusion(&[full_text, semantic]);
+        let order: Vec<&str> = fused.iter().map(|(key, _)| key.as_str()).collect();
+
+        assert_eq!(order, vec!["b", "c", "a", "d"]);
+        assert!(fused.iter().all(|(_, score)| *score > 0.0 && *score < 1.0));
+    }
+
+    #[test]
+    fn duplicates_within_a_list_count_once() {
+        let fused = reciprocal_rank_fusion(&[keys(&["a", "a", "b"])]);
+
+        assert_eq!(fused, vec![("a".to_string(), 1.0), ("b".to_string(), rank_score(3))]);
+    }
+}
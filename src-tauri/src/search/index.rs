@@ -0,0 +1,274 @@
+use super::memory::MemoryVectorStore;
+use super::qdrant::QdrantVectorStore;
+use super::vector_store::{ChunkPayload, VectorPoint, VectorStore};
+use crate::ai::cache::content_hash;
+use crate::ai::context;
+use crate::ai::embeddings::{embedding_provider_from_env, EmbeddingProvider, HashEmbeddings};
+use crate::ai::summarize::split_into_chunks;
+use crate::browser::ContentExtractor;
+use crate::error::ThetaError;
+use crate::models::{DocumentSource, SemanticMatch};
+use reqwest::Client;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Size of an embedded chunk.
+const CHUNK_TOKENS: u32 = 256;
+
+/// Chunks sent to the embedding provider per request.
+const EMBED_BATCH: usize = 32;
+
+/// Chunks fetched per requested result, so a document with many matching
+/// chunks does not crowd out the others.
+const CANDIDATES_PER_RESULT: usize = 4;
+
+const SNIPPET_CHARS: usize = 300;
+
+/// Text to add to the index.
+#[derive(Debug, Clone)]
+pub struct IndexDocument {
+    pub url: String,
+    pub title: String,
+    pub source: DocumentSource,
+    pub content: String,
+}
+
+/// Visited pages and bookmarks, split into chunks and embedded for search
+/// by meaning rather than by exact words.
+#[derive(Clone)]
+pub struct SemanticIndex {
+    embedder: Arc<dyn EmbeddingProvider>,
+    store: Arc<dyn VectorStore>,
+    /// Content hash last indexed per URL, so revisiting an unchanged page
+    /// does not embed it again.
+    indexed: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl SemanticIndex {
+    pub fn new(embedder: Arc<dyn EmbeddingProvider>, store: Arc<dyn VectorStore>) -> Self {
+        Self { embedder, store, indexed: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    /// Hashed-word embeddings in an in-process store; needs no services.
+    pub fn in_memory() -> Self {
+        Self::new(Arc::new(HashEmbeddings::default()), Arc::new(MemoryVectorStore::new()))
+    }
+
+    /// Uses the embedding provider chosen by `EMBEDDING_PROVIDER` and Qdrant
+    /// at `QDRANT_URL`, falling back to the in-process store when Qdrant is
+    /// not configured or not reachable.
+    pub async fn from_env(client: &Client) -> Result<Self, ThetaError> {
+        let embedder = embedding_provider_from_env(client)?;
+
+        let store: Arc<dyn VectorStore> = match std::env::var("QDRANT_URL").ok().filter(|url| !url.trim().is_empty()) {
+            Some(endpoint) => {
+                let qdrant = QdrantVectorStore::new(
+                    client.clone(),
+                    endpoint,
+                    std::env::var("QDRANT_API_KEY").ok(),
+                    QdrantVectorStore::collection_for(embedder.model()),
+                );
+                if qdrant.is_reachable().await {
+                    Arc::new(qdrant)
+                } else {
+                    log::warn!("Qdrant is not reachable; keeping the semantic index in memory");
+                    Arc::new(MemoryVectorStore::new())
+                }
+            }
+            None => Arc::new(MemoryVectorStore::new()),
+        };
+
+        log::info!(
+            "Semantic index: {} embeddings ({}) in the {} store",
+            embedder.name(),
+            embedder.model(),
+            store.name()
+        );
+        Ok(Self::new(embedder, store))
+    }
+
+    pub fn embedder(&self) -> &str {
+        self.embedder.name()
+    }
+
+    pub fn store(&self) -> &str {
+        self.store.name()
+    }
+
+    /// Whether indexing and searching keep page text on this machine or the
+    /// local network, which is what privacy mode allows.
+    pub fn is_local(&self) -> bool {
+        self.embedder.is_local() && self.store.is_local()
+    }
+
+    /// Replaces the indexed chunks of a document. Returns the number of
+    /// chunks embedded, which is 0 when the content has not changed since it
+    /// was last indexed.
+    pub async fn index_document(&self, document: &IndexDocument) -> Result<usize, ThetaError> {
+        let hash = content_hash(&document.content);
+        if self.indexed.lock().await.get(&document.url) == Some(&hash) {
+            return Ok(0);
+        }
+
+        let chunks = split_into_chunks(&document.content, CHUNK_TOKENS, &context::count_tokens);
+        let mut points = Vec::with_capacity(chunks.len());
+        for (batch_index, batch) in chunks.chunks(EMBED_BATCH).enumerate() {
+            let vectors = self.embedder.embed(batch).await?;
+            for (offset, (text, vector)) in batch.iter().zip(vectors).enumerate() {
+                let chunk = batch_index * EMBED_BATCH + offset;
+                points.push(VectorPoint {
+                    id: point_id(&document.url, chunk),
+                    vector,
+                    payload: ChunkPayload {
+                        url: document.url.clone(),
+                        title: document.title.clone(),
+                        source: document.source,
+                        chunk,
+                        text: text.clone(),
+                    },
+                });
+            }
+        }
+
+        let count = points.len();
+        self.store.delete_url(&document.url).await?;
+        self.store.upsert(points).await?;
+        self.indexed.lock().await.insert(document.url.clone(), hash);
+
+        Ok(count)
+    }
+
+    /// Fetches and extracts a page, then indexes its text.
+    pub async fn index_url(
+        &self,
+        extractor: &ContentExtractor,
+        url: &str,
+        source: DocumentSource,
+    ) -> Result<usize, ThetaError> {
+        let extracted = extractor.extract_from_url(url).await?;
+
+        self.index_document(&IndexDocument {
+            url: url.to_string(),
+            title: extracted.title,
+            source,
+            content: extracted.content,
+        })
+        .await
+    }
+
+    /// The documents closest in meaning to `query`, best first, each with
+    /// its best matching chunk.
+    pub async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        source: Option<DocumentSource>,
+    ) -> Result<Vec<SemanticMatch>, ThetaError> {
+        let vector = self
+            .embedder
+            .embed(&[query.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| ThetaError::search("No embedding returned for the query"))?;
+
+        let candidates = self.store.search(&vector, limit * CANDIDATES_PER_RESULT, source).await?;
+
+        // Candidates arrive best first, so the first chunk seen per URL is its best
+        let mut matches: Vec<SemanticMatch> = Vec::new();
+        for candidate in candidates {
+            if matches.len() == limit {
+                break;
+            }
+            if matches.iter().any(|found| found.url == candidate.payload.url) {
+                continue;
+            }
+            matches.push(SemanticMatch {
+                url: candidate.payload.url,
+                title: candidate.payload.title,
+                source: candidate.payload.source,
+                snippet: snippet(&candidate.payload.text),
+                score: candidate.score,
+            });
+        }
+
+        Ok(matches)
+    }
+
+    pub async fn count(&self) -> Result<usize, ThetaError> {
+        self.store.count().await
+    }
+}
+
+/// The same id for the same chunk of the same URL, so re-indexing
+/// overwrites rather than duplicates.
+fn point_id(url: &str, chunk: usize) -> Uuid {
+    let digest = Sha256::digest(format!("{}#{}", url, chunk).as_bytes());
+    uuid::Builder::from_random_bytes(digest[..16].try_into().unwrap()).into_uuid()
+}
+
+fn snippet(text: &str) -> String {
+    match text.char_indices().nth(SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}...", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(url: &str, source: DocumentSource, content: &str) -> IndexDocument {
+        IndexDocument {
+            url: url.to_string(),
+            title: url.to_string(),
+            source,
+            content: content.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_the_closest_document_once() {
+        let index = SemanticIndex::in_memory();
+        index
+            .index_document(&document(
+                "https://doc.rust-lang.org/book/ch04",
+                DocumentSource::Page,
+                "Ownership rules.\n\nThe borrow checker enforces ownership and lifetimes.\n\nReferences borrow values.",
+            ))
+            .await
+            .unwrap();
+        index
+            .index_document(&document(
+                "https://example.com/bread",
+                DocumentSource::Bookmark,
+                "Sourdough bread needs a starter, flour and water.",
+            ))
+            .await
+            .unwrap();
+
+        let matches = index.search("how does the borrow checker handle lifetimes", 5, None).await.unwrap();
+
+        assert_eq!(matches[0].url, "https://doc.rust-lang.org/book/ch04");
+        assert_eq!(matches.iter().filter(|found| found.url == matches[0].url).count(), 1);
+
+        let bookmarks = index.search("bread", 5, Some(DocumentSource::Bookmark)).await.unwrap();
+        assert_eq!(bookmarks.len(), 1);
+        assert_eq!(bookmarks[0].source, DocumentSource::Bookmark);
+    }
+
+    #[tokio::test]
+    async fn reindexing_replaces_chunks_and_skips_unchanged_content() {
+        let index = SemanticIndex::in_memory();
+        let mut page = document("https://example.com", DocumentSource::Page, "First version.\n\nWith two paragraphs.");
+
+        assert!(index.index_document(&page).await.unwrap() > 0);
+        assert_eq!(index.index_document(&page).await.unwrap(), 0);
+
+        page.content = "Second version.".to_string();
+        assert_eq!(index.index_document(&page).await.unwrap(), 1);
+        assert_eq!(index.count().await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,113 @@
+use super::vector_store::{cosine_similarity, ScoredPoint, VectorPoint, VectorStore};
+use crate::error::ThetaError;
+use crate::models::DocumentSource;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// An in-process vector store that searches by brute force. Used when
+/// Qdrant is not configured or not reachable; its contents last until the
+/// app exits.
+#[derive(Default)]
+pub struct MemoryVectorStore {
+    points: RwLock<HashMap<Uuid, VectorPoint>>,
+}
+
+impl MemoryVectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl VectorStore for MemoryVectorStore {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    async fn upsert(&self, points: Vec<VectorPoint>) -> Result<(), ThetaError> {
+        let mut stored = self.points.write().await;
+        for point in points {
+            stored.insert(point.id, point);
+        }
+        Ok(())
+    }
+
+    async fn delete_url(&self, url: &str) -> Result<(), ThetaError> {
+        self.points.write().await.retain(|_, point| point.payload.url != url);
+        Ok(())
+    }
+
+    async fn search(
+        &self,
+        vector: &[f32],
+        limit: usize,
+        source: Option<DocumentSource>,
+    ) -> Result<Vec<ScoredPoint>, ThetaError> {
+        let stored = self.points.read().await;
+
+        let mut scored: Vec<ScoredPoint> = stored
+            .values()
+            .filter(|point| source.is_none_or(|source| point.payload.source == source))
+            .map(|point| ScoredPoint {
+                id: point.id,
+                score: cosine_similarity(vector, &point.vector),
+                payload: point.payload.clone(),
+            })
+            .collect();
+        scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+        scored.truncate(limit);
+
+        Ok(scored)
+    }
+
+    async fn count(&self) -> Result<usize, ThetaError> {
+        Ok(self.points.read().await.len())
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::ChunkPayload;
+
+    fn point(url: &str, source: DocumentSource, vector: Vec<f32>) -> VectorPoint {
+        VectorPoint {
+            id: Uuid::new_v4(),
+            vector,
+            payload: ChunkPayload {
+                url: url.to_string(),
+                title: url.to_string(),
+                source,
+                chunk: 0,
+                text: String::new(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn searches_by_similarity_and_source() {
+        let store = MemoryVectorStore::new();
+        store
+            .upsert(vec![
+                point("a", DocumentSource::Page, vec![1.0, 0.0]),
+                point("b", DocumentSource::Bookmark, vec![0.8, 0.6]),
+                point("c", DocumentSource::Page, vec![0.0, 1.0]),
+            ])
+            .await
+            .unwrap();
+
+        let urls = |points: Vec<ScoredPoint>| points.into_iter().map(|point| point.payload.url).collect::<Vec<_>>();
+
+        assert_eq!(urls(store.search(&[1.0, 0.0], 2, None).await.unwrap()), vec!["a", "b"]);
+        assert_eq!(urls(store.search(&[1.0, 0.0], 5, Some(DocumentSource::Page)).await.unwrap()), vec!["a", "c"]);
+
+        store.delete_url("a").await.unwrap();
+        assert_eq!(store.count().await.unwrap(), 2);
+    }
+}
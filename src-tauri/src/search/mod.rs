@@ -0,0 +1,10 @@
+pub mod vector_store;
+pub mod memory;
+pub mod qdrant;
+pub mod index;
+pub mod hybrid;
+
+pub use vector_store::*;
+pub use memory::MemoryVectorStore;
+pub use qdrant::QdrantVectorStore;
+pub use index::SemanticIndex;
@@ -0,0 +1,198 @@
+use super::vector_store::{ChunkPayload, ScoredPoint, VectorPoint, VectorStore};
+use crate::ai::providers::is_local_endpoint;
+use crate::error::ThetaError;
+use crate::models::DocumentSource;
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use uuid::Uuid;
+
+/// How long startup waits for Qdrant before using the in-process store.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Client for Qdrant's REST API. The collection is created on first write,
+/// sized for the vectors written to it.
+pub struct QdrantVectorStore {
+    client: Client,
+    endpoint: String,
+    api_key: Option<String>,
+    collection: String,
+    created: OnceCell<()>,
+}
+
+impl QdrantVectorStore {
+    pub fn new(client: Client, endpoint: String, api_key: Option<String>, collection: String) -> Self {
+        Self {
+            client,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            api_key: api_key.filter(|key| !key.trim().is_empty()),
+            collection,
+            created: OnceCell::new(),
+        }
+    }
+
+    /// A collection name for vectors from one embedding model, since vectors
+    /// from different models cannot be compared.
+    pub fn collection_for(model: &str) -> String {
+        let model: String = model
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+            .collect();
+        format!("theta_pages_{}", model)
+    }
+
+    /// Whether the server answers at all.
+    pub async fn is_reachable(&self) -> bool {
+        let request = self.request(self.client.get(format!("{}/collections", self.endpoint)));
+        matches!(
+            tokio::time::timeout(CONNECT_TIMEOUT, request.send()).await,
+            Ok(Ok(response)) if response.status().is_success()
+        )
+    }
+
+    fn request(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.api_key {
+            Some(key) => request.header("api-key", key),
+            None => request,
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/collections/{}{}", self.endpoint, self.collection, path)
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Option<Value>, ThetaError> {
+        let response = self
+            .request(request)
+            .send()
+            .await
+            .map_err(|e| ThetaError::search_request("Qdrant request failed", e))?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ThetaError::Search {
+                message: format!("Qdrant returned {}: {}", status, body),
+                retryable: status.is_server_error(),
+            });
+        }
+
+        let json = response
+            .json()
+            .await
+            .map_err(|e| ThetaError::search_request("Failed to parse Qdrant response", e))?;
+        Ok(Some(json))
+    }
+
+    async fn ensure_collection(&self, dimensions: usize) -> Result<(), ThetaError> {
+        self.created
+            .get_or_try_init(|| async {
+                if self.send(self.client.get(self.url(""))).await?.is_some() {
+                    return Ok(());
+                }
+
+                self.send(self.client.put(self.url("")).json(&json!({
+                    "vectors": { "size": dimensions, "distance": "Cosine" }
+                })))
+                .await?;
+                // Deleting and filtering go by these fields
+                for field in ["url", "source"] {
+                    self.send(self.client.put(self.url("/index?wait=true")).json(&json!({
+                        "field_name": field,
+                        "field_schema": "keyword"
+                    })))
+                    .await?;
+                }
+                log::info!("Created Qdrant collection '{}' with {} dimensions", self.collection, dimensions);
+                Ok(())
+            })
+            .await
+            .map(|_| ())
+    }
+}
+
+#[async_trait]
+impl VectorStore for QdrantVectorStore {
+    fn name(&self) -> &str {
+        "qdrant"
+    }
+
+    async fn upsert(&self, points: Vec<VectorPoint>) -> Result<(), ThetaError> {
+        let Some(first) = points.first() else { return Ok(()) };
+        self.ensure_collection(first.vector.len()).await?;
+
+        let points: Vec<Value> = points
+            .into_iter()
+            .map(|point| json!({ "id": point.id, "vector": point.vector, "payload": point.payload }))
+            .collect();
+        self.send(self.client.put(self.url("/points?wait=true")).json(&json!({ "points": points })))
+            .await?
+            .ok_or_else(|| ThetaError::search(format!("Qdrant collection '{}' not found", self.collection)))?;
+
+        Ok(())
+    }
+
+    async fn delete_url(&self, url: &str) -> Result<(), ThetaError> {
+        // A missing collection has nothing to delete
+        self.send(self.client.post(self.url("/points/delete?wait=true")).json(&json!({
+            "filter": { "must": [{ "key": "url", "match": { "value": url } }] }
+        })))
+        .await?;
+
+        Ok(())
+    }
+
+    async fn search(
+        &self,
+        vector: &[f32],
+        limit: usize,
+        source: Option<DocumentSource>,
+    ) -> Result<Vec<ScoredPoint>, ThetaError> {
+        let mut body = json!({ "vector": vector, "limit": limit, "with_payload": true });
+        if let Some(source) = source {
+            body["filter"] = json!({ "must": [{ "key": "source", "match": { "value": source.as_str() } }] });
+        }
+
+        let Some(json) = self.send(self.client.post(self.url("/points/search")).json(&body)).await? else {
+            return Ok(Vec::new());
+        };
+
+        let results = json["result"].as_array().cloned().unwrap_or_default();
+        Ok(results
+            .into_iter()
+            .filter_map(|result| {
+                let id = result["id"].as_str().and_then(|id| Uuid::parse_str(id).ok())?;
+                let payload: ChunkPayload = serde_json::from_value(result["payload"].clone()).ok()?;
+                Some(ScoredPoint { id, score: result["score"].as_f64().unwrap_or(0.0) as f32, payload })
+            })
+            .collect())
+    }
+
+    async fn count(&self) -> Result<usize, ThetaError> {
+        let json = self.send(self.client.post(self.url("/points/count")).json(&json!({ "exact": true }))).await?;
+        Ok(json.and_then(|json| json["result"]["count"].as_u64()).unwrap_or(0) as usize)
+    }
+
+    fn is_local(&self) -> bool {
+        is_local_endpoint(&self.endpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collection_names_are_per_model() {
+        assert_eq!(QdrantVectorStore::collection_for("text-embedding-3-small"), "theta_pages_text_embedding_3_small");
+        assert_ne!(
+            QdrantVectorStore::collection_for("nomic-embed-text"),
+            QdrantVectorStore::collection_for("text-embedding-3-small")
+        );
+    }
+}
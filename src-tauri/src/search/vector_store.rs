@@ -0,0 +1,74 @@
+use crate::error::ThetaError;
+use crate::models::DocumentSource;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// What is stored alongside each vector: the chunk of text it was made from
+/// and the document that chunk belongs to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkPayload {
+    pub url: String,
+    pub title: String,
+    pub source: DocumentSource,
+    /// Position of the chunk within the document.
+    pub chunk: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct VectorPoint {
+    pub id: Uuid,
+    pub vector: Vec<f32>,
+    pub payload: ChunkPayload,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScoredPoint {
+    pub id: Uuid,
+    /// Cosine similarity to the query, from -1 to 1.
+    pub score: f32,
+    pub payload: ChunkPayload,
+}
+
+/// Storage for embedded chunks with nearest-neighbour search.
+#[async_trait]
+pub trait VectorStore: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Adds points, replacing any with the same id.
+    async fn upsert(&self, points: Vec<VectorPoint>) -> Result<(), ThetaError>;
+
+    /// Removes every chunk of the document at `url`.
+    async fn delete_url(&self, url: &str) -> Result<(), ThetaError>;
+
+    /// The `limit` chunks closest to `vector`, best first, optionally only
+    /// those from one kind of document.
+    async fn search(
+        &self,
+        vector: &[f32],
+        limit: usize,
+        source: Option<DocumentSource>,
+    ) -> Result<Vec<ScoredPoint>, ThetaError>;
+
+    async fn count(&self) -> Result<usize, ThetaError>;
+
+    /// Whether stored text stays on this machine or the local network.
+    fn is_local(&self) -> bool;
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm_a = a.iter().map(|value| value * value).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|value| value * value).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
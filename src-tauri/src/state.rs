@@ -1,17 +1,23 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 use sqlx::PgPool;
 use redis::Client as RedisClient;
 use reqwest::Client as HttpClient;
-use tokio::sync::RwLock;
+use tokio::sync::{oneshot, RwLock};
+use tokio::task::AbortHandle;
 use uuid::Uuid;
+use crate::ai::cache::ResponseCache;
+use crate::ai::prompts::PromptRegistry;
+use crate::ai::recorder::RunRecorder;
+use crate::ai::usage::UsageLedger;
+use crate::ai::AIServiceManager;
+use crate::error::ThetaError;
+use crate::search::SemanticIndex;
 
-#[derive(Clone)]
-pub struct AIService {
-    pub service_type: String,
-    pub endpoint: String,
-    pub api_key: Option<String>,
-    pub model: String,
-}
+/// `user_data` category holding settings that must survive a restart.
+pub const PREFERENCES_CATEGORY: &str = "preference";
+pub const PRIVACY_MODE: &str = "privacy_mode";
+pub const AI_PROVIDER_PRIORITY: &str = "ai_provider_priority";
 
 #[derive(Clone)]
 pub struct Integration {
@@ -25,10 +31,18 @@ pub struct AppState {
     pub database: Option<PgPool>,
     pub redis: Option<RedisClient>,
     pub http_client: HttpClient,
-    pub ai_services: HashMap<String, AIService>,
+    pub ai_services: AIServiceManager,
+    /// Cached analyses and summaries; in memory only until Redis is configured.
+    pub ai_cache: ResponseCache,
+    pub prompts: PromptRegistry,
+    /// Embedded chunks of visited pages and bookmarks for `semantic_search`.
+    pub semantic_index: SemanticIndex,
     pub integrations: HashMap<String, Integration>,
     pub active_tabs: HashMap<Uuid, TabState>,
     pub user_preferences: HashMap<String, String>,
+    pub ai_streams: HashMap<String, AbortHandle>,
+    /// Agent tool calls waiting for the user, by confirmation id.
+    pub tool_confirmations: HashMap<String, oneshot::Sender<bool>>,
 }
 
 #[derive(Clone)]
@@ -47,14 +61,19 @@ impl AppState {
             database: None,
             redis: None,
             http_client: HttpClient::new(),
-            ai_services: HashMap::new(),
+            ai_services: AIServiceManager::new(),
+            ai_cache: ResponseCache::new(None),
+            prompts: PromptRegistry::new(),
+            semantic_index: SemanticIndex::in_memory(),
             integrations: HashMap::new(),
             active_tabs: HashMap::new(),
             user_preferences: HashMap::new(),
+            ai_streams: HashMap::new(),
+            tool_confirmations: HashMap::new(),
         }
     }
 
-    pub async fn initialize_database(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn initialize_database(&mut self) -> Result<(), ThetaError> {
         let database_url = std::env::var("DATABASE_URL")
             .unwrap_or_else(|_| "postgresql://localhost/theta_browser".to_string());
         
@@ -64,38 +83,123 @@ impl AppState {
         let redis_url = std::env::var("REDIS_URL")
             .unwrap_or_else(|_| "redis://localhost".to_string());
         
-        let redis_client = RedisClient::open(redis_url)?;
+        let redis_client = RedisClient::open(redis_url)
+            .map_err(|e| ThetaError::NotConfigured(format!("Invalid REDIS_URL: {}", e)))?;
+        self.ai_cache = ResponseCache::new(Some(redis_client.clone()));
         self.redis = Some(redis_client);
         
         Ok(())
     }
 
-    pub async fn initialize_ai_services(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let openai_key = std::env::var("OPENAI_API_KEY").ok();
-        let anthropic_key = std::env::var("ANTHROPIC_API_KEY").ok();
+    /// Loads the preferences saved in `user_data`. Runs before
+    /// `initialize_ai_services`, which applies the AI ones.
+    pub async fn initialize_preferences(&mut self) -> Result<(), ThetaError> {
+        if let Some(pool) = &self.database {
+            let rows: Vec<(String, serde_json::Value)> = sqlx::query_as(
+                "SELECT key, value FROM user_data WHERE category = $1",
+            )
+            .bind(PREFERENCES_CATEGORY)
+            .fetch_all(pool)
+            .await?;
+            self.load_preferences(rows);
+        }
         
-        if let Some(key) = openai_key {
-            self.ai_services.insert("openai".to_string(), AIService {
-                service_type: "openai".to_string(),
-                endpoint: "https://api.openai.com/v1".to_string(),
-                api_key: Some(key),
-                model: "gpt-4".to_string(),
-            });
+        Ok(())
+    }
+
+    /// Sets a preference for this session and saves it for the next ones.
+    pub async fn set_preference(&mut self, key: &str, value: String) -> Result<(), ThetaError> {
+        if let Some(pool) = &self.database {
+            sqlx::query(
+                "INSERT INTO user_data (key, value, category, created_at, updated_at)
+                 VALUES ($1, $2, $3, NOW(), NOW())
+                 ON CONFLICT (key, category)
+                 DO UPDATE SET value = EXCLUDED.value, updated_at = EXCLUDED.updated_at",
+            )
+            .bind(key)
+            .bind(serde_json::Value::String(value.clone()))
+            .bind(PREFERENCES_CATEGORY)
+            .execute(pool)
+            .await?;
         }
+        self.user_preferences.insert(key.to_string(), value);
         
-        if let Some(key) = anthropic_key {
-            self.ai_services.insert("anthropic".to_string(), AIService {
-                service_type: "anthropic".to_string(),
-                endpoint: "https://api.anthropic.com/v1".to_string(),
-                api_key: Some(key),
-                model: "claude-3-sonnet-20240229".to_string(),
-            });
+        Ok(())
+    }
+
+    fn load_preferences(&mut self, rows: Vec<(String, serde_json::Value)>) {
+        for (key, value) in rows {
+            let value = match value {
+                serde_json::Value::String(value) => value,
+                other => other.to_string(),
+            };
+            self.user_preferences.insert(key, value);
+        }
+    }
+
+    pub async fn initialize_ai_services(&mut self) -> Result<(), ThetaError> {
+        self.ai_services.providers.register_default_providers(&self.http_client);
+        self.ai_services.providers.discover_local_providers(&self.http_client).await;
+        self.ai_services.ledger = self.database.clone().map(UsageLedger::new);
+        self.apply_ai_preferences();
+        
+        Ok(())
+    }
+
+    fn apply_ai_preferences(&mut self) {
+        self.ai_services.privacy_mode = self.user_preferences
+            .get(PRIVACY_MODE)
+            .is_some_and(|value| value == "true");
+        self.ai_services.priority = self.user_preferences
+            .get(AI_PROVIDER_PRIORITY)
+            .cloned()
+            .or_else(|| std::env::var("AI_PROVIDER_PRIORITY").ok())
+            .map(|value| {
+                value.split(',')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
+    /// Loads prompt templates from `PROMPT_TEMPLATES_DIR`, or from
+    /// `default_directory` when it is unset, then applies `user_data` overrides.
+    pub async fn initialize_prompts(&mut self, default_directory: Option<PathBuf>) -> Result<(), ThetaError> {
+        let directory = std::env::var("PROMPT_TEMPLATES_DIR")
+            .ok()
+            .map(PathBuf::from)
+            .or(default_directory);
+        
+        self.prompts = PromptRegistry::load(directory, self.database.as_ref()).await;
+        
+        Ok(())
+    }
+
+    /// Logs every provider call under `AI_RUN_LOG_DIR`, or `default_directory`
+    /// when it is unset. `AI_RUN_LOG=off` turns the log off.
+    pub async fn initialize_run_log(&mut self, default_directory: Option<PathBuf>) -> Result<(), ThetaError> {
+        if std::env::var("AI_RUN_LOG").is_ok_and(|value| value == "off") {
+            return Ok(());
         }
+        let directory = std::env::var("AI_RUN_LOG_DIR")
+            .ok()
+            .map(PathBuf::from)
+            .or(default_directory)
+            .ok_or_else(|| ThetaError::NotConfigured("No directory for the AI run log".to_string()))?;
+        
+        self.ai_services.recorder = Some(RunRecorder::open(directory).await?);
         
         Ok(())
     }
 
-    pub async fn initialize_integrations(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub async fn initialize_semantic_index(&mut self) -> Result<(), ThetaError> {
+        self.semantic_index = SemanticIndex::from_env(&self.http_client).await?;
+        
+        Ok(())
+    }
+
+    pub async fn initialize_integrations(&mut self) -> Result<(), ThetaError> {
         let n8n_endpoint = std::env::var("N8N_ENDPOINT").ok();
         let n8n_token = std::env::var("N8N_API_KEY").ok();
         
@@ -137,7 +241,39 @@ impl AppState {
         }
     }
 
+    pub fn update_tab_context(&mut self, id: &Uuid, ai_context: String) {
+        if let Some(tab) = self.active_tabs.get_mut(id) {
+            tab.ai_context = Some(ai_context);
+            tab.last_updated = chrono::Utc::now();
+        }
+    }
+
     pub fn remove_tab(&mut self, id: &Uuid) {
         self.active_tabs.remove(id);
     }
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn saved_ai_preferences_are_applied_on_start() {
+        let mut state = AppState::new();
+        state.load_preferences(vec![
+            (PRIVACY_MODE.to_string(), json!("true")),
+            (AI_PROVIDER_PRIORITY.to_string(), json!("ollama, openai")),
+            ("theme".to_string(), json!({ "dark": true })),
+        ]);
+        state.apply_ai_preferences();
+
+        assert!(state.ai_services.privacy_mode);
+        assert_eq!(state.ai_services.priority, vec!["ollama", "openai"]);
+        assert_eq!(state.user_preferences["theme"], r#"{"dark":true}"#);
+
+        state.load_preferences(vec![(PRIVACY_MODE.to_string(), json!("false"))]);
+        state.apply_ai_preferences();
+        assert!(!state.ai_services.privacy_mode);
+    }
+}
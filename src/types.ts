@@ -85,13 +85,31 @@ export interface UserPreferences {
   customCss?: string;
 }
 
+export type LinkKind = "internal" | "external" | "anchor" | "download" | "mailto" | "other";
+
+export interface PageLink {
+  url: string;
+  text: string;
+  kind: LinkKind;
+  nofollow: boolean;
+}
+
+export interface PageImage {
+  url: string;
+  alt?: string;
+}
+
 export interface WebPageContent {
   url: string;
   title: string;
   content: string;
+  markdown?: string;
   html: string;
-  links: string[];
-  images: string[];
+  links: PageLink[];
+  images: PageImage[];
   metadata: any;
+  byline?: string;
+  publishedAt?: Date;
+  leadImage?: string;
   extractedAt: Date;
 } 
\ No newline at end of file